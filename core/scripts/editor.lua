@@ -109,6 +109,27 @@ function Editor:edit(client_id, name, scratch)
     self.core:edit(client_id, name, scratch)
 end
 
+---@param client_id integer
+---@param buffer string|nil
+---@param force boolean
+---@return integer
+function Editor:write(client_id, buffer, force)
+    return self.core:write(client_id, buffer, force or false)
+end
+
+---@param force boolean
+---@return integer
+function Editor:write_all(force)
+    return self.core:write_all(force or false)
+end
+
+---@param client_id integer
+---@param line_ending string
+---@return integer
+function Editor:set_line_ending(client_id, line_ending)
+    return self.core:set_line_ending(client_id, line_ending)
+end
+
 function Editor:append_to(buffer, text)
     self.core:append_to(buffer, text)
 end
@@ -183,8 +204,456 @@ function Editor:move_to_end(client_id, extend)
 end
 
 ---@param client_id integer
-function Editor:delete_selection(client_id)
-    return self.core:delete_selection(client_id)
+---@param extend boolean
+function Editor:move_to_first_non_blank(client_id, extend)
+    return self.core:move_to_first_non_blank(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_next_word_start(client_id, extend)
+    return self.core:move_next_word_start(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_next_word_end(client_id, extend)
+    return self.core:move_next_word_end(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_previous_word_start(client_id, extend)
+    return self.core:move_previous_word_start(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_next_big_word_start(client_id, extend)
+    return self.core:move_next_big_word_start(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_next_big_word_end(client_id, extend)
+    return self.core:move_next_big_word_end(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_previous_big_word_start(client_id, extend)
+    return self.core:move_previous_big_word_start(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_next_paragraph(client_id, extend)
+    return self.core:move_next_paragraph(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_previous_paragraph(client_id, extend)
+    return self.core:move_previous_paragraph(client_id, extend)
+end
+
+---@param client_id integer
+---@param extend boolean
+function Editor:move_to_matching_bracket(client_id, extend)
+    return self.core:move_to_matching_bracket(client_id, extend)
+end
+
+---@param client_id integer
+---@param object string "parentheses", "brackets", "braces", "angle_brackets", "double_quotes",
+---"single_quotes", "backquotes", "word", "big_word", "paragraph" or "indent"
+---@param inner boolean leave out the delimiters and surrounding blanks
+---@return boolean
+function Editor:select_object(client_id, object, inner)
+    return self.core:select_object(client_id, object, inner)
+end
+
+---@param chars string characters making words along with the alphanumeric ones
+function Editor:set_word_chars(chars)
+    self.core:set_word_chars(chars)
+end
+
+---@param client_id integer
+---@param pattern string
+---@param backward boolean
+---@param extend boolean
+---@param case string|nil "smart" (default), "sensitive" or "insensitive"
+---@return boolean
+function Editor:search(client_id, pattern, backward, extend, case)
+    return self.core:search(client_id, pattern, backward or false, extend or false, case)
+end
+
+---@param client_id integer
+---@param backward boolean
+---@param extend boolean
+---@return boolean
+function Editor:search_next(client_id, backward, extend)
+    return self.core:search_next(client_id, backward or false, extend or false)
+end
+
+---@param client_id integer
+function Editor:clear_search(client_id)
+    self.core:clear_search(client_id)
+end
+
+---@param client_id integer
+---@param pattern string
+---@param case string|nil
+---@return integer
+function Editor:select_matches(client_id, pattern, case)
+    return self.core:select_matches(client_id, pattern, case)
+end
+
+---@param client_id integer
+---@param pattern string
+---@param case string|nil
+---@return integer
+function Editor:keep_matching(client_id, pattern, case)
+    return self.core:keep_matching(client_id, pattern, true, case)
+end
+
+---@param client_id integer
+---@param pattern string
+---@param case string|nil
+---@return integer
+function Editor:remove_matching(client_id, pattern, case)
+    return self.core:keep_matching(client_id, pattern, false, case)
+end
+
+---@param client_id integer
+---@return integer
+function Editor:split_lines(client_id)
+    return self.core:split_lines(client_id)
+end
+
+---@param client_id integer
+---@param pattern string
+---@param case string|nil
+---@return integer
+function Editor:split_matching(client_id, pattern, case)
+    return self.core:split_matching(client_id, pattern, case)
+end
+
+---@param client_id integer
+---@param above boolean
+---@return boolean
+function Editor:duplicate_selections(client_id, above)
+    return self.core:duplicate_selections(client_id, above)
+end
+
+---@param client_id integer
+---@param backward boolean
+function Editor:rotate_selections(client_id, backward)
+    self.core:rotate_selections(client_id, backward)
+end
+
+---@param client_id integer
+function Editor:keep_main_selection(client_id)
+    self.core:keep_main_selection(client_id)
+end
+
+---@param client_id integer
+function Editor:drop_main_selection(client_id)
+    self.core:drop_main_selection(client_id)
+end
+
+--- Shows lines `first` to `last` of the buffer in the current view of the client.
+---@param client_id integer
+---@param buffer string
+---@param first integer
+---@param last integer
+function Editor:add_lines_to_view(client_id, buffer, first, last)
+    self.core:add_lines_to_view(client_id, buffer, first, last)
+end
+
+---@param client_id integer
+---@param pattern string
+---@param case string|nil
+---@return integer
+function Editor:grep(client_id, pattern, case)
+    return self.core:grep(client_id, pattern, case)
+end
+
+---@param client_id integer
+---@param register string|nil register getting the deleted text, "_" to drop it
+---@return string[]
+function Editor:delete_selection(client_id, register)
+    return self.core:delete_selection(client_id, register)
+end
+
+---@param client_id integer
+---@param register string|nil
+---@return integer
+function Editor:yank(client_id, register)
+    return self.core:yank(client_id, register)
+end
+
+---@param client_id integer
+---@param register string|nil
+---@return boolean
+function Editor:paste_before(client_id, register)
+    return self.core:paste_before(client_id, register)
+end
+
+---@param client_id integer
+---@param register string|nil
+---@return boolean
+function Editor:paste_after(client_id, register)
+    return self.core:paste_after(client_id, register)
+end
+
+---@param client_id integer
+---@param register string|nil
+---@return boolean
+function Editor:replace_with_register(client_id, register)
+    return self.core:paste_replace(client_id, register)
+end
+
+---@param name string|nil
+---@return string[]
+function Editor:register(name)
+    return self.core:register(name)
+end
+
+---@param name string|nil
+---@param values string[]
+function Editor:set_register(name, values)
+    self.core:set_register(name, values)
+end
+
+---@param client_id integer
+---@param register string|nil register the keys go to, "@" if nil
+function Editor:start_recording(client_id, register)
+    self.core:start_recording(client_id, register)
+end
+
+---@param client_id integer
+---@return integer|nil number of keys recorded, nil if the client was not recording
+function Editor:stop_recording(client_id)
+    return self.core:stop_recording(client_id)
+end
+
+---@param client_id integer
+---@return string|nil register the keys of the client are recorded in
+function Editor:recording(client_id)
+    return self.core:recording(client_id)
+end
+
+--- Replays the keys recorded in the register as a single change.
+---@param client_id integer
+---@param register string|nil "@" if nil
+---@param count integer|nil number of times to replay the macro, once if nil or 0
+---@return boolean false if there was nothing to replay
+function Editor:replay_macro(client_id, register, count)
+    local client = self.clients[client_id]
+    -- a macro replaying itself would never end
+    if client.replaying then
+        return false
+    end
+    local keys = self.inner:macro_keys(register)
+    if #keys == 0 then
+        return false
+    end
+    client.replaying = true
+    self.core:begin_undo_group(client_id)
+    local ok, err = pcall(function()
+        for _ = 1, math.max(count or 1, 1) do
+            for _, key in ipairs(keys) do
+                client.key_handler:handle(key)
+            end
+        end
+    end)
+    self.core:end_undo_group(client_id)
+    client.replaying = false
+    if not ok then
+        error(err, 0)
+    end
+    return true
+end
+
+--- Marks the main cursor of each buffer in the view of the client.
+---@param client_id integer
+---@param name string a single character
+---@return boolean
+function Editor:set_mark(client_id, name)
+    return self.core:set_mark(client_id, name)
+end
+
+---@param client_id integer
+---@param name string
+---@param extend boolean|nil
+---@return boolean false if no buffer in the view has the mark
+function Editor:goto_mark(client_id, name, extend)
+    return self.core:goto_mark(client_id, name, extend or false)
+end
+
+---@param buffer string
+---@return table<string, integer> offset of each mark
+function Editor:marks(buffer)
+    return self.core:marks(buffer)
+end
+
+---@param client_id integer
+---@return boolean false if there is no older position in the jump list
+function Editor:jump_back(client_id)
+    return self.core:jump_back(client_id)
+end
+
+---@param client_id integer
+---@return boolean false if there is no newer position in the jump list
+function Editor:jump_forward(client_id)
+    return self.core:jump_forward(client_id)
+end
+
+---@param client_id integer
+---@param lines integer lines to scroll the view by, up if negative
+function Editor:scroll(client_id, lines)
+    self.core:scroll(client_id, lines)
+end
+
+---@param client_id integer
+---@param pages integer times the lines shown to scroll the view by, up if negative
+function Editor:scroll_pages(client_id, pages)
+    self.core:scroll_pages(client_id, pages)
+end
+
+---@param client_id integer
+function Editor:center_cursor(client_id)
+    self.core:center_cursor(client_id)
+end
+
+---@param client_id integer
+---@param columns integer columns to scroll the lines by, left if negative, when not wrapped
+function Editor:scroll_columns(client_id, columns)
+    self.core:scroll_columns(client_id, columns)
+end
+
+---@param client_id integer
+---@param wrap string "none" to scroll long lines horizontally, "char" or "word" to wrap them
+function Editor:set_wrap(client_id, wrap)
+    self.core:set_wrap(client_id, wrap)
+end
+
+---@param width integer columns between tab stops
+function Editor:set_tab_width(width)
+    self.core:set_tab_width(width)
+end
+
+---@param path string JSON file holding the name, file extensions and rules of a grammar
+---@return string name of the grammar
+function Editor:load_grammar(path)
+    return self.core:load_grammar(path)
+end
+
+---@param buffer string
+---@param name string|nil grammar highlighting the buffer, nil for none
+function Editor:set_grammar(buffer, name)
+    self.core:set_grammar(buffer, name)
+end
+
+---@return string[] names of the grammars loaded
+function Editor:grammars()
+    return self.core:grammars()
+end
+
+---@param path string TOML file giving the style of the faces, named after the file
+---@return string name of the theme
+function Editor:load_theme(path)
+    return self.core:load_theme(path)
+end
+
+---@param client_id integer
+---@param name string
+function Editor:set_theme(client_id, name)
+    self.core:set_theme(client_id, name)
+end
+
+---@return string[] names of the themes loaded
+function Editor:themes()
+    return self.core:themes()
+end
+
+---@param copy string|nil shell command copying its input to the system clipboard
+---@param paste string|nil shell command printing the system clipboard, nil to disable it
+function Editor:set_clipboard(copy, paste)
+    self.core:set_clipboard(copy, paste)
+end
+
+---@param client_id integer
+function Editor:delete_before(client_id)
+    return self.core:delete_before(client_id)
+end
+
+---@param client_id integer
+---@return boolean
+function Editor:undo(client_id)
+    return self.core:undo(client_id)
+end
+
+---@param client_id integer
+---@return boolean
+function Editor:redo(client_id)
+    return self.core:redo(client_id)
+end
+
+---@param client_id integer
+---@param seconds integer
+---@return boolean
+function Editor:earlier(client_id, seconds)
+    return self.core:earlier(client_id, seconds)
+end
+
+---@param client_id integer
+---@param seconds integer
+---@return boolean
+function Editor:later(client_id, seconds)
+    return self.core:later(client_id, seconds)
+end
+
+---@param client_id integer
+---@param buffer string
+---@param revision integer
+---@return boolean
+function Editor:goto_revision(client_id, buffer, revision)
+    return self.core:goto_revision(client_id, buffer, revision)
+end
+
+---@param client_id integer
+function Editor:begin_undo_group(client_id)
+    self.core:begin_undo_group(client_id)
+end
+
+---@param client_id integer
+function Editor:end_undo_group(client_id)
+    self.core:end_undo_group(client_id)
+end
+
+---@param client_id integer
+---@param text string
+function Editor:insert_text(client_id, text)
+    return self.core:insert_text(client_id, text)
+end
+
+---@param client_id integer
+---@param text string
+function Editor:insert_before(client_id, text)
+    return self.core:insert_before(client_id, text)
+end
+
+---@param client_id integer
+---@param text string
+function Editor:insert_after(client_id, text)
+    return self.core:insert_after(client_id, text)
+end
+
+---@param client_id integer
+---@param text string
+function Editor:replace_selection(client_id, text)
+    return self.core:replace_selection(client_id, text)
 end
 
 ---@class Editor
@@ -9,6 +9,14 @@ setmetatable(ModalHandler, {
     end,
 })
 
+---@param method string name of an editor motion
+---@param extend boolean
+local function make_motion(method, extend)
+    return function(mh)
+        return editor[method](editor, mh.client_id, extend)
+    end
+end
+
 ---@param extend boolean
 local function make_goto_mappings(extend)
     return {
@@ -26,12 +34,103 @@ local function make_goto_mappings(extend)
                 return editor:move_to_line_end(mh.client_id, extend)
             end,
         },
+        ["i"] = {
+            desc = "first non-blank",
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:move_to_first_non_blank(mh.client_id, extend)
+            end,
+        },
+        ["n"] = {
+            desc = "next paragraph",
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:move_next_paragraph(mh.client_id, extend)
+            end,
+        },
+        ["p"] = {
+            desc = "previous paragraph",
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:move_previous_paragraph(mh.client_id, extend)
+            end,
+        },
+        ["e"] = {
+            desc = "word end",
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:move_next_word_end(mh.client_id, extend)
+            end,
+        },
+        ["a-e"] = {
+            desc = "WORD end",
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:move_next_big_word_end(mh.client_id, extend)
+            end,
+        },
+        ["m"] = {
+            desc = "matching bracket",
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:move_to_matching_bracket(mh.client_id, extend)
+            end,
+        },
         default = function(mh, key)
             mh:exit_mode()
         end,
     }
 end
 
+-- keys selecting text objects, with the name the editor knows them by
+local objects = {
+    { key = "b", object = "parentheses", desc = "parentheses" },
+    { key = "r", object = "brackets", desc = "brackets" },
+    { key = "s-b", object = "braces", desc = "braces" },
+    { key = "a", object = "angle_brackets", desc = "angle brackets" },
+    { key = "s-q", object = "double_quotes", desc = "double quotes" },
+    { key = "q", object = "single_quotes", desc = "single quotes" },
+    { key = "g", object = "backquotes", desc = "backquotes" },
+    { key = "w", object = "word", desc = "word" },
+    { key = "s-w", object = "big_word", desc = "WORD" },
+    { key = "p", object = "paragraph", desc = "paragraph" },
+    { key = "i", object = "indent", desc = "indentation block" },
+}
+
+---@param inner boolean
+local function make_object_mappings(inner)
+    local mappings = {
+        default = function(mh, key)
+            mh:exit_mode()
+        end,
+    }
+    for _, o in ipairs(objects) do
+        mappings[o.key] = {
+            desc = o.desc,
+            fn = function(mh)
+                mh:exit_mode()
+                return editor:select_object(mh.client_id, o.object, inner)
+            end,
+        }
+    end
+    return mappings
+end
+
+---@param title string
+---@param action fun(mh: table, text: string)
+local function make_prompt(title, action)
+    return function(mh)
+        mh:prompt(title, action)
+    end
+end
+
+---@param backward boolean
+local function search_prompt(backward)
+    return make_prompt(backward and "?" or "/", function(mh, text)
+        editor:search(mh.client_id, text, backward, false)
+    end)
+end
+
 ModalHandler.modes = {
     normal = {
         name = "N",
@@ -39,9 +138,20 @@ ModalHandler.modes = {
         mappings = {
             ["i"] = {
                 fn = function(mh)
+                    editor:begin_undo_group(mh.client_id)
                     mh:enter_mode("insertion")
                 end,
             },
+            ["u"] = {
+                fn = function(mh)
+                    editor:undo(mh.client_id)
+                end,
+            },
+            ["s-u"] = {
+                fn = function(mh)
+                    editor:redo(mh.client_id)
+                end,
+            },
             ["g"] = {
                 fn = function(mh)
                     mh:enter_mode("moveto")
@@ -92,6 +202,14 @@ ModalHandler.modes = {
                     return editor:move_right(mh.client_id, true)
                 end,
             },
+            ["w"] = { fn = make_motion("move_next_word_start", false) },
+            ["b"] = { fn = make_motion("move_previous_word_start", false) },
+            ["s-w"] = { fn = make_motion("move_next_word_start", true) },
+            ["s-b"] = { fn = make_motion("move_previous_word_start", true) },
+            ["a-w"] = { fn = make_motion("move_next_big_word_start", false) },
+            ["a-b"] = { fn = make_motion("move_previous_big_word_start", false) },
+            ["a-s-w"] = { fn = make_motion("move_next_big_word_start", true) },
+            ["a-s-b"] = { fn = make_motion("move_previous_big_word_start", true) },
             ["m"] = {
                 fn = function(mh)
                     editor:message(mh.client_id, "hello!")
@@ -104,19 +222,302 @@ ModalHandler.modes = {
             },
             ["d"] = {
                 fn = function(mh)
-                    editor:delete_selection(mh.client_id)
+                    editor:delete_selection(mh.client_id, mh:take_register())
                 end,
             },
+            ["a-d"] = {
+                fn = function(mh)
+                    mh:take_register()
+                    editor:delete_selection(mh.client_id, "_")
+                end,
+            },
+            ["y"] = {
+                fn = function(mh)
+                    editor:yank(mh.client_id, mh:take_register())
+                end,
+            },
+            ["p"] = {
+                fn = function(mh)
+                    editor:paste_after(mh.client_id, mh:take_register())
+                end,
+            },
+            ["s-p"] = {
+                fn = function(mh)
+                    editor:paste_before(mh.client_id, mh:take_register())
+                end,
+            },
+            ["s-r"] = {
+                fn = function(mh)
+                    editor:replace_with_register(mh.client_id, mh:take_register())
+                end,
+            },
+            ["s-q"] = {
+                fn = function(mh)
+                    if editor:recording(mh.client_id) then
+                        editor:stop_recording(mh.client_id)
+                    else
+                        editor:start_recording(mh.client_id, mh:take_register())
+                    end
+                end,
+            },
+            ["q"] = {
+                fn = function(mh)
+                    local register = mh:take_register()
+                    if not editor:replay_macro(mh.client_id, register) then
+                        editor:message(mh.client_id, "no macro in register " .. (register or "@"))
+                    end
+                end,
+            },
+            ['"'] = {
+                fn = function(mh)
+                    mh:enter_mode("register")
+                end,
+            },
+            ["a-m"] = {
+                fn = function(mh)
+                    mh:enter_mode("mark")
+                end,
+            },
+            ["'"] = {
+                fn = function(mh)
+                    mh:enter_mode("goto_mark")
+                end,
+            },
+            ["c-o"] = {
+                fn = function(mh)
+                    if not editor:jump_back(mh.client_id) then
+                        editor:message(mh.client_id, "no older jump")
+                    end
+                end,
+            },
+            ["c-i"] = {
+                fn = function(mh)
+                    if not editor:jump_forward(mh.client_id) then
+                        editor:message(mh.client_id, "no newer jump")
+                    end
+                end,
+            },
+            ["pgdn"] = {
+                fn = function(mh)
+                    editor:scroll_pages(mh.client_id, 1)
+                end,
+            },
+            ["pgup"] = {
+                fn = function(mh)
+                    editor:scroll_pages(mh.client_id, -1)
+                end,
+            },
+            ["c-l"] = {
+                fn = function(mh)
+                    editor:center_cursor(mh.client_id)
+                end,
+            },
+            ["/"] = {
+                fn = search_prompt(false),
+            },
+            ["a-/"] = {
+                fn = search_prompt(true),
+            },
+            ["n"] = {
+                fn = function(mh)
+                    editor:search_next(mh.client_id, false, false)
+                end,
+            },
+            ["s-n"] = {
+                fn = function(mh)
+                    editor:search_next(mh.client_id, true, false)
+                end,
+            },
+            ["a-n"] = {
+                fn = function(mh)
+                    editor:search_next(mh.client_id, false, true)
+                end,
+            },
+            ["esc"] = {
+                fn = function(mh)
+                    editor:clear_search(mh.client_id)
+                end,
+            },
+            ["s"] = {
+                fn = make_prompt("select: ", function(mh, text)
+                    editor:select_matches(mh.client_id, text)
+                end),
+            },
+            ["a-k"] = {
+                fn = make_prompt("keep: ", function(mh, text)
+                    editor:keep_matching(mh.client_id, text)
+                end),
+            },
+            ["a-s"] = {
+                fn = function(mh)
+                    editor:split_lines(mh.client_id)
+                end,
+            },
+            ["s-s"] = {
+                fn = make_prompt("split: ", function(mh, text)
+                    editor:split_matching(mh.client_id, text)
+                end),
+            },
+            ["s-c"] = {
+                fn = function(mh)
+                    editor:duplicate_selections(mh.client_id, false)
+                end,
+            },
+            ["a-s-c"] = {
+                fn = function(mh)
+                    editor:duplicate_selections(mh.client_id, true)
+                end,
+            },
+            [")"] = {
+                fn = function(mh)
+                    editor:rotate_selections(mh.client_id, false)
+                end,
+            },
+            ["("] = {
+                fn = function(mh)
+                    editor:rotate_selections(mh.client_id, true)
+                end,
+            },
+            [","] = {
+                fn = function(mh)
+                    editor:keep_main_selection(mh.client_id)
+                end,
+            },
+            ["a-,"] = {
+                fn = function(mh)
+                    editor:drop_main_selection(mh.client_id)
+                end,
+            },
+            ["a-i"] = {
+                fn = function(mh)
+                    mh:enter_mode("object_inner")
+                end,
+            },
+            ["a-a"] = {
+                fn = function(mh)
+                    mh:enter_mode("object_around")
+                end,
+            },
+            ["a-s-k"] = {
+                fn = make_prompt("remove: ", function(mh, text)
+                    editor:remove_matching(mh.client_id, text)
+                end),
+            },
+        },
+    },
+    register = {
+        name = "R",
+        title = "",
+        mappings = {
+            ["esc"] = {
+                fn = function(mh)
+                    mh:exit_mode()
+                end,
+            },
+            default = function(mh, key)
+                mh:exit_mode()
+                if key.text and not key.ctrl and not key.alt then
+                    mh.register = key.text
+                end
+            end,
+        },
+    },
+    mark = {
+        name = "M",
+        title = "",
+        mappings = {
+            ["esc"] = {
+                fn = function(mh)
+                    mh:exit_mode()
+                end,
+            },
+            default = function(mh, key)
+                mh:exit_mode()
+                if key.text and not key.ctrl and not key.alt then
+                    editor:set_mark(mh.client_id, key.text)
+                end
+            end,
+        },
+    },
+    goto_mark = {
+        name = "M",
+        title = "",
+        mappings = {
+            ["esc"] = {
+                fn = function(mh)
+                    mh:exit_mode()
+                end,
+            },
+            default = function(mh, key)
+                mh:exit_mode()
+                if key.text and not key.ctrl and not key.alt then
+                    if not editor:goto_mark(mh.client_id, key.text) then
+                        editor:message(mh.client_id, "no mark " .. key.text)
+                    end
+                end
+            end,
+        },
+    },
+    prompt = {
+        name = "P",
+        title = "",
+        mappings = {
+            ["esc"] = {
+                fn = function(mh)
+                    mh.pending_prompt = nil
+                    mh:exit_mode()
+                end,
+            },
+            ["ret"] = {
+                fn = function(mh)
+                    local prompt = mh.pending_prompt
+                    mh.pending_prompt = nil
+                    mh:exit_mode()
+                    prompt.action(mh, prompt.text)
+                end,
+            },
+            ["bkspc"] = {
+                fn = function(mh)
+                    local prompt = mh.pending_prompt
+                    local last = utf8.offset(prompt.text, -1)
+                    if last then
+                        prompt.text = prompt.text:sub(1, last - 1)
+                    end
+                end,
+            },
+            default = function(mh, key)
+                if key.text and not key.ctrl and not key.alt then
+                    mh.pending_prompt.text = mh.pending_prompt.text .. key.text
+                end
+            end,
         },
     },
     insertion = {
         name = "I",
         title = "",
-        mappings = { ["esc"] = {
-            fn = function(mh)
-                mh:exit_mode()
+        mappings = {
+            ["esc"] = {
+                fn = function(mh)
+                    editor:end_undo_group(mh.client_id)
+                    mh:exit_mode()
+                end,
+            },
+            ["ret"] = {
+                fn = function(mh)
+                    editor:insert_text(mh.client_id, "\n")
+                end,
+            },
+            ["bkspc"] = {
+                fn = function(mh)
+                    editor:delete_before(mh.client_id)
+                end,
+            },
+            default = function(mh, key)
+                if key.text and not key.ctrl and not key.alt then
+                    editor:insert_text(mh.client_id, key.text)
+                end
             end,
-        } },
+        },
     },
     moveto = {
         name = "g",
@@ -130,6 +531,18 @@ ModalHandler.modes = {
         hint = true,
         mappings = make_goto_mappings(true),
     },
+    object_inner = {
+        name = "O",
+        title = "select inside",
+        hint = true,
+        mappings = make_object_mappings(true),
+    },
+    object_around = {
+        name = "O",
+        title = "select around",
+        hint = true,
+        mappings = make_object_mappings(false),
+    },
 }
 
 function ModalHandler.new(client_id)
@@ -148,6 +561,20 @@ function ModalHandler:exit_mode()
     table.remove(self.mode_stack)
 end
 
+---@param title string
+---@param action fun(mh: table, text: string)
+function ModalHandler:prompt(title, action)
+    self.pending_prompt = { title = title, text = "", action = action }
+    self:enter_mode("prompt")
+end
+
+-- register chosen for the next command, if any
+function ModalHandler:take_register()
+    local register = self.register
+    self.register = nil
+    return register
+end
+
 function ModalHandler:curmode()
     return self.mode_stack[#self.mode_stack]
 end
@@ -177,6 +604,16 @@ function ModalHandler:set_status(key)
     if not status_line.mode then
         status_line.mode = { index = 90 }
     end
+    if not status_line.prompt then
+        status_line.prompt = { index = 70 }
+    end
+    if not status_line.recording then
+        status_line.recording = { index = 60 }
+    end
+    local recording = editor:recording(self.client_id)
+    status_line.recording.text = recording and ("recording " .. recording) or ""
+    local prompt = self.pending_prompt
+    status_line.prompt.text = prompt and (prompt.title .. prompt.text) or ""
     status_line.keys.text = key and key.display or ""
     status_line.mode.text = self.modes[self:curmode()].name
 end
@@ -62,6 +62,13 @@ fn main() -> io::Result<()> {
                 .takes_value(true)
                 .help("Sets session name"),
         )
+        .arg(
+            Arg::with_name("RESTORE")
+                .short("r")
+                .long("restore")
+                .takes_value(true)
+                .help("Restores a saved session, also naming the session unless set"),
+        )
         .arg(
             Arg::with_name("MODE")
                 .short("m")
@@ -83,7 +90,8 @@ fn main() -> io::Result<()> {
         }
         Ok(())
     } else {
-        let session = match matches.value_of("SESSION") {
+        let restore = matches.value_of("RESTORE");
+        let session = match matches.value_of("SESSION").or(restore) {
             Some(name) => Session::from_name(name),
             None => Session::from_pid(),
         };
@@ -113,7 +121,7 @@ fn main() -> io::Result<()> {
             Mode::term => {
                 use ced::tui::Term;
                 ensure_session(&session)?;
-                Term::new(session, &filenames).start();
+                Term::new(session, restore, &filenames).start();
                 Ok(())
             }
         }
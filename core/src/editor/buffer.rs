@@ -1,10 +1,16 @@
+use std::collections::HashMap;
 use std::env::current_dir;
+use std::fmt;
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::ops::{Index, Range};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
+use crate::editor::core::Error;
+use crate::editor::encoding::{Encoding, LineEnding};
+use crate::editor::highlight::Highlighter;
+use crate::editor::piece_table::Edit;
 use crate::editor::selection::Selection;
 use crate::editor::view::Focus;
 use crate::editor::PieceTable;
@@ -14,7 +20,7 @@ fn find_uniq_name(path: &Path, acc: &str, path_set: &[PathBuf]) -> String {
     let tail = path.file_name().unwrap();
     let matches = path_set
         .iter()
-        .filter(|x| x.file_name().map_or(false, |x| x.to_str().unwrap() == tail))
+        .filter(|x| x.file_name().is_some_and(|x| x.to_str().unwrap() == tail))
         .count();
     let mut new_acc = tail.to_str().unwrap().to_owned();
     if !acc.is_empty() {
@@ -29,18 +35,73 @@ fn find_uniq_name(path: &Path, acc: &str, path_set: &[PathBuf]) -> String {
                 new_path_set.push(new_pb);
             }
         }
-        find_uniq_name(&head, &new_acc, &new_path_set)
+        find_uniq_name(head, &new_acc, &new_path_set)
     } else {
         new_acc
     }
 }
 
+/// Text of a file as edited in a buffer, always ending with `\n`, along with what is needed to
+/// write it back in its original format.
+struct FileContent {
+    text: String,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    final_newline: bool,
+}
+
+impl FileContent {
+    fn read(path: &Path) -> Result<FileContent, Error> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| Error::from_io(path, e))?;
+        let encoding = Encoding::detect(&bytes);
+        let decoded = encoding.decode(&bytes);
+        // with mixed line endings, the most used one is kept and the others are converted to
+        // it when saving
+        let (line_ending, mut text) = if encoding.is_binary() {
+            (LineEnding::Lf, decoded)
+        } else {
+            (
+                LineEnding::detect(&decoded),
+                LineEnding::normalize(&decoded),
+            )
+        };
+        let final_newline = text.ends_with('\n');
+        if !final_newline {
+            text.push('\n');
+        }
+        Ok(FileContent {
+            text,
+            encoding,
+            line_ending,
+            final_newline,
+        })
+    }
+
+    fn new() -> FileContent {
+        FileContent {
+            text: "\n".to_owned(),
+            encoding: Encoding::default(),
+            line_ending: LineEnding::default(),
+            final_newline: true,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum BufferSource {
     Scratch(String),
     File(PathBuf),
 }
 
+/// Time of the last modification of the file at `path`, used to tell if it changed since it
+/// was last read or written.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
 fn find_shortest_name(sources: &[BufferSource], idx: usize) -> String {
     let source = sources.index(idx);
     match *source {
@@ -60,8 +121,16 @@ fn find_shortest_name(sources: &[BufferSource], idx: usize) -> String {
 pub struct Buffer {
     pub source: BufferSource,
     pub content: PieceTable,
+    pub highlighter: Option<Highlighter>,
     last_sync: Option<SystemTime>,
     modified: bool,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    // whether the file ended with a line break, the buffer content always does
+    final_newline: bool,
+    // selections before the change leading to a revision, and when last leaving the revision
+    selections_before: HashMap<usize, Vec<Selection>>,
+    selections_after: HashMap<usize, Vec<Selection>>,
 }
 
 impl Buffer {
@@ -74,37 +143,97 @@ impl Buffer {
         Buffer {
             source: BufferSource::Scratch(name),
             content: PieceTable::with_text(content),
+            highlighter: None,
             last_sync: None,
             modified: false,
+            encoding: Encoding::default(),
+            line_ending: LineEnding::default(),
+            final_newline: true,
+            selections_before: HashMap::new(),
+            selections_after: HashMap::new(),
         }
     }
 
-    pub fn new_file(filename: &Path) -> Buffer {
+    /// Opens a file buffer. A file that does not exist yet gives an empty buffer, the file being
+    /// created on the first save.
+    pub fn new_file(filename: &Path) -> Result<Buffer, Error> {
         let absolute_path = if filename.is_absolute() {
             filename.to_owned()
         } else {
-            let mut full_path = current_dir().unwrap();
+            let mut full_path = current_dir().map_err(|e| Error::from_io(filename, e))?;
             full_path.push(filename);
-            full_path.canonicalize().unwrap()
+            full_path
         };
 
-        let mut file = File::open(&absolute_path).unwrap();
-        let mut file_content = String::new();
-        file.read_to_string(&mut file_content).expect("read file");
-        let last_sync = Some(SystemTime::now());
-
-        if !file_content.ends_with('\n') {
-            file_content += "\n";
-        }
+        let (absolute_path, file, last_sync) = match fs::metadata(&absolute_path) {
+            Ok(meta) if meta.is_dir() => {
+                return Err(Error::IsADirectory {
+                    path: absolute_path,
+                })
+            }
+            Ok(_) => {
+                let path = absolute_path
+                    .canonicalize()
+                    .map_err(|e| Error::from_io(&absolute_path, e))?;
+                let last_sync = modified_time(&path);
+                let file = FileContent::read(&path)?;
+                (path, file, last_sync)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => match absolute_path.parent() {
+                Some(parent) if !parent.is_dir() => {
+                    return Err(Error::NotFound {
+                        path: parent.to_owned(),
+                    })
+                }
+                _ => (absolute_path, FileContent::new(), None),
+            },
+            Err(e) => return Err(Error::from_io(&absolute_path, e)),
+        };
 
-        Buffer {
+        Ok(Buffer {
             source: BufferSource::File(absolute_path),
-            content: PieceTable::with_text(file_content),
+            content: PieceTable::with_text(file.text),
+            highlighter: None,
             last_sync,
             modified: false,
+            encoding: file.encoding,
+            line_ending: file.line_ending,
+            final_newline: file.final_newline,
+            selections_before: HashMap::new(),
+            selections_after: HashMap::new(),
+        })
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Changes the line breaks used when writing the file.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if line_ending != self.line_ending {
+            self.line_ending = line_ending;
+            self.modified = true;
         }
     }
 
+    /// Binary files are shown as a hex dump that can't be edited.
+    pub fn is_read_only(&self) -> bool {
+        self.encoding.is_binary()
+    }
+
+    /// Tells if the buffer is backed by a file that does not exist on disk yet.
+    pub fn is_new_file(&self) -> bool {
+        matches!(self.source, BufferSource::File(_)) && self.last_sync.is_none()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
     pub fn line_count(&self) -> usize {
         self.content.line_count()
     }
@@ -121,7 +250,7 @@ impl Buffer {
         find_shortest_name(sources, idx)
     }
 
-    fn is_synced(&self) -> bool {
+    pub fn is_synced(&self) -> bool {
         if let BufferSource::File(ref path) = self.source {
             let dt_sync = match self.last_sync {
                 Some(dt) => dt,
@@ -135,29 +264,123 @@ impl Buffer {
                 return false;
             }
             match meta.modified() {
-                Ok(dt_modified) => return dt_modified < dt_sync,
+                Ok(dt_modified) => return dt_modified == dt_sync,
                 Err(_) => return false,
             }
         }
         true
     }
 
-    pub fn load_from_disk(&mut self) -> bool {
+    /// Reloads the file if it changed on disk since the last sync, returning the edits made to
+    /// the content. Scratch buffers have nothing to reload but are always up to date.
+    pub fn load_from_disk(&mut self) -> Result<Option<Vec<Edit>>, Error> {
         match self.source {
-            BufferSource::Scratch(_) => true,
+            BufferSource::Scratch(_) => Ok(Some(Vec::new())),
+            BufferSource::File(_) if self.is_new_file() || self.is_synced() => Ok(None),
+            BufferSource::File(_) => self.reload().map(Some),
+        }
+    }
+
+    /// Replaces the content with the one of the file, dropping the unsaved changes.
+    pub fn reload(&mut self) -> Result<Vec<Edit>, Error> {
+        let (file, last_sync) = match self.source {
             BufferSource::File(ref path) => {
-                if !self.is_synced() {
-                    let mut file = File::open(&path).unwrap();
-                    let mut content = String::new();
-                    file.read_to_string(&mut content).expect("read file");
-                    self.content.apply_diff(&content);
-                    self.last_sync = Some(SystemTime::now());
-                    true
-                } else {
-                    false
+                let last_sync = modified_time(path);
+                (FileContent::read(path)?, last_sync)
+            }
+            BufferSource::Scratch(_) => return Ok(Vec::new()),
+        };
+        let edits = self.content.apply_diff(&file.text);
+        self.encoding = file.encoding;
+        self.line_ending = file.line_ending;
+        self.final_newline = file.final_newline;
+        self.last_sync = last_sync;
+        self.modified = false;
+        Ok(edits)
+    }
+
+    /// Changes the whole content to `text`, as a single change.
+    pub fn replace_text(&mut self, text: &str) -> Vec<Edit> {
+        self.modified = true;
+        self.content.apply_diff(text)
+    }
+
+    /// Considers the file on disk as seen, the content being kept as is.
+    pub fn mark_synced(&mut self) {
+        if let BufferSource::File(ref path) = self.source {
+            self.last_sync = modified_time(path);
+        }
+    }
+
+    pub fn disk_text(&self) -> Result<String, Error> {
+        match self.source {
+            BufferSource::File(ref path) => FileContent::read(path).map(|file| file.text),
+            BufferSource::Scratch(ref name) => Err(Error::NotAFile {
+                name: name.to_owned(),
+            }),
+        }
+    }
+
+    /// Writes the content to the source file through a temporary file renamed over the
+    /// original one. Unless `force` is set, a file changed on disk since the last sync, or
+    /// created since a new file buffer was opened, is left untouched.
+    pub fn save(&mut self, force: bool) -> Result<PathBuf, Error> {
+        let path = match self.source {
+            BufferSource::File(ref path) => path.clone(),
+            BufferSource::Scratch(ref name) => {
+                return Err(Error::NotAFile {
+                    name: name.to_owned(),
+                })
+            }
+        };
+        let io_error = |error| Error::Io {
+            path: path.clone(),
+            error,
+        };
+        if self.is_read_only() {
+            return Err(Error::ReadOnly {
+                name: path.display().to_string(),
+            });
+        }
+        let mut text = self.content.text();
+        if !self.final_newline && text.ends_with('\n') {
+            text.pop();
+        }
+        let bytes = self
+            .encoding
+            .encode(&self.line_ending.denormalize(&text))
+            .ok_or_else(|| Error::InvalidEncoding { path: path.clone() })?;
+
+        let permissions = match fs::metadata(&path) {
+            Ok(meta) => {
+                if !force && !self.is_synced() {
+                    return Err(Error::ChangedOnDisk { path });
                 }
+                Some(meta.permissions())
             }
+            Err(_) => None,
+        };
+
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(path.file_name().unwrap_or_default());
+        tmp_name.push(".ced~");
+        let tmp_path = path.with_file_name(tmp_name);
+        let written = File::create(&tmp_path).and_then(|mut file| {
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            if let Some(perms) = permissions {
+                fs::set_permissions(&tmp_path, perms)?;
+            }
+            fs::rename(&tmp_path, &path)
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(io_error(e));
         }
+
+        self.last_sync = modified_time(&path);
+        self.modified = false;
+        Ok(path)
     }
 
     pub fn append(&mut self, text: String) {
@@ -165,6 +388,85 @@ impl Buffer {
         self.modified = true;
     }
 
+    /// Opens a new undo step that will restore `selections` when undone. The step lasts until
+    /// `end_change` is called.
+    pub fn begin_change(&mut self, selections: &[Selection]) {
+        self.content.start_bulk();
+        let revision = self.content.pending_revision();
+        if self.content.has_pending_changes() {
+            // nested in a step already changing the content
+            self.selections_before
+                .entry(revision)
+                .or_insert_with(|| selections.to_vec());
+        } else {
+            self.selections_before.insert(revision, selections.to_vec());
+        }
+    }
+
+    pub fn end_change(&mut self) {
+        self.content.end_bulk();
+    }
+
+    /// Moves the content to another revision with `jump`, `selections` being remembered for the
+    /// revision left. Returns the selections saved for the new revision, empty if unknown, or
+    /// `None` if the content did not move.
+    fn jump<F>(&mut self, selections: &[Selection], jump: F) -> Option<Vec<Selection>>
+    where
+        F: FnOnce(&mut PieceTable) -> bool,
+    {
+        let from = self.content.revision();
+        if !jump(&mut self.content) {
+            return None;
+        }
+        self.modified = true;
+        self.selections_after.insert(from, selections.to_vec());
+        let to = self.content.revision();
+        let restored = if self.content.parent_revision(from) == Some(to) {
+            self.selections_before.get(&from)
+        } else {
+            self.selections_after.get(&to)
+        };
+        Some(restored.cloned().unwrap_or_default())
+    }
+
+    pub fn undo(&mut self, selections: &[Selection]) -> Option<Vec<Selection>> {
+        self.jump(selections, PieceTable::undo)
+    }
+
+    pub fn redo(&mut self, selections: &[Selection]) -> Option<Vec<Selection>> {
+        self.jump(selections, PieceTable::redo)
+    }
+
+    pub fn goto_revision(&mut self, id: usize, selections: &[Selection]) -> Option<Vec<Selection>> {
+        self.jump(selections, |content| content.goto_revision(id))
+    }
+
+    pub fn earlier(
+        &mut self,
+        duration: Duration,
+        selections: &[Selection],
+    ) -> Option<Vec<Selection>> {
+        self.jump(selections, |content| content.earlier(duration))
+    }
+
+    pub fn later(
+        &mut self,
+        duration: Duration,
+        selections: &[Selection],
+    ) -> Option<Vec<Selection>> {
+        self.jump(selections, |content| content.later(duration))
+    }
+
+    pub fn insert(&mut self, offset: usize, text: String) {
+        self.content.insert(offset, text);
+        self.modified = true;
+    }
+
+    pub fn delete(&mut self, range: &Range<usize>) {
+        self.content.delete(range);
+        self.modified = true;
+    }
+
     pub fn selection_range(&self, sel: &Selection) -> Range<usize> {
         let end_offset = if sel.end() == self.content.max_offset() {
             sel.end() + 1
@@ -180,13 +482,14 @@ impl Buffer {
     }
 }
 
-impl ToString for Buffer {
-    fn to_string(&self) -> String {
-        let mut content = self.content.text();
+impl fmt::Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let content = self.content.text();
         if !content.is_empty() && !content.ends_with('\n') {
-            content.push('\n');
+            writeln!(f, "{}", content)
+        } else {
+            write!(f, "{}", content)
         }
-        content
     }
 }
 
@@ -221,11 +524,214 @@ mod tests {
         let mut full_path = current_dir().unwrap();
         full_path.push(filename.clone());
 
-        let buffer = Buffer::new_file(&filename);
+        let buffer = Buffer::new_file(&filename).unwrap();
         let source = BufferSource::File(full_path.as_path().canonicalize().unwrap());
 
         assert_eq!(buffer.source, source);
         assert!(buffer.last_sync.is_some());
         assert_eq!(buffer.lines(Focus::Whole), lines);
     }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("ced-test-{}-{}", std::process::id(), name));
+        fs::write(&path, content).expect("write temporary file");
+        path
+    }
+
+    #[test]
+    fn save_file() {
+        let path = temp_file("save", "first line\n");
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        buffer.insert(0, "zeroth line\n".to_owned());
+        assert!(buffer.is_modified());
+
+        assert_eq!(buffer.save(false).unwrap(), path);
+        assert!(!buffer.is_modified());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "zeroth line\nfirst line\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_changed_on_disk() {
+        let path = temp_file("changed", "original\n");
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        buffer.append("added\n".to_owned());
+        // the file was touched after the buffer was loaded
+        buffer.last_sync = Some(SystemTime::UNIX_EPOCH);
+
+        assert!(matches!(
+            buffer.save(false),
+            Err(Error::ChangedOnDisk { .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original\n");
+        assert!(buffer.save(true).is_ok());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original\nadded\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_file("perms", "text\n");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        buffer.append("more\n".to_owned());
+        buffer.save(false).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn undo_selections() {
+        let mut buffer = Buffer::new_scratch("test".to_owned(), "text\n".to_owned());
+        let before = Selection::new();
+        let mut after = Selection::new();
+        after.cursor = 3;
+
+        buffer.begin_change(&[before]);
+        buffer.insert(0, "a ".to_owned());
+        buffer.end_change();
+        buffer.append("untracked\n".to_owned());
+
+        assert_eq!(buffer.undo(&[after]), Some(Vec::new()));
+        assert_eq!(buffer.undo(&[after]), Some(vec![before]));
+        assert_eq!(buffer.content.text(), "text\n");
+        assert_eq!(buffer.undo(&[before]), None);
+        assert_eq!(buffer.redo(&[before]), Some(vec![after]));
+        assert_eq!(buffer.content.text(), "a text\n");
+    }
+
+    #[test]
+    fn open_new_file() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("ced-test-{}-new", std::process::id()));
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        assert!(buffer.is_new_file());
+        assert_eq!(buffer.content.text(), "\n");
+        assert!(buffer.load_from_disk().unwrap().is_none());
+
+        buffer.insert(0, "created".to_owned());
+        buffer.save(false).unwrap();
+        assert!(!buffer.is_new_file());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "created\n");
+        assert!(buffer.is_synced());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_new_file_created_meanwhile() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("ced-test-{}-created", std::process::id()));
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        fs::write(&path, "elsewhere\n").unwrap();
+
+        buffer.insert(0, "mine".to_owned());
+        assert!(matches!(
+            buffer.save(false),
+            Err(Error::ChangedOnDisk { .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "elsewhere\n");
+        buffer.save(true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "mine\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_errors() {
+        let dir = std::env::temp_dir();
+        assert!(matches!(
+            Buffer::new_file(&dir),
+            Err(Error::IsADirectory { .. })
+        ));
+        assert!(matches!(
+            Buffer::new_file(&dir.join("ced-no-such-dir").join("file")),
+            Err(Error::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn save_encoding() {
+        let path = temp_file("latin1", "");
+        fs::write(&path, b"caf\xe9\n").unwrap();
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        assert_eq!(buffer.encoding(), Encoding::Latin1);
+        assert_eq!(buffer.content.text(), "café\n");
+
+        buffer.insert(0, "à ".to_owned());
+        buffer.save(false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\xe0 caf\xe9\n");
+        buffer.insert(0, "€".to_owned());
+        assert!(matches!(
+            buffer.save(false),
+            Err(Error::InvalidEncoding { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_line_endings() {
+        let path = temp_file("crlf", "first\r\nsecond");
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        assert_eq!(buffer.line_ending(), LineEnding::CrLf);
+        assert_eq!(buffer.lines(Focus::Whole), vec!["first", "second"]);
+
+        buffer.insert(0, "zero\n".to_owned());
+        buffer.save(false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "zero\r\nfirst\r\nsecond"
+        );
+
+        buffer.set_line_ending(LineEnding::Lf);
+        assert!(buffer.is_modified());
+        buffer.save(false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "zero\nfirst\nsecond");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_mixed_line_endings() {
+        let path = temp_file("mixed", "first\r\nsecond\nthird\r\nfourth\r");
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        assert_eq!(buffer.line_ending(), LineEnding::CrLf);
+        assert_eq!(
+            buffer.lines(Focus::Whole),
+            vec!["first", "second", "third", "fourth"]
+        );
+
+        buffer.insert(0, "x".to_owned());
+        buffer.save(false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "xfirst\r\nsecond\r\nthird\r\nfourth\r\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn binary_read_only() {
+        let path = temp_file("binary", "");
+        fs::write(&path, b"\x7fELF\x00").unwrap();
+        let mut buffer = Buffer::new_file(&path).unwrap();
+        assert!(buffer.is_read_only());
+        assert!(buffer
+            .content
+            .text()
+            .starts_with("00000000  7f 45 4c 46 00"));
+        assert!(matches!(buffer.save(true), Err(Error::ReadOnly { .. })));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_scratch() {
+        let mut buffer = Buffer::new_scratch("*scratch*".to_owned(), String::new());
+        assert!(matches!(buffer.save(true), Err(Error::NotAFile { .. })));
+    }
 }
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::time::SystemTime;
 
 use crate::editor::menu::{Menu, MenuEntry};
+use crate::editor::search::CaseMode;
 use crate::editor::{Editor, EditorInfo, View};
 use ignore::Walk;
 use remote::jsonrpc;
@@ -19,7 +21,7 @@ fn submenu_action(key: &str, editor: &mut Editor, client_id: usize) -> Result<()
             buffers: &editor.core.buffers(),
             views: &editor.core.views(),
         };
-        menu.populate(&info);
+        menu.populate(&info, &editor.core, client_id);
     }
     let menu = &editor.command_map[key];
     editor.core.get_notifier().notify(
@@ -29,6 +31,16 @@ fn submenu_action(key: &str, editor: &mut Editor, client_id: usize) -> Result<()
     Ok(())
 }
 
+fn format_age(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
 pub fn default_commands() -> HashMap<String, Menu> {
     let mut commands = HashMap::new();
 
@@ -42,6 +54,40 @@ pub fn default_commands() -> HashMap<String, Menu> {
                 description: Some("Open and read a new file.".to_string()),
                 action: submenu_action,
             });
+            entries.push(MenuEntry {
+                key: "write".to_string(),
+                label: "Save".to_string(),
+                description: Some("Write the files of the current view to disk.".to_string()),
+                action: |_key, editor, client_id| {
+                    let params = requests::WriteParams {
+                        buffer: None,
+                        force: false,
+                    };
+                    editor.command_write(client_id, &params)?;
+                    Ok(())
+                },
+            });
+            entries.push(MenuEntry {
+                key: "write_all".to_string(),
+                label: "Save all".to_string(),
+                description: Some("Write all the modified files to disk.".to_string()),
+                action: |_key, editor, client_id| {
+                    let params = requests::WriteParams {
+                        buffer: None,
+                        force: false,
+                    };
+                    editor.command_write_all(client_id, &params)?;
+                    Ok(())
+                },
+            });
+            entries.push(MenuEntry {
+                key: "grep".to_string(),
+                label: "Search project".to_string(),
+                description: Some(
+                    "Show the lines matching a pattern in the files of the project.".to_string(),
+                ),
+                action: submenu_action,
+            });
             entries.push(MenuEntry {
                 key: "quit".to_string(),
                 label: "Quit".to_string(),
@@ -84,6 +130,38 @@ pub fn default_commands() -> HashMap<String, Menu> {
                 description: Some("Remove a buffer from the current view.".to_string()),
                 action: submenu_action,
             });
+            entries.push(MenuEntry {
+                key: "line_ending".to_string(),
+                label: "Line endings".to_string(),
+                description: Some("Convert the line endings of the current files.".to_string()),
+                action: submenu_action,
+            });
+            entries.push(MenuEntry {
+                key: "revisions".to_string(),
+                label: "Revisions".to_string(),
+                description: Some("Go back to a revision of the current buffers.".to_string()),
+                action: submenu_action,
+            });
+            entries.push(MenuEntry {
+                key: "file_changed".to_string(),
+                label: "File changes".to_string(),
+                description: Some(
+                    "Choose between unsaved changes and the files changed on disk.".to_string(),
+                ),
+                action: submenu_action,
+            });
+            entries.push(MenuEntry {
+                key: "recover".to_string(),
+                label: "Recover changes".to_string(),
+                description: Some("Bring back the changes lost by a previous session.".to_string()),
+                action: submenu_action,
+            });
+            entries.push(MenuEntry {
+                key: "theme".to_string(),
+                label: "Change theme".to_string(),
+                description: Some("Select the colors of the faces.".to_string()),
+                action: submenu_action,
+            });
             entries.push(MenuEntry {
                 key: "exec".to_string(),
                 label: "Execute script.".to_string(),
@@ -97,12 +175,12 @@ pub fn default_commands() -> HashMap<String, Menu> {
     commands.insert(
         String::from("open"),
         Menu::new("open", "file", |info| {
-            Walk::new(&info.cwd)
+            Walk::new(info.cwd)
                 .filter_map(std::result::Result::ok)
                 .filter(|e| e.file_type().map(|ft| !ft.is_dir()).unwrap_or(false))
                 .filter_map(|e| {
                     e.path()
-                        .strip_prefix(&info.cwd)
+                        .strip_prefix(info.cwd)
                         .unwrap_or_else(|_| e.path())
                         .to_str()
                         .map(String::from)
@@ -124,6 +202,27 @@ pub fn default_commands() -> HashMap<String, Menu> {
         }),
     );
 
+    commands.insert(
+        String::from("grep"),
+        Menu::prompt(
+            "grep",
+            "search",
+            "Pattern to search the project for.",
+            |key, editor, client_id| {
+                let count = editor
+                    .core
+                    .grep(client_id, key, CaseMode::default())
+                    .map_err(|e| jsonrpc::Error::invalid_params(&e.to_string()))?;
+                if count > 0 {
+                    editor
+                        .core
+                        .message(client_id, &format!("{} matching lines", count));
+                }
+                Ok(())
+            },
+        ),
+    );
+
     commands.insert(
         String::from("scratch"),
         Menu::prompt(
@@ -201,6 +300,166 @@ pub fn default_commands() -> HashMap<String, Menu> {
         }),
     );
 
+    commands.insert(
+        String::from("line_ending"),
+        Menu::new("line_ending", "line ending", |_| {
+            [
+                ("lf", "Unix (LF)"),
+                ("crlf", "Windows (CRLF)"),
+                ("cr", "Classic Mac (CR)"),
+            ]
+            .iter()
+            .map(|(key, label)| MenuEntry {
+                key: key.to_string(),
+                label: label.to_string(),
+                description: None,
+                action: |key, editor, client_id| {
+                    let line_ending = key
+                        .parse()
+                        .map_err(|e: String| jsonrpc::Error::invalid_params(&e))?;
+                    editor.core.set_line_ending(client_id, line_ending);
+                    Ok(())
+                },
+            })
+            .collect()
+        }),
+    );
+
+    commands.insert(
+        String::from("revisions"),
+        Menu::for_client("revisions", "revision", |core, client_id| {
+            core.revisions(client_id)
+                .into_iter()
+                .rev()
+                .map(|rev| MenuEntry {
+                    key: format!("{}:{}", rev.info.id, rev.buffer),
+                    label: format!(
+                        "{} #{}{}",
+                        rev.buffer,
+                        rev.info.id,
+                        if rev.current { " (current)" } else { "" }
+                    ),
+                    description: Some(format!(
+                        "{} ago, {}",
+                        format_age(rev.info.time),
+                        rev.preview
+                    )),
+                    action: |key, editor, client_id| {
+                        let (revision, buffer) = key
+                            .split_once(':')
+                            .and_then(|(id, buffer)| Some((id.parse().ok()?, buffer)))
+                            .ok_or_else(|| jsonrpc::Error::invalid_params("invalid revision"))?;
+                        editor
+                            .core
+                            .goto_revision(client_id, buffer, revision)
+                            .map_err(|e| jsonrpc::Error::invalid_params(&e.to_string()))?;
+                        Ok(())
+                    },
+                })
+                .collect()
+        }),
+    );
+
+    commands.insert(
+        String::from("file_changed"),
+        Menu::for_client("file_changed", "changed file", |core, _client_id| {
+            let choices: [(&str, &str, &str); 3] = [
+                (
+                    "reload",
+                    "Reload",
+                    "Drop the unsaved changes for the file content.",
+                ),
+                (
+                    "keep",
+                    "Keep",
+                    "Keep the unsaved changes, overwriting the file on save.",
+                ),
+                ("diff", "Compare", "Show the differences with the file."),
+            ];
+            core.conflicts()
+                .into_iter()
+                .flat_map(|name| {
+                    choices
+                        .iter()
+                        .map(move |(choice, label, description)| MenuEntry {
+                            key: format!("{}:{}", choice, name),
+                            label: format!("{} {}", label, name),
+                            description: Some(description.to_string()),
+                            action: |key, editor, client_id| {
+                                let (choice, name) = key.split_once(':').ok_or_else(|| {
+                                    jsonrpc::Error::invalid_params("invalid choice")
+                                })?;
+                                match choice {
+                                    "reload" => editor.core.reload_buffer(name),
+                                    "keep" => editor.core.keep_buffer(name),
+                                    _ => editor.core.diff_buffer(client_id, name),
+                                }
+                                .map_err(|e| jsonrpc::Error::invalid_params(&e.to_string()))
+                            },
+                        })
+                })
+                .collect()
+        }),
+    );
+
+    commands.insert(
+        String::from("recover"),
+        Menu::for_client("recover", "unsaved changes", |core, _client_id| {
+            let choices: [(&str, &str, &str); 3] = [
+                ("recover", "Recover", "Restore the unsaved changes"),
+                ("diff", "Compare", "Show the unsaved changes"),
+                ("discard", "Discard", "Give up the unsaved changes"),
+            ];
+            core.recoveries()
+                .into_iter()
+                .flat_map(|(name, time)| {
+                    choices
+                        .iter()
+                        .map(move |(choice, label, description)| MenuEntry {
+                            key: format!("{}:{}", choice, name),
+                            label: format!("{} {}", label, name),
+                            description: Some(format!(
+                                "{} made {} ago.",
+                                description,
+                                format_age(time)
+                            )),
+                            action: |key, editor, client_id| {
+                                let (choice, name) = key.split_once(':').ok_or_else(|| {
+                                    jsonrpc::Error::invalid_params("invalid choice")
+                                })?;
+                                match choice {
+                                    "recover" => editor.core.recover_buffer(name),
+                                    "discard" => editor.core.discard_recovery(name),
+                                    _ => editor.core.diff_recovery(client_id, name),
+                                }
+                                .map_err(|e| jsonrpc::Error::invalid_params(&e.to_string()))
+                            },
+                        })
+                })
+                .collect()
+        }),
+    );
+
+    commands.insert(
+        String::from("theme"),
+        Menu::for_client("theme", "theme", |core, client_id| {
+            core.themes(client_id)
+                .into_iter()
+                .map(|(name, current)| MenuEntry {
+                    key: name.clone(),
+                    label: format!("{}{}", name, if current { " (current)" } else { "" }),
+                    description: None,
+                    action: |key, editor, client_id| {
+                        editor
+                            .core
+                            .set_theme(client_id, key)
+                            .map_err(|e| jsonrpc::Error::invalid_params(&e.to_string()))
+                    },
+                })
+                .collect()
+        }),
+    );
+
     commands.insert(
         String::from("exec"),
         Menu::prompt(
@@ -1,27 +1,49 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use std::{cell::RefCell, env::current_dir};
-use std::{
-    collections::{HashMap, HashSet},
-    sync::MutexGuard,
-};
 
-use crate::editor::selection::Selection;
-use crate::editor::view::{Focus, Lens, View};
-use crate::editor::{Buffer, Coords, EditorInfo};
+use crate::editor::diff::{line_diff, line_summary};
+use crate::editor::encoding::{Encoding, LineEnding};
+use crate::editor::highlight::{Grammar, Highlighter};
+use crate::editor::journal::{swap_path, swap_paths, Journal, Recovery};
+use crate::editor::layout::{display_width, Layout, Wrap, DEFAULT_TAB_WIDTH};
+use crate::editor::piece_table::{Change, RevisionInfo, TextObject, WordChars};
+use crate::editor::register::{
+    register_name, Clipboard, Registers, REGISTER_DEFAULT, REGISTER_MACRO,
+};
+use crate::editor::search::{
+    build_regex, find_all, find_next, last_char_offset, CaseMode, Direction,
+};
+use crate::editor::selection::{merge_overlapping, Selection};
+use crate::editor::session::{BufferSnapshot, SessionSnapshot, ViewSnapshot};
+use crate::editor::theme::{Theme, DEFAULT_THEME};
+use crate::editor::view::{
+    shown_lines, Focus, Lens, View, ViewItem, ViewSync, ViewUpdate, Viewport,
+};
+use crate::editor::{Buffer, BufferSource, Coords, EditorInfo};
 use crate::server::BroadcastMessage;
 use crate::stackmap::StackMap;
 use async_channel::Sender;
 use futures_lite::*;
+use ignore::Walk;
+use regex::Regex;
 use remote::jsonrpc::Notification;
 use remote::protocol::{
     notifications::{self, Notification as _},
-    Face, Text, TextFragment,
+    Face, KeyEvent, Text, TextFragment,
 };
 
 pub const BUFFER_DEBUG: &str = "*debug*";
 pub const BUFFER_SCRATCH: &str = "*scratch*";
+// lines shown around each line matched by a project search, and how many lines to show at most
+const GREP_CONTEXT: usize = 1;
+const GREP_MAX_LINES: usize = 1000;
+// positions kept in the jump list of each client
+const MAX_JUMPS: usize = 100;
 
 #[derive(Clone)]
 pub struct Notifier {
@@ -70,9 +92,20 @@ impl Notifier {
         self.notify(client_id, notifications::Info::new(params));
     }
 
-    pub fn view_update(&self, params: Vec<(usize, notifications::ViewParams)>) {
-        for (client_id, np) in params {
-            self.notify(client_id, notifications::View::new(np));
+    pub fn theme_update(&self, client_id: usize, theme: &Theme) {
+        self.notify(
+            client_id,
+            notifications::Theme::new(theme.to_notification_params()),
+        );
+    }
+
+    pub fn view_update(&self, updates: Vec<(usize, ViewUpdate)>) {
+        for (client_id, update) in updates {
+            let notif = match update {
+                ViewUpdate::Whole(params) => notifications::View::new(params),
+                ViewUpdate::Patch(params) => notifications::ViewPatch::new(params),
+            };
+            self.notify(client_id, notif);
         }
     }
 }
@@ -83,7 +116,7 @@ impl From<Sender<BroadcastMessage>> for Notifier {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum CursorTarget {
     Left,
     Right,
@@ -93,12 +126,64 @@ pub enum CursorTarget {
     End,
     LineBegin,
     LineEnd,
+    FirstNonBlank,
+    NextWordStart,
+    NextWordEnd,
+    PreviousWordStart,
+    NextBigWordStart,
+    NextBigWordEnd,
+    PreviousBigWordStart,
+    NextParagraph,
+    PreviousParagraph,
+    MatchingBracket,
+}
+
+#[derive(Clone, Debug)]
+pub struct RevisionEntry {
+    pub buffer: String,
+    pub info: RevisionInfo,
+    pub current: bool,
+    pub preview: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum InsertTarget {
+    Cursor,
+    Before,
+    After,
+    Replace,
+}
+
+/// A position in the jump list: a view and the selections the client had in it.
+#[derive(Clone, Debug, PartialEq)]
+struct Jump {
+    view: String,
+    selections: HashMap<String, Vec<Selection>>,
 }
 
 #[derive(Clone, Debug)]
 struct ClientContext {
     view: Rc<RefCell<View>>,
     selections: HashMap<String, HashMap<String, Vec<Selection>>>,
+    // nesting level of the undo groups opened by the client and the buffers they changed
+    undo_group_depth: usize,
+    undo_group: HashSet<String>,
+    // last search of the client, highlighted in its views
+    search: Option<Regex>,
+    // register the keys of the client go to and the keys recorded so far
+    recording: Option<(char, Vec<String>)>,
+    // positions left by big moves and view switches, the client being at `jump_index` in
+    // them while going back, past the last one otherwise
+    jumps: Vec<Jump>,
+    jump_index: usize,
+    // lines of the view the client shows
+    viewport: Viewport,
+    // views sent to the client
+    view_sync: ViewSync,
+    // how long lines are shown to the client
+    layout: Layout,
+    // name of the theme of the client
+    theme: String,
 }
 
 struct CoreState {
@@ -106,18 +191,109 @@ struct CoreState {
     clients: StackMap<usize, ClientContext>,
     buffers: HashMap<String, Buffer>,
     views: StackMap<String, Rc<RefCell<View>>>,
+    // buffers with unsaved changes whose file also changed on disk
+    conflicts: HashSet<String>,
+    // where to keep the journals of unsaved changes, none if they are not kept
+    swap_dir: Option<PathBuf>,
+    journals: HashMap<String, Journal>,
+    // journals left by a previous process, by buffer, until recovered or discarded
+    recoveries: HashMap<String, PathBuf>,
+    // what words are made of, for the word motions
+    word_chars: WordChars,
+    // columns between tab stops
+    tab_width: usize,
+    // grammars files are highlighted with, the last loaded first
+    grammars: Vec<Rc<Grammar>>,
+    themes: Vec<Theme>,
+    registers: Registers,
 }
 
 macro_rules! lock {
     ($s:ident) => {
-        $s.state.lock().expect("lock state mutex")
+        $s.state.borrow_mut()
     };
 }
 
 #[derive(Debug)]
 pub enum Error {
-    BufferNotFound { name: String },
-    ViewNotFound { view_id: String },
+    BufferNotFound {
+        name: String,
+    },
+    ChangedOnDisk {
+        path: PathBuf,
+    },
+    Clipboard {
+        command: String,
+        error: std::io::Error,
+    },
+    GrammarNotFound {
+        name: String,
+    },
+    InvalidEncoding {
+        path: PathBuf,
+    },
+    InvalidGrammar {
+        path: PathBuf,
+        error: String,
+    },
+    InvalidMacro {
+        register: char,
+        key: String,
+    },
+    InvalidMark {
+        name: String,
+    },
+    InvalidPattern {
+        pattern: String,
+        error: regex::Error,
+    },
+    InvalidRegister {
+        name: String,
+    },
+    InvalidTheme {
+        path: PathBuf,
+        error: String,
+    },
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    IsADirectory {
+        path: PathBuf,
+    },
+    NoRecovery {
+        name: String,
+    },
+    NotAFile {
+        name: String,
+    },
+    NotFound {
+        path: PathBuf,
+    },
+    PermissionDenied {
+        path: PathBuf,
+    },
+    ReadOnly {
+        name: String,
+    },
+    ThemeNotFound {
+        name: String,
+    },
+    ViewNotFound {
+        view_id: String,
+    },
+}
+
+impl Error {
+    /// Sorts out the I/O errors worth a dedicated variant.
+    pub fn from_io(path: &Path, error: std::io::Error) -> Error {
+        let path = path.to_owned();
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Error::NotFound { path },
+            std::io::ErrorKind::PermissionDenied => Error::PermissionDenied { path },
+            _ => Error::Io { path, error },
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -125,6 +301,40 @@ impl std::fmt::Display for Error {
         use Error::*;
         match self {
             BufferNotFound { name } => write!(f, "buffer not found: {}", name),
+            ChangedOnDisk { path } => write!(f, "file changed on disk: {}", path.display()),
+            Clipboard { command, error } => {
+                write!(f, "clipboard command failed: {}: {}", command, error)
+            }
+            GrammarNotFound { name } => write!(f, "grammar not found: {}", name),
+            InvalidEncoding { path } => {
+                write!(
+                    f,
+                    "content not representable in the file encoding: {}",
+                    path.display()
+                )
+            }
+            InvalidGrammar { path, error } => {
+                write!(f, "invalid grammar {}: {}", path.display(), error)
+            }
+            InvalidMacro { register, key } => {
+                write!(f, "invalid key in macro register {}: {}", register, key)
+            }
+            InvalidMark { name } => write!(f, "invalid mark: {:?}", name),
+            InvalidPattern { pattern, error } => {
+                write!(f, "invalid pattern {}: {}", pattern, error)
+            }
+            InvalidRegister { name } => write!(f, "invalid register: {:?}", name),
+            InvalidTheme { path, error } => {
+                write!(f, "invalid theme {}: {}", path.display(), error)
+            }
+            Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            IsADirectory { path } => write!(f, "is a directory: {}", path.display()),
+            NoRecovery { name } => write!(f, "no changes to recover: {}", name),
+            NotAFile { name } => write!(f, "buffer is not backed by a file: {}", name),
+            NotFound { path } => write!(f, "file not found: {}", path.display()),
+            PermissionDenied { path } => write!(f, "permission denied: {}", path.display()),
+            ReadOnly { name } => write!(f, "buffer is read-only: {}", name),
+            ThemeNotFound { name } => write!(f, "theme not found: {}", name),
             ViewNotFound { view_id } => write!(f, "view not found: {}", view_id),
         }
     }
@@ -150,7 +360,7 @@ fn normalize_error_message(message: &str) -> String {
 
 #[derive(Clone)]
 pub struct Core {
-    state: Arc<Mutex<CoreState>>,
+    state: Rc<RefCell<CoreState>>,
     notifier: Notifier,
     pub debug_mode: bool,
 }
@@ -158,11 +368,20 @@ pub struct Core {
 impl Core {
     pub fn new(notifier: Notifier) -> Core {
         Core {
-            state: Arc::new(Mutex::new(CoreState {
+            state: Rc::new(RefCell::new(CoreState {
                 cwd: current_dir().unwrap_or_else(|_| dirs::home_dir().unwrap_or_default()),
                 clients: StackMap::new(),
                 buffers: HashMap::new(),
                 views: StackMap::new(),
+                conflicts: HashSet::new(),
+                swap_dir: None,
+                journals: HashMap::new(),
+                recoveries: HashMap::new(),
+                word_chars: WordChars::default(),
+                tab_width: DEFAULT_TAB_WIDTH,
+                grammars: Vec::new(),
+                themes: vec![Theme::default()],
+                registers: Registers::default(),
             })),
             notifier,
             debug_mode: true,
@@ -193,7 +412,7 @@ impl Core {
         lock!(self).views.keys().map(String::to_owned).collect()
     }
 
-    fn clients_with_buffer(&self, name: &str) -> Vec<usize> {
+    pub fn clients_with_buffer(&self, name: &str) -> Vec<usize> {
         if !self.buffer_exists(name) {
             return Vec::new();
         }
@@ -216,36 +435,191 @@ impl Core {
         state
             .buffers
             .get(buffer)
-            .map(|b| b.content.text_range(&b.selection_range(sel)))
-            .flatten()
+            .and_then(|b| b.content.text_range(&b.selection_range(sel)))
     }
 
     fn get_coord(&self, buffer: &str, offset: usize) -> Option<Coords> {
         lock!(self)
             .buffers
             .get(buffer)
-            .map(|b| b.content.offset_to_coord(offset))
-            .flatten()
+            .and_then(|b| b.content.offset_to_coord(offset))
+    }
+
+    /// The main cursor of each buffer in the view of the client, with the line of the view
+    /// showing it.
+    fn main_cursors(state: &CoreState, client_id: usize) -> Vec<(String, usize, Option<usize>)> {
+        let ctx = &state.clients[&client_id];
+        let view = ctx.view.borrow();
+        let sels = match ctx.selections.get(view.id()) {
+            Some(sels) => sels,
+            None => return Vec::new(),
+        };
+        view.buffers()
+            .into_iter()
+            .filter_map(|name| {
+                let cursor = sels.get(name)?.first()?.cursor;
+                let coords = state.buffers[name].content.offset_to_coord(cursor)?;
+                let line = view.view_line(&state.buffers, name, coords.l - 1);
+                Some((name.to_owned(), cursor, line))
+            })
+            .collect()
     }
 
     fn notify_view_update(&self, clients: Vec<usize>) {
-        let state = lock!(self);
+        let mut guard = lock!(self);
+        let state = &mut *guard;
         let params = clients
             .iter()
             .map(|id| {
-                let view = state.clients[&id].view.borrow();
-                let sels = state.clients[&id].selections.get(&view.key());
-                (*id, view.to_notification_params(&state.buffers, sels))
+                let cursors = Self::main_cursors(state, *id);
+                let ctx = state.clients.get_mut(id).unwrap();
+                let view = ctx.view.borrow();
+                let followed = ctx.viewport.follow(view.id(), &cursors);
+                if let Some((buffer, offset, _)) = followed {
+                    let col =
+                        Self::display_column(&state.buffers, state.tab_width, buffer, *offset);
+                    ctx.layout.reveal(col);
+                }
+                let sels = ctx.selections.get(view.id());
+                let search = ctx.search.as_ref();
+                let (buffers, tab_width, layout) = (&state.buffers, state.tab_width, &ctx.layout);
+                let build = |window| {
+                    view.to_notification_params(buffers, sels, search, window, layout, tab_width)
+                };
+                let mut params = build(ctx.viewport.window());
+                // headers leave fewer lines shown than rows, scroll on until the cursor is
+                if let Some(line) = followed.and_then(|c| c.2) {
+                    loop {
+                        let end = ctx.viewport.top + shown_lines(&params);
+                        if line < end || end == ctx.viewport.top {
+                            break;
+                        }
+                        ctx.viewport.top += line + 1 - end;
+                        params = build(ctx.viewport.window());
+                    }
+                }
+                drop(view);
+                (*id, ctx.view_sync.update(params))
             })
             .collect();
         self.notifier.view_update(params);
     }
 
+    /// Sends the next views to the client as patches.
+    pub fn enable_view_patches(&mut self, client_id: usize) {
+        if let Some(ctx) = lock!(self).clients.get_mut(&client_id) {
+            ctx.view_sync.enable_patches();
+        }
+    }
+
+    /// Records that the client holds the view of `revision`, the next ones being sent as
+    /// patches against it. Returns false if the revision is unknown.
+    pub fn ack_view(&mut self, client_id: usize, revision: usize) -> bool {
+        lock!(self)
+            .clients
+            .get_mut(&client_id)
+            .is_some_and(|ctx| ctx.view_sync.ack(revision))
+    }
+
+    /// Column of the screen `offset` is shown at in `buffer`, counted from the line start.
+    fn display_column(
+        buffers: &HashMap<String, Buffer>,
+        tab_width: usize,
+        buffer: &str,
+        offset: usize,
+    ) -> usize {
+        let content = &buffers[buffer].content;
+        let line = match content.offset_to_coord(offset) {
+            Some(coords) => coords.l,
+            None => return 0,
+        };
+        let start = content
+            .coord_to_offset(Coords { l: line, c: 1 })
+            .unwrap_or(offset);
+        let text = content.line_text(line).unwrap_or_default();
+        let prefix = text.get(..offset - start).unwrap_or(&text);
+        display_width(prefix, tab_width)
+    }
+
+    /// Sets the number of lines of the view the client shows and their width.
+    pub fn resize(&mut self, client_id: usize, width: usize, height: usize) {
+        if let Some(ctx) = lock!(self).clients.get_mut(&client_id) {
+            ctx.viewport.set_height(height);
+            ctx.layout.width = Some(width);
+        }
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// Sets how the lines wider than the client are shown.
+    pub fn set_wrap(&mut self, client_id: usize, wrap: Wrap) {
+        if let Some(ctx) = lock!(self).clients.get_mut(&client_id) {
+            ctx.layout.wrap = wrap;
+            ctx.layout.column = 0;
+        }
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// Sets the columns between tab stops for every client.
+    pub fn set_tab_width(&mut self, width: usize) {
+        let clients = {
+            let mut state = lock!(self);
+            state.tab_width = width.max(1);
+            state.clients.keys().copied().collect()
+        };
+        self.notify_view_update(clients);
+    }
+
+    /// Scrolls the lines of the client by `columns` columns, right if positive, when they are
+    /// not wrapped.
+    pub fn scroll_columns(&mut self, client_id: usize, columns: isize) {
+        if let Some(ctx) = lock!(self).clients.get_mut(&client_id) {
+            ctx.layout.column = ctx.layout.column.saturating_add_signed(columns);
+        }
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// Scrolls the view of the client by `lines` lines, down if positive, the cursors
+    /// staying where they are.
+    pub fn scroll(&mut self, client_id: usize, lines: isize) {
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let ctx = state.clients.get_mut(&client_id).unwrap();
+            let line_count = ctx.view.borrow().line_count(&state.buffers);
+            ctx.viewport.scroll(lines, line_count);
+        }
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// Scrolls the view of the client by `pages` times the lines it shows.
+    pub fn scroll_pages(&mut self, client_id: usize, pages: isize) {
+        let height = lock!(self).clients[&client_id].viewport.height;
+        if let Some(height) = height {
+            self.scroll(client_id, pages.saturating_mul(height as isize));
+        }
+    }
+
+    /// Scrolls the view of the client to put the main cursor in the middle.
+    pub fn center_cursor(&mut self, client_id: usize) {
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let cursors = Self::main_cursors(state, client_id);
+            let ctx = state.clients.get_mut(&client_id).unwrap();
+            ctx.viewport.center(&cursors);
+        }
+        self.notify_view_update(vec![client_id]);
+    }
+
     fn append_to(&mut self, buffer: &str, text: String) {
         if !self.buffer_exists(buffer) {
             self.open_scratch(buffer, text);
-        } else if let Some(buf) = lock!(self).buffers.get_mut(buffer) {
-            buf.append(text);
+        } else {
+            let mut state = lock!(self);
+            if let Some(buf) = state.buffers.get_mut(buffer) {
+                buf.append(text);
+                Self::apply_changes(&mut state, buffer);
+            }
         }
         self.notify_view_update(self.clients_with_buffer(buffer));
     }
@@ -293,12 +667,23 @@ impl Core {
         {
             let mut state = lock!(self);
             let context = if let Some(c) = state.clients.latest() {
-                state.clients[c].clone()
+                ClientContext {
+                    undo_group_depth: 0,
+                    undo_group: HashSet::new(),
+                    search: None,
+                    recording: None,
+                    jumps: Vec::new(),
+                    jump_index: 0,
+                    viewport: Viewport::default(),
+                    view_sync: ViewSync::default(),
+                    layout: Layout::default(),
+                    ..state.clients[c].clone()
+                }
             } else {
                 let latest_view = state.views.latest_value().unwrap();
                 let mut selections = HashMap::new();
                 selections.insert(
-                    latest_view.borrow().key(),
+                    latest_view.borrow().id().to_owned(),
                     latest_view
                         .borrow()
                         .buffers()
@@ -309,15 +694,31 @@ impl Core {
                 ClientContext {
                     view: Rc::clone(latest_view),
                     selections,
+                    undo_group_depth: 0,
+                    undo_group: HashSet::new(),
+                    search: None,
+                    recording: None,
+                    jumps: Vec::new(),
+                    jump_index: 0,
+                    viewport: Viewport::default(),
+                    view_sync: ViewSync::default(),
+                    layout: Layout::default(),
+                    theme: DEFAULT_THEME.to_owned(),
                 }
             };
             state.clients.insert(id, context);
         }
         self.debug(&format!("new client: {}", id));
         self.notifier.info_update(id, info);
+        self.notify_theme(id);
+    }
+
+    pub fn has_client(&self, id: usize) -> bool {
+        lock!(self).clients.contains_key(&id)
     }
 
     pub fn remove_client(&mut self, id: usize) {
+        self.end_undo_group(id);
         lock!(self).clients.remove(&id);
         self.debug(&format!("client left: {}", id));
     }
@@ -327,296 +728,2309 @@ impl Core {
         lock!(self).buffers.insert(name.to_owned(), buffer);
     }
 
-    pub fn open_file(&mut self, buffer_name: &str, filename: &Path) {
-        let buffer = Buffer::new_file(filename);
-        lock!(self).buffers.insert(buffer_name.to_owned(), buffer);
-    }
-
-    pub fn add_view(&mut self, view: View) {
-        lock!(self)
-            .views
-            .insert(view.key(), Rc::new(RefCell::new(view.clone())));
-        for (_id, context) in lock!(self).clients.iter_mut() {
-            let sels_by_view = context
-                .selections
-                .entry(view.key())
-                .or_insert_with(HashMap::new);
-            for buffer in view.buffers() {
-                sels_by_view
-                    .entry(buffer.to_owned())
-                    .or_insert_with(|| vec![Selection::new()]);
-            }
+    pub fn open_file(&mut self, buffer_name: &str, filename: &Path) -> Result<(), Error> {
+        let mut buffer = Buffer::new_file(filename)?;
+        let mut state = lock!(self);
+        if let BufferSource::File(path) = &buffer.source {
+            buffer.highlighter = state
+                .grammars
+                .iter()
+                .find(|g| g.matches(path))
+                .map(|g| Highlighter::new(g.clone()));
         }
+        state.buffers.insert(buffer_name.to_owned(), buffer);
+        Ok(())
     }
 
-    pub fn delete_view(&mut self, view_id: &str) -> Result<(), Error> {
-        if !self.view_exists(view_id) {
-            return Err(Error::ViewNotFound {
-                view_id: view_id.to_owned(),
-            });
-        }
+    /// Loads the grammar at `path` and adds it. Returns its name.
+    pub fn load_grammar(&mut self, path: &Path) -> Result<String, Error> {
+        Grammar::load(path).map(|grammar| self.add_grammar(grammar))
+    }
 
-        let view = lock!(self).views.remove(&view_id.to_owned()).unwrap();
-        self.debug(&format!("delete view: {}", view_id));
-        for (_id, context) in lock!(self).clients.iter_mut() {
-            context.selections.remove(view_id);
-        }
-        for buffer in view.borrow().buffers() {
-            let mut has_ref = false;
-            for view in lock!(self).views.values() {
-                if view.borrow().buffers().iter().any(|&b| b == buffer) {
-                    has_ref = true;
-                    break;
+    /// Adds a grammar, replacing the one of the same name, and highlights the files it
+    /// matches that are not highlighted yet. Returns its name.
+    pub fn add_grammar(&mut self, grammar: Grammar) -> String {
+        let grammar = Rc::new(grammar);
+        let clients = {
+            let mut state = lock!(self);
+            state.grammars.retain(|g| g.name != grammar.name);
+            state.grammars.insert(0, grammar.clone());
+            for buffer in state.buffers.values_mut() {
+                let replaced = match (&buffer.highlighter, &buffer.source) {
+                    (Some(h), _) => h.grammar().name == grammar.name,
+                    (None, BufferSource::File(path)) => grammar.matches(path),
+                    (None, _) => false,
+                };
+                if replaced {
+                    buffer.highlighter = Some(Highlighter::new(grammar.clone()));
                 }
             }
-            if !has_ref {
-                lock!(self).buffers.remove(&buffer.to_owned());
-                self.debug(&format!("delete buffer: {}", buffer));
+            state.clients.keys().copied().collect()
+        };
+        self.notify_view_update(clients);
+        grammar.name.clone()
+    }
+
+    /// Highlights `buffer` with the grammar named `name`, or not at all if none.
+    pub fn set_grammar(&mut self, buffer: &str, name: Option<&str>) -> Result<(), Error> {
+        let clients = {
+            let mut state = lock!(self);
+            let grammar = match name {
+                Some(name) => Some(
+                    state
+                        .grammars
+                        .iter()
+                        .find(|g| g.name == name)
+                        .cloned()
+                        .ok_or_else(|| Error::GrammarNotFound {
+                            name: name.to_owned(),
+                        })?,
+                ),
+                None => None,
+            };
+            state
+                .buffers
+                .get_mut(buffer)
+                .ok_or_else(|| Error::BufferNotFound {
+                    name: buffer.to_owned(),
+                })?
+                .highlighter = grammar.map(Highlighter::new);
+            state.clients.keys().copied().collect()
+        };
+        self.notify_view_update(clients);
+        Ok(())
+    }
+
+    /// Loads the theme at `path` and adds it. Returns its name.
+    pub fn load_theme(&mut self, path: &Path) -> Result<String, Error> {
+        Theme::load(path).map(|theme| self.add_theme(theme))
+    }
+
+    /// Adds a theme, replacing the one of the same name. Returns its name.
+    pub fn add_theme(&mut self, theme: Theme) -> String {
+        let clients: Vec<usize> = {
+            let mut state = lock!(self);
+            match state.themes.iter_mut().find(|t| t.name == theme.name) {
+                Some(old) => *old = theme.clone(),
+                None => state.themes.push(theme.clone()),
             }
+            state
+                .clients
+                .iter()
+                .filter(|(_, ctx)| ctx.theme == theme.name)
+                .map(|(id, _)| *id)
+                .collect()
+        };
+        for id in clients {
+            self.notifier.theme_update(id, &theme);
         }
-        if lock!(self).views.is_empty() {
-            if lock!(self).buffers.is_empty() {
-                self.open_scratch(BUFFER_SCRATCH, String::new());
+        theme.name
+    }
+
+    /// Changes the theme of the client.
+    pub fn set_theme(&mut self, client_id: usize, name: &str) -> Result<(), Error> {
+        {
+            let mut state = lock!(self);
+            if !state.themes.iter().any(|t| t.name == name) {
+                return Err(Error::ThemeNotFound {
+                    name: name.to_owned(),
+                });
             }
-            self.add_view(View::for_buffer(BUFFER_SCRATCH));
-        }
-        let mut to_notify = Vec::new();
-        let latest_view = Rc::clone(lock!(self).views.latest_value().expect("get latest view"));
-        for (id, context) in lock!(self).clients.iter_mut() {
-            if context.view.borrow().key() == *view_id {
-                context.view = Rc::clone(&latest_view);
-                to_notify.push(*id);
+            if let Some(ctx) = state.clients.get_mut(&client_id) {
+                ctx.theme = name.to_owned();
             }
         }
-        self.notify_view_update(to_notify);
+        self.notify_theme(client_id);
         Ok(())
     }
 
-    pub fn modify_view<F>(&mut self, view_id: &str, f: F)
-    where
-        F: Fn(&mut View),
-    {
-        let mut new_view = lock!(self).views[view_id].borrow().clone();
-        let old_key = new_view.key();
-        f(&mut new_view);
-        let new_key = new_view.key();
-        if old_key != new_key {
-            if !new_view.is_empty() {
-                for (_id, context) in lock!(self).clients.iter_mut() {
-                    if let Some(old_sels) = context.selections.remove(&old_key) {
-                        context.selections.insert(new_key.clone(), old_sels);
-                    }
-                }
-                self.add_view(new_view);
-            }
-            self.delete_view(&old_key).expect("delete old view");
-        }
+    /// Names of the themes loaded, along with whether it is the one of the client.
+    pub fn themes(&self, client_id: usize) -> Vec<(String, bool)> {
+        let state = lock!(self);
+        let current = state.clients.get(&client_id).map(|ctx| ctx.theme.as_str());
+        state
+            .themes
+            .iter()
+            .map(|t| (t.name.clone(), Some(t.name.as_str()) == current))
+            .collect()
     }
 
-    pub fn edit(&mut self, client_id: usize, name: &str, scratch: bool) {
-        // TODO check for same file but different name
-        let exists = self.buffer_exists(name);
-        let notify_change = if scratch {
-            if !exists {
-                self.open_scratch(name, String::new());
-            }
-            false
-        } else if exists {
-            let reloaded = lock!(self).buffers.get_mut(name).unwrap().load_from_disk();
-            if reloaded {
-                self.debug(&format!("reloaded from disk: {}", name));
-            }
-            reloaded
-        } else {
-            let path = {
-                let mut absolute = self.cwd();
-                absolute.push(name);
-                absolute
+    /// Names of the themes loaded.
+    pub fn theme_names(&self) -> Vec<String> {
+        lock!(self).themes.iter().map(|t| t.name.clone()).collect()
+    }
+
+    fn notify_theme(&self, client_id: usize) {
+        let theme = {
+            let state = lock!(self);
+            let name = match state.clients.get(&client_id) {
+                Some(ctx) => &ctx.theme,
+                None => return,
             };
-            self.open_file(name, &path);
-            true
+            state.themes.iter().find(|t| &t.name == name).cloned()
         };
+        if let Some(theme) = theme {
+            self.notifier.theme_update(client_id, &theme);
+        }
+    }
 
-        let view = View::for_buffer(name);
-        let view_id = view.key();
-        self.add_view(view);
-        self.view(client_id, &view_id).expect("set view after edit");
+    /// Names of the grammars loaded.
+    pub fn grammars(&self) -> Vec<String> {
+        lock!(self)
+            .grammars
+            .iter()
+            .map(|g| g.name.clone())
+            .collect()
+    }
 
-        self.debug(&format!("edit: {}", name));
-        if notify_change {
-            let client_ids = self
-                .clients_with_buffer(name)
-                .into_iter()
-                .filter(|&id| id != client_id)
-                .collect();
-            self.notify_view_update(client_ids);
-        }
+    pub fn save_buffer(&mut self, name: &str, force: bool) -> Result<PathBuf, Error> {
+        let path = lock!(self)
+            .buffers
+            .get_mut(name)
+            .ok_or_else(|| Error::BufferNotFound {
+                name: name.to_owned(),
+            })?
+            .save(force)?;
+        self.debug(&format!("written: {}", path.display()));
+        Ok(path)
     }
 
-    pub fn view(&mut self, client_id: usize, view_id: &str) -> Result<(), Error> {
-        if self.view_exists(view_id) {
-            {
-                let mut state = lock!(self);
-                let view = Rc::clone(&state.views[view_id]);
-                state.clients.get_mut(&client_id).unwrap().view = view;
+    /// Saves the file buffers of the client's current view, or only `buffer` if given.
+    pub fn write(
+        &mut self,
+        client_id: usize,
+        buffer: Option<&str>,
+        force: bool,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let names = match buffer {
+            Some(name) => vec![name.to_owned()],
+            None => {
+                let state = lock!(self);
+                let view = state.clients[&client_id].view.borrow();
+                view.buffers()
+                    .into_iter()
+                    .filter(|&b| matches!(state.buffers[b].source, BufferSource::File(_)))
+                    .cloned()
+                    .collect()
             }
-            self.notify_view_update(vec![client_id]);
-            Ok(())
-        } else if self.buffer_exists(view_id) {
-            {
-                let mut state = lock!(self);
-                let view = Rc::new(RefCell::new(View::for_buffer(view_id)));
-                let key = view.borrow().key();
-                let context = state.clients.get_mut(&client_id).unwrap();
-                context.view = Rc::clone(&view);
-                state.views.entry(key).or_insert(view);
+        };
+        names
+            .iter()
+            .map(|name| self.save_buffer(name, force))
+            .collect()
+    }
+
+    /// Changes the line breaks written for the file buffers of the client's current view.
+    pub fn set_line_ending(&mut self, client_id: usize, line_ending: LineEnding) -> Vec<String> {
+        let mut changed = Vec::new();
+        {
+            let mut state = lock!(self);
+            let names = state.clients[&client_id]
+                .view
+                .borrow()
+                .buffers()
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            for name in names {
+                let buffer = state.buffers.get_mut(&name).unwrap();
+                if matches!(buffer.source, BufferSource::File(_))
+                    && !buffer.is_read_only()
+                    && buffer.line_ending() != line_ending
+                {
+                    buffer.set_line_ending(line_ending);
+                    changed.push(name);
+                }
             }
-            self.notify_view_update(vec![client_id]);
-            Ok(())
-        } else {
-            Err(Error::ViewNotFound {
-                view_id: view_id.to_owned(),
-            })
         }
+        for name in &changed {
+            self.message(
+                client_id,
+                &format!("{}: {} line endings", name, line_ending),
+            );
+        }
+        changed
     }
 
-    pub fn delete_current_view(&mut self, client_id: usize) {
-        let view_id = lock!(self).clients[&client_id].view.borrow().key();
-        self.delete_view(&view_id).unwrap();
+    /// Saves every modified file buffer.
+    pub fn write_all(&mut self, force: bool) -> Result<Vec<PathBuf>, Error> {
+        let names: Vec<String> = lock!(self)
+            .buffers
+            .iter()
+            .filter(|(_, b)| b.is_modified() && matches!(b.source, BufferSource::File(_)))
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        names
+            .iter()
+            .map(|name| self.save_buffer(name, force))
+            .collect()
     }
 
-    pub fn add_to_current_view(&mut self, client_id: usize, buffer: &str) -> Result<(), Error> {
-        if !self.buffer_exists(buffer) {
-            return Err(Error::BufferNotFound {
-                name: buffer.to_owned(),
-            });
-        }
-
-        let view_id = lock!(self).clients[&client_id].view.borrow().key();
-        self.modify_view(&view_id, |view| {
-            view.add_lens(Lens {
-                buffer: buffer.to_owned(),
-                focus: Focus::Whole,
-            });
-        });
-        Ok(())
+    /// Paths of the files open in buffers, new files aside.
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        lock!(self)
+            .buffers
+            .values()
+            .filter(|b| !b.is_new_file())
+            .filter_map(|b| match b.source {
+                BufferSource::File(ref path) => Some(path.clone()),
+                BufferSource::Scratch(_) => None,
+            })
+            .collect()
     }
 
-    pub fn remove_from_current_view(
-        &mut self,
-        client_id: usize,
-        buffer: &str,
-    ) -> Result<(), Error> {
-        if !self.buffer_exists(buffer) {
-            return Err(Error::BufferNotFound {
-                name: buffer.to_owned(),
-            });
+    /// Replaces the content of `name` with the one of its file, the selections staying on the
+    /// same text.
+    pub fn reload_buffer(&mut self, name: &str) -> Result<(), Error> {
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            state
+                .buffers
+                .get_mut(name)
+                .ok_or_else(|| Error::BufferNotFound {
+                    name: name.to_owned(),
+                })?
+                .reload()?;
+            Self::apply_changes(state, name);
+            state.conflicts.remove(name);
         }
-
-        let view_id = lock!(self).clients[&client_id].view.borrow().key();
-        self.modify_view(&view_id, |view| {
-            if view.contains_buffer(buffer) {
-                view.remove_lens_group(buffer);
-            }
-        });
+        self.notify_buffers_update(&[name.to_owned()]);
         Ok(())
     }
 
-    pub fn move_cursor(&mut self, client_id: usize, direction: CursorTarget, extend: bool) {
-        let ctx = lock!(self).clients[&client_id].clone();
-        let curview = ctx.view.borrow().key();
-        let mut selections = ctx.selections[&curview].clone();
-        let mut moved = false;
-        for (b, bss) in selections.iter_mut() {
+    /// Looks for the buffers whose file is among the `paths` changed on disk. Buffers without
+    /// unsaved changes are reloaded, the other ones are returned if they were not already in
+    /// conflict with their file.
+    pub fn check_files(&mut self, paths: &[PathBuf]) -> Vec<String> {
+        let changed = lock!(self)
+            .buffers
+            .iter()
+            .filter(|(_, b)| match b.source {
+                BufferSource::File(ref path) => {
+                    paths.contains(path) && path.is_file() && !b.is_new_file() && !b.is_synced()
+                }
+                BufferSource::Scratch(_) => false,
+            })
+            .map(|(name, b)| (name.to_owned(), b.is_modified()))
+            .collect::<Vec<_>>();
+
+        let mut conflicts = Vec::new();
+        for (name, modified) in changed {
+            if modified {
+                if lock!(self).conflicts.insert(name.clone()) {
+                    conflicts.push(name);
+                }
+                continue;
+            }
+            let result = self.reload_buffer(&name);
+            for client_id in self.clients_with_buffer(&name) {
+                match &result {
+                    Ok(()) => self.message(client_id, &format!("reloaded: {}", name)),
+                    Err(e) => self.error(client_id, "reload", &e.to_string()),
+                }
+            }
+        }
+        conflicts.sort();
+        conflicts
+    }
+
+    /// Buffers with unsaved changes whose file also changed on disk.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = lock!(self).conflicts.iter().cloned().collect::<Vec<_>>();
+        conflicts.sort();
+        conflicts
+    }
+
+    /// Settles a conflict in favor of the buffer, the file being overwritten on the next save.
+    pub fn keep_buffer(&mut self, name: &str) -> Result<(), Error> {
+        let mut state = lock!(self);
+        state
+            .buffers
+            .get_mut(name)
+            .ok_or_else(|| Error::BufferNotFound {
+                name: name.to_owned(),
+            })?
+            .mark_synced();
+        state.conflicts.remove(name);
+        Ok(())
+    }
+
+    /// Shows the differences between `name` and its file in a scratch buffer.
+    pub fn diff_buffer(&mut self, client_id: usize, name: &str) -> Result<(), Error> {
+        let diff = {
+            let state = lock!(self);
+            let buffer = state
+                .buffers
+                .get(name)
+                .ok_or_else(|| Error::BufferNotFound {
+                    name: name.to_owned(),
+                })?;
+            line_diff(&buffer.content.text(), &buffer.disk_text()?)
+        };
+        self.show_scratch(client_id, &format!("*diff {}*", name), diff)
+    }
+
+    fn show_scratch(&mut self, client_id: usize, name: &str, content: String) -> Result<(), Error> {
+        self.open_scratch(name, content);
+        Self::clamp_all_selections(&mut lock!(self), name);
+        self.edit(client_id, name, true)
+    }
+
+    pub fn set_swap_dir(&mut self, dir: PathBuf) {
+        lock!(self).swap_dir = Some(dir);
+    }
+
+    /// Records the unsaved changes of the file buffers in their journal, and drops the journals
+    /// of the buffers without any.
+    pub fn write_journals(&mut self) {
+        let mut guard = lock!(self);
+        let state = &mut *guard;
+        let dir = match state.swap_dir {
+            Some(ref dir) => dir.clone(),
+            None => return,
+        };
+        for (name, buffer) in state.buffers.iter() {
+            let source = match buffer.source {
+                BufferSource::File(ref path) => path,
+                BufferSource::Scratch(_) => continue,
+            };
+            // a leftover journal is kept until recovered or discarded
+            if !buffer.is_modified() || state.recoveries.contains_key(name) {
+                if let Some(journal) = state.journals.remove(name) {
+                    journal
+                        .remove()
+                        .unwrap_or_else(|e| log::warn!("remove journal of {}: {}", name, e));
+                }
+                continue;
+            }
+            let text = buffer.content.text();
+            let result = match state.journals.get_mut(name) {
+                Some(journal) => journal.record(&text),
+                None => match Journal::create(
+                    swap_path(&dir, source, std::process::id()),
+                    source,
+                    &text,
+                ) {
+                    Ok(journal) => {
+                        state.journals.insert(name.to_owned(), journal);
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            if let Err(e) = result {
+                log::warn!("journal of {}: {}", name, e);
+            }
+        }
+    }
+
+    /// Records the last unsaved changes and releases the journals, left behind for the next
+    /// process to recover until their buffer is saved or its changes discarded.
+    pub fn close_journals(&mut self) {
+        self.write_journals();
+        lock!(self).journals.clear();
+    }
+
+    /// Looks for the journal of `name` left by a process that went away without saving it, the
+    /// latest one if there are several.
+    fn find_recovery(&mut self, name: &str) -> bool {
+        let mut state = lock!(self);
+        let (paths, source) = match (&state.swap_dir, &state.buffers[name].source) {
+            (Some(dir), BufferSource::File(source)) => (swap_paths(dir, source), source.clone()),
+            _ => return false,
+        };
+        let latest = paths
+            .into_iter()
+            .filter_map(|path| Recovery::read(&path).ok().map(|recovery| (path, recovery)))
+            .filter(|(_, recovery)| recovery.source == source && recovery.is_leftover())
+            .max_by_key(|(_, recovery)| recovery.time);
+        match latest {
+            Some((path, _)) => {
+                state.recoveries.insert(name.to_owned(), path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Buffers with unsaved changes left by a previous process, with the time of the last one.
+    pub fn recoveries(&self) -> Vec<(String, SystemTime)> {
+        let mut recoveries = lock!(self)
+            .recoveries
+            .iter()
+            .map(|(name, path)| {
+                let time = Recovery::read(path)
+                    .map(|recovery| recovery.time)
+                    .unwrap_or_else(|_| SystemTime::now());
+                (name.to_owned(), time)
+            })
+            .collect::<Vec<_>>();
+        recoveries.sort();
+        recoveries
+    }
+
+    fn read_recovery(&self, name: &str) -> Result<Recovery, Error> {
+        let path = lock!(self)
+            .recoveries
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::NoRecovery {
+                name: name.to_owned(),
+            })?;
+        Recovery::read(&path).map_err(|e| Error::from_io(&path, e))
+    }
+
+    /// Brings back the unsaved changes left by a previous process, as unsaved changes again.
+    pub fn recover_buffer(&mut self, name: &str) -> Result<(), Error> {
+        let recovery = self.read_recovery(name)?;
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let buffer = state
+                .buffers
+                .get_mut(name)
+                .ok_or_else(|| Error::BufferNotFound {
+                    name: name.to_owned(),
+                })?;
+            buffer.replace_text(&recovery.text);
+            Self::apply_changes(state, name);
+        }
+        // the leftover is only removed once the changes are in the journal of this process
+        let path = lock!(self).recoveries.remove(name);
+        self.write_journals();
+        if let Some(path) = path {
+            fs::remove_file(&path)
+                .unwrap_or_else(|e| log::warn!("remove journal of {}: {}", name, e));
+        }
+        self.notify_buffers_update(&[name.to_owned()]);
+        Ok(())
+    }
+
+    pub fn discard_recovery(&mut self, name: &str) -> Result<(), Error> {
+        let path = lock!(self)
+            .recoveries
+            .remove(name)
+            .ok_or_else(|| Error::NoRecovery {
+                name: name.to_owned(),
+            })?;
+        fs::remove_file(&path).map_err(|e| Error::from_io(&path, e))
+    }
+
+    /// Shows the differences between the file of `name` and its recoverable content.
+    pub fn diff_recovery(&mut self, client_id: usize, name: &str) -> Result<(), Error> {
+        let recovery = self.read_recovery(name)?;
+        let diff = {
+            let state = lock!(self);
+            let buffer = state
+                .buffers
+                .get(name)
+                .ok_or_else(|| Error::BufferNotFound {
+                    name: name.to_owned(),
+                })?;
+            line_diff(&buffer.content.text(), &recovery.text)
+        };
+        self.show_scratch(client_id, &format!("*recovery {}*", name), diff)
+    }
+
+    /// Saves the buffers, the views and the selections of the client, its view last. The
+    /// selections of the other clients are left out: client ids don't outlive the session, and
+    /// the restoring client gets these ones.
+    pub fn save_session(&self, client_id: usize, path: &Path) -> Result<(), Error> {
+        let snapshot = {
+            let state = lock!(self);
+            let mut names = state
+                .buffers
+                .keys()
+                .filter(|&name| name != BUFFER_DEBUG)
+                .collect::<Vec<_>>();
+            names.sort();
+            let buffers = names
+                .into_iter()
+                .map(|name| match &state.buffers[name].source {
+                    BufferSource::File(path) => BufferSnapshot::File {
+                        name: name.to_owned(),
+                        path: path.to_owned(),
+                    },
+                    BufferSource::Scratch(_) => BufferSnapshot::Scratch {
+                        name: name.to_owned(),
+                        content: state.buffers[name].content.text(),
+                    },
+                })
+                .collect();
+
+            let context = &state.clients[&client_id];
+            let current = context.view.borrow().id().to_owned();
+            let mut keys = state
+                .views
+                .stack()
+                .iter()
+                .rev()
+                .filter(|&key| *key != current)
+                .collect::<Vec<_>>();
+            keys.push(&current);
+            let views = keys
+                .into_iter()
+                .map(|key| {
+                    let lenses = state.views[key]
+                        .borrow()
+                        .as_vec()
+                        .into_iter()
+                        .filter_map(|item| match item {
+                            ViewItem::Lens(lens) => Some(lens),
+                            ViewItem::Header(_) => None,
+                        })
+                        .collect();
+                    let selections = context
+                        .selections
+                        .get(key)
+                        .map(|sels_by_buffer| {
+                            sels_by_buffer
+                                .iter()
+                                .map(|(buffer, sels)| {
+                                    (buffer.to_owned(), sels.iter().map(Into::into).collect())
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    ViewSnapshot { lenses, selections }
+                })
+                .collect();
+
+            SessionSnapshot {
+                cwd: state.cwd.to_owned(),
+                buffers,
+                views,
+                macros: state.registers.macros(),
+            }
+        };
+        snapshot.write(path).map_err(|e| Error::from_io(path, e))
+    }
+
+    /// Opens the buffers and views of a saved session, the client getting the view it was
+    /// saved with. Files that cannot be opened anymore are reported and left out.
+    pub fn restore_session(&mut self, client_id: usize, path: &Path) -> Result<(), Error> {
+        let snapshot = SessionSnapshot::read(path).map_err(|e| Error::from_io(path, e))?;
+        {
+            let mut state = lock!(self);
+            state.cwd = snapshot.cwd;
+            for (register, keys) in snapshot.macros {
+                state.registers.set_macro(register, keys);
+            }
+        }
+
+        for buffer in snapshot.buffers {
+            match buffer {
+                BufferSnapshot::File { name, path } => {
+                    if self.buffer_exists(&name) {
+                        continue;
+                    }
+                    if let Err(e) = self.open_file(&name, &path) {
+                        self.error(client_id, "session", &e.to_string());
+                    } else if self.find_recovery(&name) {
+                        self.message(client_id, &format!("unsaved changes found: {}", name));
+                    }
+                }
+                BufferSnapshot::Scratch { name, content } => {
+                    self.open_scratch(&name, content);
+                    Self::clamp_all_selections(&mut lock!(self), &name);
+                }
+            }
+        }
+
+        let mut current = None;
+        for saved in snapshot.views {
+            let mut view = View::default();
+            {
+                let state = lock!(self);
+                for mut lens in saved.lenses {
+                    let line_count = match state.buffers.get(&lens.buffer) {
+                        Some(buffer) => buffer.line_count(),
+                        None => continue,
+                    };
+                    // the file may have shrunk since
+                    if let Focus::Range(range) = &mut lens.focus {
+                        range.end = range.end.min(line_count);
+                        if range.start >= range.end {
+                            continue;
+                        }
+                    }
+                    view.add_lens(lens);
+                }
+            }
+            if view.is_empty() {
+                continue;
+            }
+            let buffers = view.buffers().into_iter().cloned().collect::<Vec<_>>();
+            let key = self.add_view(view);
+
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let context = state.clients.get_mut(&client_id).unwrap();
+            let sels_by_buffer = context.selections.entry(key.clone()).or_default();
+            for (buffer, sels) in saved.selections {
+                if sels.is_empty() || !buffers.contains(&buffer) {
+                    continue;
+                }
+                let max_offset = state.buffers[&buffer].content.max_offset();
+                let sels = sels
+                    .iter()
+                    .map(|s| {
+                        let mut sel = Selection::from(s);
+                        sel.clamp_to(max_offset);
+                        sel
+                    })
+                    .collect();
+                sels_by_buffer.insert(buffer, sels);
+            }
+            current = Some(key);
+        }
+
+        match current {
+            Some(key) => self.view(client_id, &key),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds the view unless one with the same lenses exists, returning the id of the view. A
+    /// new view is known by its key, made unique if another view got it first.
+    pub fn add_view(&mut self, mut view: View) -> String {
+        let mut guard = lock!(self);
+        let state = &mut *guard;
+        let key = view.key();
+        let existing = state
+            .views
+            .stack()
+            .iter()
+            .find(|id| state.views[*id].borrow().key() == key)
+            .cloned();
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let mut id = key.clone();
+                let mut n = 1;
+                while state.views.contains_key(&id) {
+                    n += 1;
+                    id = format!("{}#{}", key, n);
+                }
+                view.set_id(id.clone());
+                state
+                    .views
+                    .insert(id.clone(), Rc::new(RefCell::new(view.clone())));
+                id
+            }
+        };
+        Self::init_selections(state, &id);
+        id
+    }
+
+    /// Gives every client a selection in each buffer of the view it has none in yet.
+    fn init_selections(state: &mut CoreState, view_id: &str) {
+        let view = state.views[view_id].borrow();
+        for (_id, context) in state.clients.iter_mut() {
+            let sels_by_view = context
+                .selections
+                .entry(view_id.to_owned())
+                .or_insert_with(HashMap::new);
+            for buffer in view.buffers() {
+                sels_by_view
+                    .entry(buffer.to_owned())
+                    .or_insert_with(|| vec![Selection::new()]);
+            }
+        }
+    }
+
+    pub fn delete_view(&mut self, view_id: &str) -> Result<(), Error> {
+        if !self.view_exists(view_id) {
+            return Err(Error::ViewNotFound {
+                view_id: view_id.to_owned(),
+            });
+        }
+
+        let view = lock!(self).views.remove(&view_id.to_owned()).unwrap();
+        self.debug(&format!("delete view: {}", view_id));
+        for (_id, context) in lock!(self).clients.iter_mut() {
+            context.selections.remove(view_id);
+        }
+        let buffers = view
+            .borrow()
+            .buffers()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        self.drop_unused_buffers(&buffers);
+        if lock!(self).views.is_empty() {
+            if lock!(self).buffers.is_empty() {
+                self.open_scratch(BUFFER_SCRATCH, String::new());
+            }
+            self.add_view(View::for_buffer(BUFFER_SCRATCH));
+        }
+        let mut to_notify = Vec::new();
+        let latest_view = Rc::clone(lock!(self).views.latest_value().expect("get latest view"));
+        for (id, context) in lock!(self).clients.iter_mut() {
+            if context.view.borrow().id() == view_id {
+                context.view = Rc::clone(&latest_view);
+                to_notify.push(*id);
+            }
+        }
+        self.notify_view_update(to_notify);
+        Ok(())
+    }
+
+    /// Deletes those of the buffers no view shows anymore.
+    fn drop_unused_buffers(&mut self, buffers: &[String]) {
+        for buffer in buffers {
+            let used = lock!(self)
+                .views
+                .values()
+                .any(|view| view.borrow().contains_buffer(buffer));
+            if !used {
+                lock!(self).buffers.remove(buffer);
+                self.debug(&format!("delete buffer: {}", buffer));
+            }
+        }
+    }
+
+    /// Changes the lenses of the view, which is deleted if none is left.
+    pub fn modify_view<F>(&mut self, view_id: &str, f: F)
+    where
+        F: Fn(&mut View),
+    {
+        let (empty, removed) = {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let mut view = state.views[view_id].borrow_mut();
+            let before = view.buffers().into_iter().cloned().collect::<Vec<_>>();
+            f(&mut view);
+            let removed = before
+                .into_iter()
+                .filter(|b| !view.contains_buffer(b))
+                .collect::<Vec<_>>();
+            (view.is_empty(), removed)
+        };
+        if empty {
+            self.delete_view(view_id).expect("delete empty view");
+            return;
+        }
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            Self::init_selections(state, view_id);
+            for ctx in state.clients.values_mut() {
+                if let Some(sels) = ctx.selections.get_mut(view_id) {
+                    sels.retain(|b, _| !removed.contains(b));
+                }
+            }
+        }
+        self.drop_unused_buffers(&removed);
+        let clients = lock!(self)
+            .clients
+            .iter()
+            .filter(|(_, ctx)| ctx.view.borrow().id() == view_id)
+            .map(|(id, _)| *id)
+            .collect();
+        self.notify_view_update(clients);
+    }
+
+    pub fn edit(&mut self, client_id: usize, name: &str, scratch: bool) -> Result<(), Error> {
+        // TODO check for same file but different name
+        let exists = self.buffer_exists(name);
+        let notify_change = if scratch {
+            if !exists {
+                self.open_scratch(name, String::new());
+            }
+            false
+        } else if exists {
+            let reloaded = {
+                let mut guard = lock!(self);
+                let state = &mut *guard;
+                let buffer = state.buffers.get_mut(name).unwrap();
+                // unsaved changes are only dropped on demand, from the conflict menu
+                let edits = if buffer.is_modified() {
+                    None
+                } else {
+                    buffer.load_from_disk()?
+                };
+                Self::apply_changes(state, name);
+                edits.is_some()
+            };
+            if reloaded {
+                self.debug(&format!("reloaded from disk: {}", name));
+            }
+            reloaded
+        } else {
+            let path = {
+                let mut absolute = self.cwd();
+                absolute.push(name);
+                absolute
+            };
+            self.open_file(name, &path)?;
+            let (new_file, encoding) = {
+                let buffer = &lock!(self).buffers[name];
+                (buffer.is_new_file(), buffer.encoding())
+            };
+            if new_file {
+                self.message(client_id, &format!("new file: {}", name));
+            } else if encoding != Encoding::default() {
+                self.message(client_id, &format!("{}: {}", name, encoding));
+            }
+            if self.find_recovery(name) {
+                self.message(client_id, &format!("unsaved changes found: {}", name));
+            }
+            true
+        };
+
+        let view = View::for_buffer(name);
+        let view_id = self.add_view(view);
+        self.view(client_id, &view_id).expect("set view after edit");
+
+        self.debug(&format!("edit: {}", name));
+        if notify_change {
+            let client_ids = self
+                .clients_with_buffer(name)
+                .into_iter()
+                .filter(|&id| id != client_id)
+                .collect();
+            self.notify_view_update(client_ids);
+        }
+        Ok(())
+    }
+
+    pub fn view(&mut self, client_id: usize, view_id: &str) -> Result<(), Error> {
+        let exists = self.view_exists(view_id);
+        if !exists && !self.buffer_exists(view_id) {
+            return Err(Error::ViewNotFound {
+                view_id: view_id.to_owned(),
+            });
+        }
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let current = Self::current_jump(state, client_id);
+            if current.view != view_id {
+                Self::push_jump(state, client_id, current);
+            }
+        }
+        let id = if exists {
+            view_id.to_owned()
+        } else {
+            self.add_view(View::for_buffer(view_id))
+        };
+        {
+            let mut state = lock!(self);
+            let view = Rc::clone(&state.views[&id]);
+            state.clients.get_mut(&client_id).unwrap().view = view;
+        }
+        self.notify_view_update(vec![client_id]);
+        Ok(())
+    }
+
+    pub fn delete_current_view(&mut self, client_id: usize) {
+        let view_id = lock!(self).clients[&client_id]
+            .view
+            .borrow()
+            .id()
+            .to_owned();
+        self.delete_view(&view_id).unwrap();
+    }
+
+    pub fn add_to_current_view(&mut self, client_id: usize, buffer: &str) -> Result<(), Error> {
+        if !self.buffer_exists(buffer) {
+            return Err(Error::BufferNotFound {
+                name: buffer.to_owned(),
+            });
+        }
+
+        let view_id = lock!(self).clients[&client_id]
+            .view
+            .borrow()
+            .id()
+            .to_owned();
+        self.modify_view(&view_id, |view| {
+            view.add_lens(Lens {
+                buffer: buffer.to_owned(),
+                focus: Focus::Whole,
+            });
+        });
+        Ok(())
+    }
+
+    /// Adds a lens on `lines` of the buffer, counted from 0, to the current view of the client.
+    pub fn add_lines_to_current_view(
+        &mut self,
+        client_id: usize,
+        buffer: &str,
+        lines: std::ops::Range<usize>,
+    ) -> Result<(), Error> {
+        let line_count = match lock!(self).buffers.get(buffer) {
+            Some(b) => b.line_count(),
+            None => {
+                return Err(Error::BufferNotFound {
+                    name: buffer.to_owned(),
+                })
+            }
+        };
+        let lines = lines.start..lines.end.min(line_count);
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let view_id = lock!(self).clients[&client_id]
+            .view
+            .borrow()
+            .id()
+            .to_owned();
+        self.modify_view(&view_id, |view| {
+            view.add_lens(Lens {
+                buffer: buffer.to_owned(),
+                focus: Focus::Range(lines.clone()),
+            });
+        });
+        Ok(())
+    }
+
+    pub fn remove_from_current_view(
+        &mut self,
+        client_id: usize,
+        buffer: &str,
+    ) -> Result<(), Error> {
+        if !self.buffer_exists(buffer) {
+            return Err(Error::BufferNotFound {
+                name: buffer.to_owned(),
+            });
+        }
+
+        let view_id = lock!(self).clients[&client_id]
+            .view
+            .borrow()
+            .id()
+            .to_owned();
+        self.modify_view(&view_id, |view| {
+            if view.contains_buffer(buffer) {
+                view.remove_lens_group(buffer);
+            }
+        });
+        Ok(())
+    }
+
+    /// Sets the characters making words along with the alphanumeric ones.
+    pub fn set_word_chars(&mut self, chars: &str) {
+        lock!(self).word_chars = WordChars::Class(chars.to_owned());
+    }
+
+    pub fn move_cursor(&mut self, client_id: usize, direction: CursorTarget, extend: bool) {
+        let words = lock!(self).word_chars.clone();
+        let ctx = lock!(self).clients[&client_id].clone();
+        let curview = ctx.view.borrow().id().to_owned();
+        let mut selections = ctx.selections[&curview].clone();
+        let before = Jump {
+            view: curview.clone(),
+            selections: selections.clone(),
+        };
+        let mut moved = false;
+        for (b, bss) in selections.iter_mut() {
             let buffer = &lock!(self).buffers[b];
             if buffer.content.len() == 1 {
                 // there is absolutly nowhere to move out to, let's bail out
                 continue;
             }
-            for s in bss.iter_mut() {
-                let original = &s.clone();
-                let coord = buffer.content.offset_to_coord(s.cursor);
-                let mut nv = buffer.content.navigate(coord).unwrap();
-                nv.target_col = s.target_col;
-                match direction {
-                    CursorTarget::Left => nv.previous(),
-                    CursorTarget::Right => nv.next(),
-                    CursorTarget::Up => nv.previous_line(),
-                    CursorTarget::Down => nv.next_line(),
-                    CursorTarget::LineBegin => nv.line_begin(),
-                    CursorTarget::LineEnd => nv.line_end(),
-                    CursorTarget::Begin => nv.begin(),
-                    CursorTarget::End => nv.end(),
+            for s in bss.iter_mut() {
+                let original = &s.clone();
+                let coord = buffer.content.offset_to_coord(s.cursor);
+                let mut nv = buffer.content.navigate(coord).unwrap();
+                nv.target_col = s.target_col;
+                match direction {
+                    CursorTarget::Left => nv.previous(),
+                    CursorTarget::Right => nv.next(),
+                    CursorTarget::Up => nv.previous_line(),
+                    CursorTarget::Down => nv.next_line(),
+                    CursorTarget::LineBegin => nv.line_begin(),
+                    CursorTarget::LineEnd => nv.line_end(),
+                    CursorTarget::Begin => nv.begin(),
+                    CursorTarget::End => nv.end(),
+                    CursorTarget::FirstNonBlank => nv.first_non_blank(),
+                    CursorTarget::NextWordStart => nv.next_word_start(&words),
+                    CursorTarget::NextWordEnd => nv.next_word_end(&words),
+                    CursorTarget::PreviousWordStart => nv.previous_word_start(&words),
+                    CursorTarget::NextBigWordStart => nv.next_word_start(&WordChars::Big),
+                    CursorTarget::NextBigWordEnd => nv.next_word_end(&WordChars::Big),
+                    CursorTarget::PreviousBigWordStart => nv.previous_word_start(&WordChars::Big),
+                    CursorTarget::NextParagraph => nv.next_paragraph(),
+                    CursorTarget::PreviousParagraph => nv.previous_paragraph(),
+                    CursorTarget::MatchingBracket => nv.matching_bracket(),
+                };
+                s.cursor = nv.pos().offset;
+                s.target_col = nv.target_col;
+                if !extend {
+                    s.anchor = s.cursor
+                }
+                moved |= s != original;
+            }
+            merge_overlapping(bss, |s| buffer.selection_range(s));
+        }
+        {
+            let mut state = lock!(self);
+            let is_jump = matches!(
+                direction,
+                CursorTarget::Begin
+                    | CursorTarget::End
+                    | CursorTarget::NextParagraph
+                    | CursorTarget::PreviousParagraph
+                    | CursorTarget::MatchingBracket
+            );
+            if moved && is_jump {
+                Self::push_jump(&mut state, client_id, before);
+            }
+            state
+                .clients
+                .get_mut(&client_id)
+                .unwrap()
+                .selections
+                .insert(curview, selections);
+        }
+        if moved {
+            self.notify_view_update(vec![client_id]);
+        }
+    }
+
+    /// Selects the object around each selection of the client, selections with none around
+    /// them staying as they are. Returns whether any selection changed.
+    pub fn select_object(&mut self, client_id: usize, object: TextObject, inner: bool) -> bool {
+        let words = lock!(self).word_chars.clone();
+        let changed = self.update_selections(client_id, |buffer, sels| {
+            let mut changed = false;
+            for s in sels.iter_mut() {
+                let range = buffer
+                    .content
+                    .offset_to_coord(s.cursor)
+                    .and_then(|coord| buffer.content.navigate(coord))
+                    .and_then(|nv| nv.object(object, inner, &words));
+                if let Some(range) = range {
+                    let original = *s;
+                    s.anchor = *range.start();
+                    s.cursor = *range.end();
+                    s.target_col = buffer
+                        .content
+                        .offset_to_coord(s.cursor)
+                        .map_or(1, |coord| coord.c);
+                    changed |= *s != original;
+                }
+            }
+            merge_overlapping(sels, |s| buffer.selection_range(s));
+            changed
+        });
+        let changed = changed.contains(&true);
+        if changed {
+            self.notify_view_update(vec![client_id]);
+        }
+        changed
+    }
+
+    /// Splits the selections of the client into one selection per line. Returns the number
+    /// of selections.
+    pub fn split_lines(&mut self, client_id: usize) -> usize {
+        let counts = self.update_selections(client_id, |buffer, sels| {
+            let text = buffer.content.text();
+            let mut lines = Vec::new();
+            for s in sels.iter() {
+                let range = buffer.selection_range(s);
+                let end = range.end.min(text.len());
+                let mut start = range.start;
+                while start < end {
+                    let line_end = text[start..end].find('\n').map_or(end, |i| start + i + 1);
+                    lines.push(Self::range_selection(buffer, &text, &(start..line_end)));
+                    start = line_end;
+                }
+            }
+            if !lines.is_empty() {
+                *sels = lines;
+            }
+            sels.len()
+        });
+        self.notify_view_update(vec![client_id]);
+        counts.iter().sum()
+    }
+
+    /// Splits the selections of the client on the matches of `pattern`, keeping what lies
+    /// between them. Returns the number of selections.
+    pub fn split_matching(
+        &mut self,
+        client_id: usize,
+        pattern: &str,
+        case: CaseMode,
+    ) -> Result<usize, Error> {
+        let regex = Self::build_pattern(pattern, case)?;
+        let counts = self.update_selections(client_id, |buffer, sels| {
+            let text = buffer.content.text();
+            let mut pieces = Vec::new();
+            for s in sels.iter() {
+                let range = buffer.selection_range(s);
+                let end = range.end.min(text.len());
+                let mut start = range.start;
+                for m in find_all(&regex, &text[range.start..end]) {
+                    if start < range.start + m.start {
+                        let piece = start..range.start + m.start;
+                        pieces.push(Self::range_selection(buffer, &text, &piece));
+                    }
+                    start = range.start + m.end;
+                }
+                if start < end {
+                    pieces.push(Self::range_selection(buffer, &text, &(start..end)));
+                }
+            }
+            if !pieces.is_empty() {
+                *sels = pieces;
+            }
+            sels.len()
+        });
+        self.notify_view_update(vec![client_id]);
+        Ok(counts.iter().sum())
+    }
+
+    /// Adds a copy of each selection of the client on the next lines below or above it where
+    /// its columns fit. Returns whether any selection was added.
+    pub fn duplicate_selections(&mut self, client_id: usize, above: bool) -> bool {
+        let added = self.update_selections(client_id, |buffer, sels| {
+            let content = &buffer.content;
+            let mut copies = Vec::new();
+            for s in sels.iter() {
+                let (anchor, cursor) = match (
+                    content.offset_to_coord(s.anchor),
+                    content.offset_to_coord(s.cursor),
+                ) {
+                    (Some(anchor), Some(cursor)) => (anchor, cursor),
+                    _ => continue,
+                };
+                let height = anchor.l.max(cursor.l) - anchor.l.min(cursor.l) + 1;
+                let mut shift = height;
+                loop {
+                    let moved = |c: Coords| {
+                        let l = if above {
+                            c.l.checked_sub(shift).filter(|l| *l > 0)
+                        } else {
+                            Some(c.l + shift).filter(|l| *l <= content.line_count())
+                        };
+                        l.map(|l| Coords { l, c: c.c })
+                    };
+                    let (a, c) = match (moved(anchor), moved(cursor)) {
+                        (Some(a), Some(c)) => (a, c),
+                        _ => break,
+                    };
+                    if let (Some(a), Some(c)) =
+                        (content.coord_to_offset(a), content.coord_to_offset(c))
+                    {
+                        copies.push(Selection {
+                            anchor: a,
+                            cursor: c,
+                            target_col: s.target_col,
+                        });
+                        break;
+                    }
+                    shift += height;
+                }
+            }
+            let count = sels.len();
+            sels.extend(copies);
+            merge_overlapping(sels, |s| buffer.selection_range(s));
+            sels.len() > count
+        });
+        self.notify_view_update(vec![client_id]);
+        added.contains(&true)
+    }
+
+    /// Makes the next selection of the client the main one, or the previous one if
+    /// `backward`.
+    pub fn rotate_selections(&mut self, client_id: usize, backward: bool) {
+        self.update_selections(client_id, |_, sels| {
+            if backward {
+                sels.rotate_right(1)
+            } else {
+                sels.rotate_left(1)
+            }
+        });
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// Drops every selection of the client but the main one.
+    pub fn keep_main_selection(&mut self, client_id: usize) {
+        self.update_selections(client_id, |_, sels| sels.truncate(1));
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// Drops the main selection of the client, the next one becoming the main one. Buffers
+    /// keep their last selection.
+    pub fn drop_main_selection(&mut self, client_id: usize) {
+        self.update_selections(client_id, |_, sels| {
+            if sels.len() > 1 {
+                sels.remove(0);
+            }
+        });
+        self.notify_view_update(vec![client_id]);
+    }
+
+    /// The selection of the non-empty `range` of `text`.
+    fn range_selection(buffer: &Buffer, text: &str, range: &std::ops::Range<usize>) -> Selection {
+        Selection {
+            anchor: range.start,
+            cursor: last_char_offset(text, range),
+            target_col: buffer
+                .content
+                .offset_to_coord(range.start)
+                .map_or(1, |c| c.c),
+        }
+    }
+
+    fn build_pattern(pattern: &str, case: CaseMode) -> Result<Regex, Error> {
+        build_regex(pattern, case).map_err(|error| Error::InvalidPattern {
+            pattern: pattern.to_owned(),
+            error,
+        })
+    }
+
+    /// Applies `f` to the selections of each buffer in the current view of the client, along
+    /// with the buffer. Returns what `f` returned for each buffer.
+    fn update_selections<F, R>(&mut self, client_id: usize, mut f: F) -> Vec<R>
+    where
+        F: FnMut(&Buffer, &mut Vec<Selection>) -> R,
+    {
+        let mut guard = lock!(self);
+        let state = &mut *guard;
+        let context = state.clients.get_mut(&client_id).unwrap();
+        let key = context.view.borrow().id().to_owned();
+        let mut results = Vec::new();
+        if let Some(sels_by_buffer) = context.selections.get_mut(&key) {
+            for (name, sels) in sels_by_buffer.iter_mut() {
+                let buffer = &state.buffers[name];
+                results.push(f(buffer, sels));
+            }
+        }
+        results
+    }
+
+    /// Moves each selection of the client onto the next match of `pattern`, wrapping around
+    /// its buffer, and remembers the search to highlight it. Returns whether anything matched.
+    pub fn search(
+        &mut self,
+        client_id: usize,
+        pattern: &str,
+        case: CaseMode,
+        direction: Direction,
+        extend: bool,
+    ) -> Result<bool, Error> {
+        let regex = Self::build_pattern(pattern, case)?;
+        lock!(self).clients.get_mut(&client_id).unwrap().search = Some(regex);
+        Ok(self.search_next(client_id, direction, extend))
+    }
+
+    /// Repeats the last search of the client.
+    pub fn search_next(&mut self, client_id: usize, direction: Direction, extend: bool) -> bool {
+        let regex = match &lock!(self).clients[&client_id].search {
+            Some(regex) => regex.clone(),
+            None => return false,
+        };
+        let before = Self::current_jump(&lock!(self), client_id);
+        let results = self.update_selections(client_id, |buffer, sels| {
+            let text = buffer.content.text();
+            let mut found = false;
+            let mut wrapped = false;
+            for s in sels.iter_mut() {
+                let from = match direction {
+                    Direction::Forward => s.end(),
+                    Direction::Backward => s.begin(),
+                };
+                if let Some((m, w)) = find_next(&regex, &text, from, direction) {
+                    let last = last_char_offset(&text, &m);
+                    let (anchor, cursor) = match direction {
+                        Direction::Forward => (m.start, last),
+                        Direction::Backward => (last, m.start),
+                    };
+                    if !extend {
+                        s.anchor = anchor;
+                    }
+                    s.cursor = cursor;
+                    s.target_col = buffer.content.offset_to_coord(cursor).map_or(1, |c| c.c);
+                    found = true;
+                    wrapped |= w;
+                }
+            }
+            (found, wrapped)
+        });
+        let found = results.iter().any(|&(found, _)| found);
+        if found {
+            Self::push_jump(&mut lock!(self), client_id, before);
+        }
+        if !found {
+            self.message(client_id, &format!("no match: {}", regex));
+        } else if results.iter().any(|&(_, wrapped)| wrapped) {
+            self.message(client_id, "search wrapped");
+        }
+        self.notify_view_update(vec![client_id]);
+        found
+    }
+
+    pub fn clear_search(&mut self, client_id: usize) {
+        let cleared = lock!(self)
+            .clients
+            .get_mut(&client_id)
+            .unwrap()
+            .search
+            .take()
+            .is_some();
+        if cleared {
+            self.notify_view_update(vec![client_id]);
+        }
+    }
+
+    /// Replaces the selections of the client with the matches of `pattern` they contain.
+    /// Buffers without any match keep their selections. Returns the number of matches.
+    pub fn select_matches(
+        &mut self,
+        client_id: usize,
+        pattern: &str,
+        case: CaseMode,
+    ) -> Result<usize, Error> {
+        let regex = Self::build_pattern(pattern, case)?;
+        let counts = self.update_selections(client_id, |buffer, sels| {
+            let text = buffer.content.text();
+            let mut matches = Vec::new();
+            for s in sels.iter() {
+                let range = buffer.selection_range(s);
+                let end = range.end.min(text.len());
+                for m in find_all(&regex, &text[range.start..end]) {
+                    let m = m.start + range.start..m.end + range.start;
+                    matches.push(Self::range_selection(buffer, &text, &m));
+                }
+            }
+            matches.sort_by_key(|s| (s.begin(), s.end()));
+            matches.dedup();
+            let count = matches.len();
+            if count > 0 {
+                *sels = matches;
+            }
+            count
+        });
+        let count = counts.iter().sum();
+        self.notify_view_update(vec![client_id]);
+        Ok(count)
+    }
+
+    /// Keeps the selections of the client whose text matches `pattern`, or the ones that do
+    /// not if `keep` is false. Buffers left without any selection keep them all. Returns the
+    /// number of selections dropped.
+    pub fn keep_matching(
+        &mut self,
+        client_id: usize,
+        pattern: &str,
+        case: CaseMode,
+        keep: bool,
+    ) -> Result<usize, Error> {
+        let regex = Self::build_pattern(pattern, case)?;
+        let dropped = self.update_selections(client_id, |buffer, sels| {
+            let text = buffer.content.text();
+            let kept = sels
+                .iter()
+                .filter(|s| {
+                    let range = buffer.selection_range(s);
+                    let selected = &text[range.start..range.end.min(text.len())];
+                    regex.is_match(selected) == keep
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            if kept.is_empty() {
+                return 0;
+            }
+            let dropped = sels.len() - kept.len();
+            *sels = kept;
+            dropped
+        });
+        self.notify_view_update(vec![client_id]);
+        Ok(dropped.iter().sum())
+    }
+
+    /// Searches the files under the working directory, ignored ones aside, and shows each
+    /// matching line in a lens of its buffer, along with the lines around it that are not
+    /// shown for another match. The buffers are opened if needed, so that the results are
+    /// edited in place, and each one selects its first match. Returns the number of matching
+    /// lines.
+    pub fn grep(
+        &mut self,
+        client_id: usize,
+        pattern: &str,
+        case: CaseMode,
+    ) -> Result<usize, Error> {
+        let regex = Self::build_pattern(pattern, case)?;
+        let cwd = self.cwd();
+        let mut view = View::default();
+        let mut first_matches = Vec::new();
+        let mut count = 0;
+        let mut truncated = false;
+        let mut paths = Walk::new(&cwd)
+            .filter_map(std::result::Result::ok)
+            .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()))
+            .map(|e| e.into_path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in &paths {
+            let name = path
+                .strip_prefix(&cwd)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned();
+            if !self.buffer_exists(&name) {
+                // binary files are left out
+                match fs::read_to_string(path) {
+                    Ok(text) if !text.contains('\0') && regex.is_match(&text) => {}
+                    _ => continue,
+                }
+                if let Err(e) = self.open_file(&name, path) {
+                    log::warn!("grep: {}", e);
+                    continue;
+                }
+            }
+
+            let (text, line_count) = {
+                let state = lock!(self);
+                let buffer = &state.buffers[&name];
+                (buffer.content.text(), buffer.line_count())
+            };
+            let mut lines = Vec::new();
+            let mut line_start = 0;
+            for (i, line) in text.lines().enumerate() {
+                if regex.is_match(line) {
+                    if lines.is_empty() {
+                        let first = regex.find(line).unwrap();
+                        let range = line_start + first.start()..line_start + first.end();
+                        let cursor = last_char_offset(&text, &range);
+                        first_matches.push((name.clone(), range.start, cursor));
+                    }
+                    lines.push(i);
+                }
+                line_start += line.len() + 1;
+            }
+            if count + lines.len() > GREP_MAX_LINES {
+                lines.truncate(GREP_MAX_LINES - count);
+                truncated = true;
+            }
+            count += lines.len();
+
+            // the lines between two close matches go to the lens of the second one
+            let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+            for line in lines {
+                let mut range =
+                    line.saturating_sub(GREP_CONTEXT)..(line + GREP_CONTEXT + 1).min(line_count);
+                if let Some(last) = ranges.last_mut() {
+                    last.end = last.end.min(line);
+                    range.start = range.start.max(last.end);
+                }
+                ranges.push(range);
+            }
+            for range in ranges {
+                view.add_lens(Lens {
+                    buffer: name.clone(),
+                    focus: Focus::Range(range),
+                });
+            }
+            if truncated {
+                break;
+            }
+        }
+
+        if view.is_empty() {
+            self.message(client_id, &format!("no match: {}", pattern));
+            return Ok(0);
+        }
+        if truncated {
+            self.message(
+                client_id,
+                &format!("too many matches, showing the first {} lines", count),
+            );
+        }
+        let id = self.add_view(view);
+        {
+            let mut state = lock!(self);
+            let context = state.clients.get_mut(&client_id).unwrap();
+            let sels_by_buffer = context.selections.entry(id.clone()).or_default();
+            for (name, anchor, cursor) in first_matches {
+                let sel = Selection {
+                    anchor,
+                    cursor,
+                    ..Selection::new()
                 };
-                s.cursor = nv.pos().offset;
-                s.target_col = nv.target_col;
-                if !extend {
-                    s.anchor = s.cursor
+                sels_by_buffer.insert(name, vec![sel]);
+            }
+        }
+        self.view(client_id, &id)?;
+        Ok(count)
+    }
+
+    /// Sets the commands bridging the clipboard register to the system clipboard, none leaving
+    /// it a plain register.
+    pub fn set_clipboard(&mut self, clipboard: Option<Clipboard>) {
+        lock!(self).registers.clipboard = clipboard;
+    }
+
+    pub fn register(&self, name: char) -> Result<Vec<String>, Error> {
+        // the clipboard command is run once the state is unlocked
+        let clipboard = lock!(self).registers.clipboard_of(name);
+        match clipboard {
+            Some(clipboard) => Ok(vec![clipboard.paste()?]),
+            None => lock!(self).registers.get(name),
+        }
+    }
+
+    pub fn set_register(&mut self, name: char, values: Vec<String>) -> Result<(), Error> {
+        let clipboard = lock!(self).registers.clipboard_of(name);
+        match clipboard {
+            Some(clipboard) => clipboard.copy(&values.concat()),
+            None => lock!(self).registers.set(name, values),
+        }
+    }
+
+    /// Stores the selected text of the client in the register, a value per selection. Returns
+    /// the number of values.
+    pub fn yank(&mut self, client_id: usize, register: char) -> Result<usize, Error> {
+        let mut yanked = Vec::new();
+        {
+            let state = lock!(self);
+            let ctx = &state.clients[&client_id];
+            let view = ctx.view.borrow();
+            for bufname in view.buffers() {
+                let buffer = &state.buffers[bufname];
+                let sels = ctx.selections[view.id()].get(bufname);
+                for sel in sels.into_iter().flatten() {
+                    if let Some(text) = buffer.content.text_range(&buffer.selection_range(sel)) {
+                        yanked.push(text);
+                    }
                 }
-                moved |= s != original;
             }
         }
+        let count = yanked.len();
+        self.set_register(register, yanked)?;
+        Ok(count)
+    }
+
+    /// Inserts the values of the register before, after or in place of the selections of the
+    /// client, a value per selection, and selects them. Returns false if the register is
+    /// empty.
+    pub fn paste(
+        &mut self,
+        client_id: usize,
+        register: char,
+        target: InsertTarget,
+    ) -> Result<bool, Error> {
+        let values = self.register(register)?;
+        if values.is_empty() {
+            return Ok(false);
+        }
+        self.insert_texts(client_id, &values, target, true);
+        Ok(true)
+    }
+
+    /// Starts recording the keys of the client in the register, a previous recording being
+    /// dropped.
+    pub fn start_recording(&mut self, client_id: usize, register: char) {
+        if let Some(ctx) = lock!(self).clients.get_mut(&client_id) {
+            ctx.recording = Some((register, Vec::new()));
+        }
+    }
+
+    /// Stops recording the keys of the client and stores them. Returns the register and the
+    /// number of keys, if the client was recording.
+    pub fn stop_recording(&mut self, client_id: usize) -> Option<(char, usize)> {
+        let mut guard = lock!(self);
+        let state = &mut *guard;
+        let (register, keys) = state.clients.get_mut(&client_id)?.recording.take()?;
+        let count = keys.len();
+        state.registers.set_macro(register, keys);
+        Some((register, count))
+    }
+
+    /// The register the keys of the client are recorded in, if any.
+    pub fn recording(&self, client_id: usize) -> Option<char> {
         lock!(self)
+            .clients
+            .get(&client_id)
+            .and_then(|ctx| ctx.recording.as_ref())
+            .map(|(register, _)| *register)
+    }
+
+    /// The keys of the macro in the register, the macro one if none is given.
+    pub fn macro_keys(&self, register: Option<&str>) -> Result<Vec<KeyEvent>, Error> {
+        let register = register_name(register, REGISTER_MACRO)?;
+        self.register(register)?
+            .iter()
+            .map(|key| {
+                key.parse().map_err(|_| Error::InvalidMacro {
+                    register,
+                    key: key.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn record_key(&mut self, client_id: usize, key: &str) {
+        if let Some((_, keys)) = lock!(self)
             .clients
             .get_mut(&client_id)
-            .unwrap()
-            .selections
-            .insert(curview, selections);
-        if moved {
-            self.notify_view_update(vec![client_id]);
+            .and_then(|ctx| ctx.recording.as_mut())
+        {
+            keys.push(key.to_owned());
         }
     }
 
-    fn clamp_selections(state: &mut MutexGuard<CoreState>, client_id: usize, bufname: &str) {
-        let max_offset = state.buffers[bufname].content.max_offset();
+    /// Sets the mark on the main cursor of each buffer in the view of the client. Returns
+    /// false if the view has no buffer.
+    pub fn set_mark(&mut self, client_id: usize, name: char) -> bool {
+        let mut guard = lock!(self);
+        let state = &mut *guard;
+        let ctx = &state.clients[&client_id];
+        let view_key = ctx.view.borrow().id().to_owned();
+        let mut set = false;
+        for (bufname, sels) in &ctx.selections[&view_key] {
+            if let (Some(buffer), Some(sel)) = (state.buffers.get_mut(bufname), sels.first()) {
+                buffer.content.set_mark(name, sel.cursor);
+                set = true;
+            }
+        }
+        set
+    }
+
+    /// Marks of the buffer, sorted by name.
+    pub fn marks(&self, buffer: &str) -> Result<Vec<(char, usize)>, Error> {
+        lock!(self)
+            .buffers
+            .get(buffer)
+            .map(|b| b.content.marks())
+            .ok_or_else(|| Error::BufferNotFound {
+                name: buffer.to_owned(),
+            })
+    }
+
+    /// Moves the cursor of the client to the mark in each buffer of its view having it,
+    /// keeping only the main selection. Returns false if no buffer has the mark.
+    pub fn goto_mark(&mut self, client_id: usize, name: char, extend: bool) -> bool {
+        let before = Self::current_jump(&lock!(self), client_id);
+        let found = self.update_selections(client_id, |buffer, sels| {
+            let mark = match buffer.content.mark(name) {
+                Some(mark) => mark,
+                None => return false,
+            };
+            sels.truncate(1);
+            let s = &mut sels[0];
+            if !extend {
+                s.anchor = mark;
+            }
+            s.cursor = mark;
+            s.target_col = buffer.content.offset_to_coord(mark).map_or(1, |c| c.c);
+            true
+        });
+        if !found.contains(&true) {
+            return false;
+        }
+        Self::push_jump(&mut lock!(self), client_id, before);
+        self.notify_view_update(vec![client_id]);
+        true
+    }
+
+    fn current_jump(state: &CoreState, client_id: usize) -> Jump {
+        let ctx = &state.clients[&client_id];
+        let view = ctx.view.borrow().id().to_owned();
+        Jump {
+            selections: ctx.selections.get(&view).cloned().unwrap_or_default(),
+            view,
+        }
+    }
+
+    /// Adds the position the client leaves to its jump list, dropping the ones it went back
+    /// over.
+    fn push_jump(state: &mut CoreState, client_id: usize, jump: Jump) {
+        let ctx = match state.clients.get_mut(&client_id) {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        ctx.jumps.truncate(ctx.jump_index);
+        if ctx.jumps.last() != Some(&jump) {
+            ctx.jumps.push(jump);
+        }
+        if ctx.jumps.len() > MAX_JUMPS {
+            ctx.jumps.remove(0);
+        }
+        ctx.jump_index = ctx.jumps.len();
+    }
+
+    /// Puts the client back at the position, false if its view is gone.
+    fn restore_jump(state: &mut CoreState, client_id: usize, jump: &Jump) -> bool {
+        let view = match state.views.get(&jump.view) {
+            Some(view) => Rc::clone(view),
+            None => return false,
+        };
+        let buffers = &state.buffers;
         let ctx = state.clients.get_mut(&client_id).unwrap();
-        let view_key = ctx.view.borrow().key();
-        ctx.selections
-            .get_mut(&view_key)
-            .unwrap()
-            .get_mut(bufname)
-            .unwrap()
-            .iter_mut()
-            .for_each(|s| {
-                s.clamp_to(max_offset);
-            });
+        ctx.view = view;
+        let selections = ctx.selections.entry(jump.view.clone()).or_default();
+        for (bufname, sels) in &jump.selections {
+            if let Some(buffer) = buffers.get(bufname) {
+                let max = buffer.content.max_offset();
+                let sels = sels
+                    .iter()
+                    .map(|s| Selection {
+                        anchor: s.anchor.min(max),
+                        cursor: s.cursor.min(max),
+                        target_col: s.target_col,
+                    })
+                    .collect();
+                selections.insert(bufname.clone(), sels);
+            }
+        }
+        true
+    }
+
+    /// Goes back to the position the client was at before its last jump. Returns false if
+    /// there is none.
+    pub fn jump_back(&mut self, client_id: usize) -> bool {
+        let jumped = {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let current = Self::current_jump(state, client_id);
+            let ctx = state.clients.get_mut(&client_id).unwrap();
+            let mut index = ctx.jump_index;
+            if index >= ctx.jumps.len() {
+                // the current position is kept to come back to it
+                if ctx.jumps.last() != Some(&current) {
+                    ctx.jumps.push(current);
+                }
+                index = ctx.jumps.len() - 1;
+                ctx.jump_index = ctx.jumps.len();
+            }
+            loop {
+                if index == 0 {
+                    break false;
+                }
+                index -= 1;
+                let jump = state.clients[&client_id].jumps[index].clone();
+                let restored = Self::restore_jump(state, client_id, &jump);
+                let ctx = state.clients.get_mut(&client_id).unwrap();
+                if restored {
+                    ctx.jump_index = index;
+                    break true;
+                }
+                ctx.jumps.remove(index);
+            }
+        };
+        if jumped {
+            self.notify_view_update(vec![client_id]);
+        }
+        jumped
+    }
+
+    /// Goes forward to the position the client was at before going back. Returns false if
+    /// there is none.
+    pub fn jump_forward(&mut self, client_id: usize) -> bool {
+        let jumped = {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            loop {
+                let ctx = &state.clients[&client_id];
+                let index = ctx.jump_index + 1;
+                if index >= ctx.jumps.len() {
+                    break false;
+                }
+                let jump = ctx.jumps[index].clone();
+                let restored = Self::restore_jump(state, client_id, &jump);
+                let ctx = state.clients.get_mut(&client_id).unwrap();
+                if restored {
+                    ctx.jump_index = index;
+                    break true;
+                }
+                ctx.jumps.remove(index);
+            }
+        };
+        if jumped {
+            self.notify_view_update(vec![client_id]);
+        }
+        jumped
     }
 
-    pub fn delete_selection(&mut self, client_id: usize) -> Vec<String> {
+    /// Deletes the selected text of the client and stores it in the register, returning it in
+    /// the order of the selections of each buffer in its view.
+    pub fn delete_selection(
+        &mut self,
+        client_id: usize,
+        register: char,
+    ) -> Result<Vec<String>, Error> {
         let mut deleted = Vec::new();
+        let mut read_only = Vec::new();
         {
-            let mut modified_buffers = HashSet::new();
-            let mut state = lock!(self);
-            let ctx = &state.clients[&client_id];
-            let view_key = ctx.view.borrow().key();
-            for (bufname, sels) in &ctx.selections[&view_key].clone() {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let view_key = state.clients[&client_id].view.borrow().id().to_owned();
+            let bufnames = state.clients[&client_id]
+                .view
+                .borrow()
+                .buffers()
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            let selections = state.clients[&client_id].selections[&view_key].clone();
+            for bufname in &bufnames {
+                let sels = match selections.get(bufname) {
+                    Some(sels) => sels,
+                    None => continue,
+                };
                 let buffer = state
                     .buffers
-                    .get_mut(bufname)
+                    .get(bufname)
                     .unwrap_or_else(|| panic!("invalid buffer: {}", bufname));
+                if buffer.is_read_only() {
+                    read_only.push(bufname.to_owned());
+                    continue;
+                }
+                let mut ranges = Vec::new();
                 for sel in sels {
                     let srange = buffer.selection_range(sel);
                     if let Some(text) = buffer.content.text_range(&srange) {
-                        buffer.content.delete(&srange);
                         deleted.push(text);
-                        modified_buffers.insert(bufname.to_owned());
+                        ranges.push(srange);
+                    }
+                }
+                if ranges.is_empty() {
+                    continue;
+                }
+                ranges.sort_by_key(|range| range.start);
+                let mut merged: Vec<std::ops::Range<usize>> = Vec::new();
+                for range in ranges {
+                    match merged.last_mut() {
+                        Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                        _ => merged.push(range),
+                    }
+                }
+
+                Self::begin_change(state, client_id, bufname);
+                // delete from the end of the buffer so that pending offsets stay valid
+                for range in merged.into_iter().rev() {
+                    let buffer = state.buffers.get_mut(bufname).unwrap();
+                    buffer.delete(&range);
+                    if buffer.content.is_empty() {
+                        buffer.append("\n".to_owned());
+                    }
+                    Self::apply_changes(state, bufname);
+                }
+                Self::end_change(state, client_id, bufname);
+            }
+        }
+        self.report_read_only(client_id, &read_only);
+        if !deleted.is_empty() {
+            self.notify_view_update(vec![client_id]);
+            self.set_register(register, deleted.clone())?;
+        }
+        Ok(deleted)
+    }
+
+    fn report_read_only(&mut self, client_id: usize, buffers: &[String]) {
+        for name in buffers {
+            let error = Error::ReadOnly {
+                name: name.to_owned(),
+            };
+            self.error(client_id, "edit", &error.to_string());
+        }
+    }
+
+    /// Follows the changes made to `bufname` with the selections of every client and the range
+    /// lenses of every view.
+    fn apply_changes(state: &mut CoreState, bufname: &str) {
+        let changes = match state.buffers.get_mut(bufname) {
+            Some(buffer) => buffer.content.take_changes(),
+            None => return,
+        };
+        if changes.is_empty() {
+            return;
+        }
+        let max_offset = state.buffers[bufname].content.max_offset();
+        for (_id, ctx) in state.clients.iter_mut() {
+            for sels_by_buffer in ctx.selections.values_mut() {
+                if let Some(sels) = sels_by_buffer.get_mut(bufname) {
+                    for s in sels.iter_mut() {
+                        for Change { edit, .. } in &changes {
+                            s.shift(edit.offset, edit.removed, edit.inserted);
+                        }
+                        s.clamp_to(max_offset);
+                    }
+                }
+            }
+        }
+        let buffer = state.buffers.get_mut(bufname).unwrap();
+        if let Some(highlighter) = &mut buffer.highlighter {
+            highlighter.follow_changes(&changes);
+        }
+        let line_count = buffer.line_count();
+        Self::move_lenses(state, bufname, &changes, line_count);
+    }
+
+    /// Moves the range lenses on `bufname` along with its changes.
+    fn move_lenses(state: &mut CoreState, bufname: &str, changes: &[Change], line_count: usize) {
+        for view in state.views.values() {
+            view.borrow_mut()
+                .follow_changes(bufname, changes, line_count);
+        }
+    }
+
+    /// Keeps the selections of every client on `bufname` inside the buffer after a change that
+    /// cannot be described as a single edit.
+    fn clamp_all_selections(state: &mut CoreState, bufname: &str) {
+        let max_offset = state.buffers[bufname].content.max_offset();
+        for (_id, ctx) in state.clients.iter_mut() {
+            for sels_by_buffer in ctx.selections.values_mut() {
+                if let Some(sels) = sels_by_buffer.get_mut(bufname) {
+                    for s in sels.iter_mut() {
+                        s.clamp_to(max_offset);
+                    }
+                }
+            }
+        }
+    }
+
+    fn notify_buffers_update(&self, buffers: &[String]) {
+        let mut clients = Vec::new();
+        for bufname in buffers {
+            for id in self.clients_with_buffer(bufname) {
+                if !clients.contains(&id) {
+                    clients.push(id);
+                }
+            }
+        }
+        self.notify_view_update(clients);
+    }
+
+    pub fn insert(&mut self, client_id: usize, text: &str, target: InsertTarget) {
+        self.insert_texts(client_id, &[text.to_owned()], target, false);
+    }
+
+    /// Inserts the texts at the selections of the client, one per selection in order, the
+    /// last one going to the selections left. Selects what was inserted if `select` is set, as
+    /// replacing does anyway.
+    fn insert_texts(
+        &mut self,
+        client_id: usize,
+        texts: &[String],
+        target: InsertTarget,
+        select: bool,
+    ) {
+        let replace = matches!(target, InsertTarget::Replace);
+        if texts.iter().all(String::is_empty) && !replace {
+            return;
+        }
+        let mut modified_buffers = Vec::new();
+        let mut read_only = Vec::new();
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let view_key = state.clients[&client_id].view.borrow().id().to_owned();
+            let selections = state.clients[&client_id].selections[&view_key].clone();
+            for (bufname, sels) in selections {
+                let buffer = state
+                    .buffers
+                    .get(&bufname)
+                    .unwrap_or_else(|| panic!("invalid buffer: {}", bufname));
+                if buffer.is_read_only() {
+                    read_only.push(bufname);
+                    continue;
+                }
+                let mut edits = sels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, sel)| {
+                        let range = match target {
+                            InsertTarget::Cursor => sel.cursor..sel.cursor,
+                            InsertTarget::Before => sel.begin()..sel.begin(),
+                            InsertTarget::After => {
+                                let end = buffer.selection_range(sel).end;
+                                end..end
+                            }
+                            InsertTarget::Replace => buffer.selection_range(sel),
+                        };
+                        (i, range)
+                    })
+                    .collect::<Vec<_>>();
+                // edit from the end of the buffer so that pending offsets stay valid
+                edits.sort_by_key(|(_, range)| Reverse(range.start));
+                edits.dedup_by_key(|(_, range)| range.start);
+
+                Self::begin_change(state, client_id, &bufname);
+                for (i, range) in edits {
+                    let text = &texts[i.min(texts.len() - 1)];
+                    if text.is_empty() && range.is_empty() {
+                        continue;
+                    }
+                    let buffer = state.buffers.get_mut(&bufname).unwrap();
+                    if !range.is_empty() {
+                        buffer.delete(&range);
+                    }
+                    if !text.is_empty() {
+                        buffer.insert(range.start, text.to_owned());
                     }
+                    if buffer.content.is_empty() {
+                        buffer.append("\n".to_owned());
+                    }
+                    Self::apply_changes(state, &bufname);
+                    if replace || select {
+                        let max_offset = state.buffers[&bufname].content.max_offset();
+                        let sel = &mut state
+                            .clients
+                            .get_mut(&client_id)
+                            .unwrap()
+                            .selections
+                            .get_mut(&view_key)
+                            .and_then(|ss| ss.get_mut(&bufname))
+                            .unwrap()[i];
+                        let end = (range.start + text.len()).saturating_sub(1);
+                        if sel.anchor <= sel.cursor {
+                            sel.anchor = range.start;
+                            sel.cursor = end;
+                        } else {
+                            sel.anchor = end;
+                            sel.cursor = range.start;
+                        }
+                        sel.clamp_to(max_offset);
+                    }
+                }
+                Self::end_change(state, client_id, &bufname);
+                modified_buffers.push(bufname);
+            }
+        }
+        self.report_read_only(client_id, &read_only);
+        self.notify_buffers_update(&modified_buffers);
+    }
+
+    pub fn delete_before(&mut self, client_id: usize) {
+        let mut modified_buffers = Vec::new();
+        let mut read_only = Vec::new();
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let view_key = state.clients[&client_id].view.borrow().id().to_owned();
+            let selections = state.clients[&client_id].selections[&view_key].clone();
+            for (bufname, sels) in selections {
+                let buffer = &state.buffers[&bufname];
+                if buffer.is_read_only() {
+                    read_only.push(bufname);
+                    continue;
+                }
+                let mut ranges = sels
+                    .iter()
+                    .filter(|sel| sel.cursor > 0)
+                    .map(|sel| {
+                        let coord = buffer.content.offset_to_coord(sel.cursor);
+                        let previous = buffer
+                            .content
+                            .navigate(coord)
+                            .unwrap()
+                            .previous()
+                            .pos()
+                            .offset;
+                        previous..sel.cursor
+                    })
+                    .collect::<Vec<_>>();
+                ranges.sort_by_key(|range| Reverse(range.start));
+                ranges.dedup_by_key(|range| range.start);
+                if ranges.is_empty() {
+                    continue;
+                }
+
+                Self::begin_change(state, client_id, &bufname);
+                for range in ranges {
+                    state.buffers.get_mut(&bufname).unwrap().delete(&range);
+                    Self::apply_changes(state, &bufname);
+                }
+                Self::end_change(state, client_id, &bufname);
+                modified_buffers.push(bufname);
+            }
+        }
+        self.report_read_only(client_id, &read_only);
+        self.notify_buffers_update(&modified_buffers);
+    }
+
+    /// Opens an undo step on `bufname` saving the current selections of the client, unless the
+    /// client already changed the buffer in its current undo group.
+    fn begin_change(state: &mut CoreState, client_id: usize, bufname: &str) {
+        let ctx = state.clients.get_mut(&client_id).unwrap();
+        if ctx.undo_group_depth > 0 && !ctx.undo_group.insert(bufname.to_owned()) {
+            return;
+        }
+        let view_key = ctx.view.borrow().id().to_owned();
+        let sels = ctx
+            .selections
+            .get(&view_key)
+            .and_then(|ss| ss.get(bufname))
+            .cloned()
+            .unwrap_or_default();
+        if let Some(buffer) = state.buffers.get_mut(bufname) {
+            buffer.begin_change(&sels);
+        }
+    }
+
+    fn end_change(state: &mut CoreState, client_id: usize, bufname: &str) {
+        if state.clients[&client_id].undo_group_depth > 0 {
+            return;
+        }
+        if let Some(buffer) = state.buffers.get_mut(bufname) {
+            buffer.end_change();
+        }
+    }
+
+    /// Groups all the following edits of the client in a single undo step per buffer, until the
+    /// matching `end_undo_group`.
+    pub fn begin_undo_group(&mut self, client_id: usize) {
+        if let Some(ctx) = lock!(self).clients.get_mut(&client_id) {
+            ctx.undo_group_depth += 1;
+        }
+    }
+
+    pub fn end_undo_group(&mut self, client_id: usize) {
+        let mut guard = lock!(self);
+        let state = &mut *guard;
+        if let Some(ctx) = state.clients.get_mut(&client_id) {
+            if ctx.undo_group_depth == 0 {
+                return;
+            }
+            ctx.undo_group_depth -= 1;
+            if ctx.undo_group_depth == 0 {
+                for bufname in ctx.undo_group.drain() {
+                    if let Some(buffer) = state.buffers.get_mut(&bufname) {
+                        buffer.end_change();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reverts the last change of each buffer in the current view of the client. Returns false if
+    /// there was nothing to undo.
+    pub fn undo(&mut self, client_id: usize) -> bool {
+        self.history_step(client_id, None, |buffer, sels| buffer.undo(sels))
+    }
+
+    pub fn redo(&mut self, client_id: usize) -> bool {
+        self.history_step(client_id, None, |buffer, sels| buffer.redo(sels))
+    }
+
+    /// Brings the buffers in the current view of the client back to their state `duration` ago.
+    pub fn earlier(&mut self, client_id: usize, duration: Duration) -> bool {
+        self.history_step(client_id, None, |buffer, sels| {
+            buffer.earlier(duration, sels)
+        })
+    }
+
+    pub fn later(&mut self, client_id: usize, duration: Duration) -> bool {
+        self.history_step(client_id, None, |buffer, sels| buffer.later(duration, sels))
+    }
+
+    pub fn goto_revision(
+        &mut self,
+        client_id: usize,
+        bufname: &str,
+        revision: usize,
+    ) -> Result<bool, Error> {
+        if !self.current_buffers(client_id).iter().any(|b| b == bufname) {
+            return Err(Error::BufferNotFound {
+                name: bufname.to_owned(),
+            });
+        }
+        Ok(self.history_step(client_id, Some(bufname), |buffer, sels| {
+            buffer.goto_revision(revision, sels)
+        }))
+    }
+
+    /// Lists the revisions of the buffers in the current view of the client, along with a
+    /// summary of the changes between each revision and the current content.
+    pub fn revisions(&self, client_id: usize) -> Vec<RevisionEntry> {
+        let bufnames = self.current_buffers(client_id);
+        let state = lock!(self);
+        let mut entries = Vec::new();
+        for bufname in bufnames {
+            let content = &state.buffers[&bufname].content;
+            let current = content.revision();
+            let text = content.text();
+            for info in content.revisions() {
+                entries.push(RevisionEntry {
+                    buffer: bufname.clone(),
+                    info,
+                    current: info.id == current,
+                    preview: content
+                        .revision_text(info.id)
+                        .map(|rev_text| line_summary(&text, &rev_text))
+                        .unwrap_or_default(),
+                });
+            }
+        }
+        entries
+    }
+
+    fn current_buffers(&self, client_id: usize) -> Vec<String> {
+        lock!(self).clients[&client_id]
+            .view
+            .borrow()
+            .buffers()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Moves buffers of the current view of the client, or only `only` if given, to another
+    /// revision with `step`, restoring the selections of the client saved for that revision.
+    fn history_step<F>(&mut self, client_id: usize, only: Option<&str>, step: F) -> bool
+    where
+        F: Fn(&mut Buffer, &[Selection]) -> Option<Vec<Selection>>,
+    {
+        let mut modified_buffers = Vec::new();
+        {
+            let mut guard = lock!(self);
+            let state = &mut *guard;
+            let ctx = state.clients.get_mut(&client_id).unwrap();
+            let view_key = ctx.view.borrow().id().to_owned();
+            let bufnames = ctx
+                .view
+                .borrow()
+                .buffers()
+                .into_iter()
+                .filter(|&b| only.map(|o| o == b).unwrap_or(true))
+                .cloned()
+                .collect::<Vec<_>>();
+            for bufname in bufnames {
+                let ctx = state.clients.get_mut(&client_id).unwrap();
+                let buffer = state.buffers.get_mut(&bufname).unwrap();
+                // an open undo group must not swallow the history step
+                if ctx.undo_group.remove(&bufname) {
+                    buffer.end_change();
                 }
-                let buffer = &mut state.buffers.get_mut(bufname).unwrap();
-                if buffer.content.is_empty() {
-                    buffer.content.append("\n".to_owned());
+                let sels = ctx
+                    .selections
+                    .entry(view_key.clone())
+                    .or_default()
+                    .entry(bufname.clone())
+                    .or_insert_with(|| vec![Selection::new()])
+                    .clone();
+                if let Some(restored) = step(buffer, &sels) {
+                    Self::apply_changes(state, &bufname);
+                    if !restored.is_empty() {
+                        let max_offset = state.buffers[&bufname].content.max_offset();
+                        let sels = state
+                            .clients
+                            .get_mut(&client_id)
+                            .unwrap()
+                            .selections
+                            .get_mut(&view_key)
+                            .and_then(|ss| ss.get_mut(&bufname))
+                            .unwrap();
+                        *sels = restored;
+                        for s in sels.iter_mut() {
+                            s.clamp_to(max_offset);
+                        }
+                    }
+                    modified_buffers.push(bufname);
                 }
             }
-            // XXX selections reprocessing is made afterwards because of the borrow rules on struct attributes
-            // XXX in 2021 edition it should be possible to handle both operations in one pass
-            for bufname in modified_buffers {
-                Self::clamp_selections(&mut state, client_id, &bufname);
-            }
         }
-        if !deleted.is_empty() {
-            self.notify_view_update(vec![client_id]);
-        }
-        deleted
+        self.notify_buffers_update(&modified_buffers);
+        !modified_buffers.is_empty()
     }
 }
 
@@ -656,6 +3070,34 @@ impl<'lua> rlua::ToLua<'lua> for SelectionData {
     }
 }
 
+type SearchArgs = (usize, String, bool, bool, Option<String>);
+
+pub(crate) fn lua_error(error: Error) -> rlua::Error {
+    rlua::Error::RuntimeError(error.to_string())
+}
+
+fn lua_case_mode(case: Option<String>) -> rlua::Result<CaseMode> {
+    case.map_or(Ok(CaseMode::default()), |c| {
+        c.parse().map_err(rlua::Error::RuntimeError)
+    })
+}
+
+fn lua_mark(name: String) -> rlua::Result<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(lua_error(Error::InvalidMark { name })),
+    }
+}
+
+fn lua_direction(backward: bool) -> Direction {
+    if backward {
+        Direction::Backward
+    } else {
+        Direction::Forward
+    }
+}
+
 impl rlua::UserData for Core {
     fn add_methods<'lua, M: rlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method_mut("debug", |_, this, content: String| {
@@ -673,11 +3115,16 @@ impl rlua::UserData for Core {
 
         methods.add_method("get_context", |lua, this, client: usize| {
             let context = lock!(this).clients[&client].clone();
-            let view_key = context.view.borrow().key();
-            let sels = &context.selections[&view_key];
+            let view_id = context.view.borrow().id().to_owned();
+            let sels = &context.selections[&view_id];
 
             let view_t = lua.create_table()?;
-            view_t.set("key", view_key)?;
+            view_t.set("key", context.view.borrow().key())?;
+            view_t.set("id", view_id.as_str())?;
+            view_t.set("top", context.viewport.top + 1)?;
+            view_t.set("height", context.viewport.height)?;
+            view_t.set("wrap", context.layout.wrap.to_string())?;
+            view_t.set("column", context.layout.column + 1)?;
 
             let selections_t = lua.create_table()?;
             for (k, v) in sels {
@@ -708,17 +3155,35 @@ impl rlua::UserData for Core {
             "scratch",
             |_, this, (client, name, content): (usize, String, String)| {
                 this.open_scratch(&name, content);
-                this.edit(client, &name, true);
-                Ok(())
+                this.edit(client, &name, true).map_err(lua_error)
             },
         );
         methods.add_method_mut(
             "edit",
             |_, this, (client, name, scratch): (usize, String, bool)| {
-                this.edit(client, &name, scratch);
-                Ok(())
+                this.edit(client, &name, scratch).map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "set_line_ending",
+            |_, this, (client, line_ending): (usize, String)| {
+                let line_ending = line_ending.parse().map_err(rlua::Error::RuntimeError)?;
+                Ok(this.set_line_ending(client, line_ending).len())
+            },
+        );
+        methods.add_method_mut(
+            "write",
+            |_, this, (client, buffer, force): (usize, Option<String>, bool)| {
+                this.write(client, buffer.as_deref(), force)
+                    .map(|paths| paths.len())
+                    .map_err(lua_error)
             },
         );
+        methods.add_method_mut("write_all", |_, this, force: bool| {
+            this.write_all(force)
+                .map(|paths| paths.len())
+                .map_err(lua_error)
+        });
         methods.add_method_mut("append_to", |_, this, (buffer, text): (String, String)| {
             this.append_to(&buffer, text);
             Ok(())
@@ -756,16 +3221,722 @@ impl rlua::UserData for Core {
             this.move_cursor(client, CursorTarget::End, extend);
             Ok(())
         });
-        methods.add_method_mut("delete_selection", |_, this, client| {
-            let deleted = this.delete_selection(client);
-            Ok(deleted)
+        methods.add_method_mut("move_to_first_non_blank", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::FirstNonBlank, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_next_word_start", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::NextWordStart, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_next_word_end", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::NextWordEnd, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_previous_word_start", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::PreviousWordStart, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_next_big_word_start", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::NextBigWordStart, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_next_big_word_end", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::NextBigWordEnd, extend);
+            Ok(())
+        });
+        methods.add_method_mut(
+            "move_previous_big_word_start",
+            |_, this, (client, extend)| {
+                this.move_cursor(client, CursorTarget::PreviousBigWordStart, extend);
+                Ok(())
+            },
+        );
+        methods.add_method_mut("move_next_paragraph", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::NextParagraph, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_previous_paragraph", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::PreviousParagraph, extend);
+            Ok(())
+        });
+        methods.add_method_mut("move_to_matching_bracket", |_, this, (client, extend)| {
+            this.move_cursor(client, CursorTarget::MatchingBracket, extend);
+            Ok(())
+        });
+        methods.add_method_mut(
+            "select_object",
+            |_, this, (client, object, inner): (usize, String, bool)| {
+                let object = object.parse().map_err(rlua::Error::RuntimeError)?;
+                Ok(this.select_object(client, object, inner))
+            },
+        );
+        methods.add_method_mut("set_word_chars", |_, this, chars: String| {
+            this.set_word_chars(&chars);
+            Ok(())
+        });
+        methods.add_method_mut(
+            "search",
+            |_, this, (client, pattern, backward, extend, case): SearchArgs| {
+                this.search(
+                    client,
+                    &pattern,
+                    lua_case_mode(case)?,
+                    lua_direction(backward),
+                    extend,
+                )
+                .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "search_next",
+            |_, this, (client, backward, extend): (usize, bool, bool)| {
+                Ok(this.search_next(client, lua_direction(backward), extend))
+            },
+        );
+        methods.add_method_mut("clear_search", |_, this, client| {
+            this.clear_search(client);
+            Ok(())
+        });
+        methods.add_method_mut(
+            "select_matches",
+            |_, this, (client, pattern, case): (usize, String, Option<String>)| {
+                this.select_matches(client, &pattern, lua_case_mode(case)?)
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "keep_matching",
+            |_, this, (client, pattern, keep, case): (usize, String, bool, Option<String>)| {
+                this.keep_matching(client, &pattern, lua_case_mode(case)?, keep)
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "split_lines",
+            |_, this, client| Ok(this.split_lines(client)),
+        );
+        methods.add_method_mut(
+            "split_matching",
+            |_, this, (client, pattern, case): (usize, String, Option<String>)| {
+                this.split_matching(client, &pattern, lua_case_mode(case)?)
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "duplicate_selections",
+            |_, this, (client, above): (usize, bool)| Ok(this.duplicate_selections(client, above)),
+        );
+        methods.add_method_mut(
+            "rotate_selections",
+            |_, this, (client, backward): (usize, bool)| {
+                this.rotate_selections(client, backward);
+                Ok(())
+            },
+        );
+        methods.add_method_mut("keep_main_selection", |_, this, client| {
+            this.keep_main_selection(client);
+            Ok(())
+        });
+        methods.add_method_mut("drop_main_selection", |_, this, client| {
+            this.drop_main_selection(client);
+            Ok(())
+        });
+        methods.add_method_mut(
+            "add_lines_to_view",
+            |_, this, (client, buffer, first, last): (usize, String, usize, usize)| {
+                this.add_lines_to_current_view(client, &buffer, first.saturating_sub(1)..last)
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "grep",
+            |_, this, (client, pattern, case): (usize, String, Option<String>)| {
+                this.grep(client, &pattern, lua_case_mode(case)?)
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "delete_selection",
+            |_, this, (client, register): (usize, Option<String>)| {
+                let register =
+                    register_name(register.as_deref(), REGISTER_DEFAULT).map_err(lua_error)?;
+                this.delete_selection(client, register).map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "yank",
+            |_, this, (client, register): (usize, Option<String>)| {
+                let register =
+                    register_name(register.as_deref(), REGISTER_DEFAULT).map_err(lua_error)?;
+                this.yank(client, register).map_err(lua_error)
+            },
+        );
+        for (method, target) in [
+            ("paste_before", InsertTarget::Before),
+            ("paste_after", InsertTarget::After),
+            ("paste_replace", InsertTarget::Replace),
+        ] {
+            methods.add_method_mut(
+                method,
+                move |_, this, (client, register): (usize, Option<String>)| {
+                    let register =
+                        register_name(register.as_deref(), REGISTER_DEFAULT).map_err(lua_error)?;
+                    this.paste(client, register, target).map_err(lua_error)
+                },
+            );
+        }
+        methods.add_method("register", |_, this, name: Option<String>| {
+            let name = register_name(name.as_deref(), REGISTER_DEFAULT).map_err(lua_error)?;
+            this.register(name).map_err(lua_error)
+        });
+        methods.add_method_mut(
+            "set_register",
+            |_, this, (name, values): (Option<String>, Vec<String>)| {
+                let name = register_name(name.as_deref(), REGISTER_DEFAULT).map_err(lua_error)?;
+                this.set_register(name, values).map_err(lua_error)
+            },
+        );
+        methods.add_method_mut(
+            "start_recording",
+            |_, this, (client, register): (usize, Option<String>)| {
+                let register =
+                    register_name(register.as_deref(), REGISTER_MACRO).map_err(lua_error)?;
+                this.start_recording(client, register);
+                Ok(())
+            },
+        );
+        methods.add_method_mut("stop_recording", |_, this, client| {
+            Ok(this.stop_recording(client).map(|(_, count)| count))
+        });
+        methods.add_method("recording", |_, this, client| {
+            Ok(this.recording(client).map(|register| register.to_string()))
+        });
+        methods.add_method("macro", |_, this, register: Option<String>| {
+            let register = register_name(register.as_deref(), REGISTER_MACRO).map_err(lua_error)?;
+            this.register(register).map_err(lua_error)
+        });
+        methods.add_method_mut("set_mark", |_, this, (client, name): (usize, String)| {
+            Ok(this.set_mark(client, lua_mark(name)?))
+        });
+        methods.add_method_mut(
+            "goto_mark",
+            |_, this, (client, name, extend): (usize, String, bool)| {
+                Ok(this.goto_mark(client, lua_mark(name)?, extend))
+            },
+        );
+        methods.add_method("marks", |_, this, buffer: String| {
+            Ok(this
+                .marks(&buffer)
+                .map_err(lua_error)?
+                .into_iter()
+                .map(|(name, offset)| (name.to_string(), offset))
+                .collect::<HashMap<_, _>>())
+        });
+        methods.add_method_mut("scroll", |_, this, (client, lines): (usize, isize)| {
+            this.scroll(client, lines);
+            Ok(())
+        });
+        methods.add_method_mut(
+            "scroll_pages",
+            |_, this, (client, pages): (usize, isize)| {
+                this.scroll_pages(client, pages);
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "scroll_columns",
+            |_, this, (client, columns): (usize, isize)| {
+                this.scroll_columns(client, columns);
+                Ok(())
+            },
+        );
+        methods.add_method_mut("set_wrap", |_, this, (client, wrap): (usize, String)| {
+            this.set_wrap(client, wrap.parse().map_err(rlua::Error::RuntimeError)?);
+            Ok(())
+        });
+        methods.add_method_mut("set_tab_width", |_, this, width: usize| {
+            this.set_tab_width(width);
+            Ok(())
+        });
+        methods.add_method_mut("load_grammar", |_, this, path: String| {
+            this.load_grammar(Path::new(&path)).map_err(lua_error)
+        });
+        methods.add_method_mut(
+            "set_grammar",
+            |_, this, (buffer, name): (String, Option<String>)| {
+                this.set_grammar(&buffer, name.as_deref())
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method("grammars", |_, this, ()| Ok(this.grammars()));
+        methods.add_method_mut("load_theme", |_, this, path: String| {
+            this.load_theme(Path::new(&path)).map_err(lua_error)
+        });
+        methods.add_method_mut("set_theme", |_, this, (client, name): (usize, String)| {
+            this.set_theme(client, &name).map_err(lua_error)
+        });
+        methods.add_method("themes", |_, this, ()| Ok(this.theme_names()));
+        methods.add_method_mut("center_cursor", |_, this, client| {
+            this.center_cursor(client);
+            Ok(())
+        });
+        methods.add_method_mut("jump_back", |_, this, client| Ok(this.jump_back(client)));
+        methods.add_method_mut("jump_forward", |_, this, client| {
+            Ok(this.jump_forward(client))
+        });
+        methods.add_method_mut(
+            "set_clipboard",
+            |_, this, (copy, paste): (Option<String>, Option<String>)| {
+                let clipboard = match (copy, paste) {
+                    (Some(copy), Some(paste)) => Some(Clipboard { copy, paste }),
+                    _ => None,
+                };
+                this.set_clipboard(clipboard);
+                Ok(())
+            },
+        );
+        methods.add_method_mut("delete_before", |_, this, client| {
+            this.delete_before(client);
+            Ok(())
+        });
+        methods.add_method_mut("undo", |_, this, client| Ok(this.undo(client)));
+        methods.add_method_mut("redo", |_, this, client| Ok(this.redo(client)));
+        methods.add_method_mut("earlier", |_, this, (client, seconds): (usize, u64)| {
+            Ok(this.earlier(client, Duration::from_secs(seconds)))
+        });
+        methods.add_method_mut("later", |_, this, (client, seconds): (usize, u64)| {
+            Ok(this.later(client, Duration::from_secs(seconds)))
+        });
+        methods.add_method_mut(
+            "goto_revision",
+            |_, this, (client, buffer, revision): (usize, String, usize)| {
+                this.goto_revision(client, &buffer, revision)
+                    .map_err(lua_error)
+            },
+        );
+        methods.add_method_mut("begin_undo_group", |_, this, client| {
+            this.begin_undo_group(client);
+            Ok(())
+        });
+        methods.add_method_mut("end_undo_group", |_, this, client| {
+            this.end_undo_group(client);
+            Ok(())
+        });
+        methods.add_method_mut("insert_text", |_, this, (client, text): (usize, String)| {
+            this.insert(client, &text, InsertTarget::Cursor);
+            Ok(())
         });
+        methods.add_method_mut(
+            "insert_before",
+            |_, this, (client, text): (usize, String)| {
+                this.insert(client, &text, InsertTarget::Before);
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "insert_after",
+            |_, this, (client, text): (usize, String)| {
+                this.insert(client, &text, InsertTarget::After);
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "replace_selection",
+            |_, this, (client, text): (usize, String)| {
+                this.insert(client, &text, InsertTarget::Replace);
+                Ok(())
+            },
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_channel::{unbounded, Receiver};
+    use std::time::Instant;
+
+    fn core_with_clients(content: &str, clients: &[usize]) -> (Core, Receiver<BroadcastMessage>) {
+        let (tx, rx) = unbounded();
+        let mut core = Core::new(tx.into());
+        core.debug_mode = false;
+        core.open_scratch("test", content.to_owned());
+        core.add_view(View::for_buffer("test"));
+        let cwd = PathBuf::new();
+        let info = EditorInfo {
+            session: "",
+            cwd: &cwd,
+            buffers: &[],
+            views: &[],
+        };
+        for &id in clients {
+            core.add_client(id, &info);
+        }
+        (core, rx)
+    }
+
+    fn selections(core: &Core, client_id: usize) -> Vec<Selection> {
+        let state = lock!(core);
+        let ctx = &state.clients[&client_id];
+        let key = ctx.view.borrow().id().to_owned();
+        ctx.selections[&key]["test"].clone()
+    }
+
+    fn set_selection(core: &Core, client_id: usize, anchor: usize, cursor: usize) {
+        let mut state = lock!(core);
+        let ctx = state.clients.get_mut(&client_id).unwrap();
+        let key = ctx.view.borrow().id().to_owned();
+        let sel = &mut ctx
+            .selections
+            .get_mut(&key)
+            .unwrap()
+            .get_mut("test")
+            .unwrap()[0];
+        sel.anchor = anchor;
+        sel.cursor = cursor;
+    }
+
+    fn text(core: &Core) -> String {
+        lock!(core).buffers["test"].content.text()
+    }
+
+    #[test]
+    fn undo_restores_selections() {
+        let (mut core, _rx) = core_with_clients("hello world\n", &[1]);
+        set_selection(&core, 1, 6, 10);
+        core.insert(1, "big", InsertTarget::Replace);
+        set_selection(&core, 1, 0, 0);
+
+        assert!(core.undo(1));
+        assert_eq!(text(&core), "hello world\n");
+        assert_eq!(
+            (
+                selections(&core, 1)[0].anchor,
+                selections(&core, 1)[0].cursor
+            ),
+            (6, 10)
+        );
+        assert!(core.redo(1));
+        assert_eq!(
+            (
+                selections(&core, 1)[0].anchor,
+                selections(&core, 1)[0].cursor
+            ),
+            (0, 0)
+        );
+
+        let revisions = core.revisions(1);
+        assert_eq!(revisions.len(), 2);
+        assert!(revisions[1].current);
+        assert_eq!(revisions[0].preview, "+1 -1: hello world");
+    }
+
+    #[test]
+    fn insert_shifts_other_clients() {
+        let (mut core, _rx) = core_with_clients("hello world\n", &[1, 2]);
+        set_selection(&core, 1, 0, 0);
+        set_selection(&core, 2, 6, 10);
+
+        core.insert(1, ">> ", InsertTarget::Cursor);
+        assert_eq!(text(&core), ">> hello world\n");
+        assert_eq!(
+            (
+                selections(&core, 1)[0].anchor,
+                selections(&core, 1)[0].cursor
+            ),
+            (3, 3)
+        );
+        assert_eq!(
+            (
+                selections(&core, 2)[0].anchor,
+                selections(&core, 2)[0].cursor
+            ),
+            (9, 13)
+        );
+
+        core.insert(2, "big", InsertTarget::Replace);
+        assert_eq!(text(&core), ">> hello big\n");
+        assert_eq!(
+            (
+                selections(&core, 1)[0].anchor,
+                selections(&core, 1)[0].cursor
+            ),
+            (3, 3)
+        );
+        assert_eq!(
+            (
+                selections(&core, 2)[0].anchor,
+                selections(&core, 2)[0].cursor
+            ),
+            (9, 11)
+        );
+
+        core.delete_before(2);
+        assert_eq!(text(&core), ">> hello bg\n");
+        assert_eq!(
+            (
+                selections(&core, 2)[0].anchor,
+                selections(&core, 2)[0].cursor
+            ),
+            (9, 10)
+        );
+    }
+
+    #[test]
+    fn delete_and_undo_move_other_clients() {
+        let (mut core, _rx) = core_with_clients("hello world\n", &[1, 2]);
+        set_selection(&core, 1, 0, 5);
+        set_selection(&core, 2, 6, 10);
+
+        assert_eq!(
+            core.delete_selection(1, REGISTER_DEFAULT).unwrap(),
+            vec!["hello "]
+        );
+        assert_eq!(core.register(REGISTER_DEFAULT).unwrap(), vec!["hello "]);
+        assert_eq!(text(&core), "world\n");
+        assert_eq!(
+            (
+                selections(&core, 2)[0].anchor,
+                selections(&core, 2)[0].cursor
+            ),
+            (0, 4)
+        );
+
+        assert!(core.undo(1));
+        assert_eq!(text(&core), "hello world\n");
+        assert_eq!(
+            (
+                selections(&core, 2)[0].anchor,
+                selections(&core, 2)[0].cursor
+            ),
+            (6, 10)
+        );
+    }
+
+    #[test]
+    fn range_lenses_follow_edits() {
+        let (mut core, _rx) = core_with_clients("a\nb\nc\nd\n", &[1]);
+        let mut view = View::default();
+        view.add_lens(Lens {
+            buffer: "test".to_owned(),
+            focus: Focus::Range(2..3),
+        });
+        core.add_view(view);
+        set_selection(&core, 1, 0, 0);
+
+        let lens_key = |core: &Core| lock!(core).views["test{2..3}"].borrow().key();
+        core.insert(1, "x\ny\n", InsertTarget::Cursor);
+        assert_eq!(lens_key(&core), "test{4..5}");
+
+        // a range whose lines are removed keeps the line left in their place
+        set_selection(&core, 1, 6, 9);
+        core.delete_selection(1, REGISTER_DEFAULT).unwrap();
+        assert_eq!(text(&core), "x\ny\na\nd\n");
+        assert_eq!(lens_key(&core), "test{3..4}");
+    }
+
+    #[test]
+    fn missing_view_keeps_jumps() {
+        let (mut core, _rx) = core_with_clients("hello\n", &[1]);
+        core.open_scratch("other", "world\n".to_owned());
+        core.view(1, "other").unwrap();
+        assert!(core.jump_back(1));
+        assert!(matches!(
+            core.view(1, "missing"),
+            Err(Error::ViewNotFound { .. })
+        ));
+        // the position gone back over is still there
+        assert!(core.jump_forward(1));
+    }
+
+    #[test]
+    fn reload_changed_files() {
+        let (mut core, _rx) = core_with_clients("", &[1]);
+        let path = std::env::temp_dir().join(format!("ced-test-{}-reload", std::process::id()));
+        let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let change_file = |content: &str| {
+            // written again until the coarse clock of modification times tells the change
+            let before = modified(&path);
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                std::fs::write(&path, content).unwrap();
+                if modified(&path) != before || Instant::now() > deadline {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        };
+        std::fs::write(&path, "hello world\n").unwrap();
+        core.open_file("test", &path).unwrap();
+        let paths = vec![path.clone()];
+        assert_eq!(core.file_paths(), paths);
+        set_selection(&core, 1, 6, 10);
+
+        change_file(">> hello world\n");
+        assert!(core.check_files(&paths).is_empty());
+        assert_eq!(text(&core), ">> hello world\n");
+        assert_eq!(
+            (
+                selections(&core, 1)[0].anchor,
+                selections(&core, 1)[0].cursor
+            ),
+            (9, 13)
+        );
+
+        core.insert(1, "big", InsertTarget::Replace);
+        change_file("hello world\n");
+        assert_eq!(core.check_files(&paths), vec!["test"]);
+        assert!(core.check_files(&paths).is_empty());
+        assert_eq!(text(&core), ">> hello big\n");
+        core.keep_buffer("test").unwrap();
+        assert!(core.conflicts().is_empty());
+        assert!(core.check_files(&paths).is_empty());
+
+        change_file("other\n");
+        assert_eq!(core.check_files(&paths), vec!["test"]);
+        core.reload_buffer("test").unwrap();
+        assert_eq!(text(&core), "other\n");
+        assert!(core.conflicts().is_empty());
+        assert!(!lock!(core).buffers["test"].is_modified());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recover_journal() {
+        let (mut core, _rx) = core_with_clients("", &[1]);
+        let tmp = std::env::temp_dir();
+        let swap_dir = tmp.join(format!("ced-test-{}-swap", std::process::id()));
+        let path = tmp.join(format!("ced-test-{}-recover", std::process::id()));
+        let name = path.to_str().unwrap().to_owned();
+        let leftover = swap_path(&swap_dir, &path, u32::MAX);
+        let swap = swap_path(&swap_dir, &path, std::process::id());
+        core.set_swap_dir(swap_dir.clone());
+        fs::create_dir_all(&swap_dir).unwrap();
+        fs::write(&path, "hello world\n").unwrap();
+        // left by a process that is no more
+        let lost = "hello big world\n";
+        let journal = format!(
+            "ced journal\n{}\n{}\n0 0 {}\n{}",
+            u32::MAX,
+            name,
+            lost.len(),
+            lost
+        );
+        fs::write(&leftover, journal).unwrap();
+
+        core.edit(1, &name, false).unwrap();
+        assert_eq!(core.recoveries()[0].0, name);
+        core.write_journals();
+        assert!(leftover.exists());
+
+        core.recover_buffer(&name).unwrap();
+        assert!(core.recoveries().is_empty());
+        assert_eq!(lock!(core).buffers[&name].content.text(), lost);
+        assert!(!leftover.exists());
+        let recovery = Recovery::read(&swap).unwrap();
+        assert_eq!(recovery.pid, std::process::id());
+        assert_eq!(recovery.text, lost);
+
+        // the unsaved changes outlive the server
+        core.close_journals();
+        assert_eq!(Recovery::read(&swap).unwrap().text, lost);
+        fs::remove_file(&path).unwrap();
+        fs::remove_dir_all(&swap_dir).unwrap();
+    }
+
+    #[test]
+    fn save_and_restore_session() {
+        let (mut core, _rx) = core_with_clients("some notes\n", &[1]);
+        let tmp = std::env::temp_dir();
+        let path = tmp.join(format!("ced-test-{}-session-file", std::process::id()));
+        let saved = tmp.join(format!("ced-test-{}-session.json", std::process::id()));
+        let name = path.to_str().unwrap().to_owned();
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        core.edit(1, &name, false).unwrap();
+        core.modify_view(&View::for_buffer(&name).key(), |view| {
+            view.add_lens(Lens {
+                buffer: "test".to_owned(),
+                focus: Focus::Range(0..1),
+            })
+        });
+        let key = lock!(core).clients[&1].view.borrow().key();
+        set_selection(&core, 1, 2, 5);
+        core.start_recording(1, 'q');
+        core.record_key(1, "x");
+        core.stop_recording(1);
+        core.save_session(1, &saved).unwrap();
+
+        let (mut restored, _rx) = core_with_clients("", &[1]);
+        restored.restore_session(1, &saved).unwrap();
+        assert_eq!(text(&restored), "some notes\n");
+        assert_eq!(
+            lock!(restored).buffers[&name].content.text(),
+            "one\ntwo\nthree\n"
+        );
+        assert_eq!(lock!(restored).clients[&1].view.borrow().key(), key);
+        assert_eq!(
+            lock!(restored).views.latest_value().unwrap().borrow().key(),
+            key
+        );
+        assert_eq!(
+            (
+                selections(&restored, 1)[0].anchor,
+                selections(&restored, 1)[0].cursor
+            ),
+            (2, 5)
+        );
+        assert_eq!(restored.register('q').unwrap(), vec!["x"]);
+
+        // a file gone since is opened again as a new one
+        fs::remove_file(&path).unwrap();
+        let (mut restored, _rx) = core_with_clients("", &[1]);
+        restored.restore_session(1, &saved).unwrap();
+        assert_eq!(
+            lock!(restored).clients[&1].view.borrow().key(),
+            format!("{}{{*}}|test{{0..1}}", name)
+        );
+        fs::remove_file(&saved).unwrap();
+    }
+
+    #[test]
+    fn grep_project() {
+        let (mut core, _rx) = core_with_clients("", &[1]);
+        let dir = std::env::temp_dir().join(format!("ced-test-{}-grep", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(".ignore"), "ignored.txt\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "needle\n").unwrap();
+        fs::write(dir.join("binary"), "needle\0\n").unwrap();
+        fs::write(dir.join("a.txt"), "1\n2\nneedle\nneedle\n5\n6\n7\nneedle\n").unwrap();
+        fs::write(dir.join("src").join("b.txt"), "no\nneedle here\n").unwrap();
+        lock!(core).cwd = dir.clone();
+
+        assert_eq!(core.grep(1, "nothing", CaseMode::Smart).unwrap(), 0);
+        assert_eq!(core.grep(1, "needle", CaseMode::Smart).unwrap(), 4);
+        let key = lock!(core).clients[&1].view.borrow().key();
+        let b = Path::new("src").join("b.txt").display().to_string();
+        assert_eq!(key, format!("a.txt{{1..3,3..5,6..8}}|{}{{0..2}}", b));
+        {
+            let state = lock!(core);
+            let id = state.clients[&1].view.borrow().id().to_owned();
+            let sels = &state.clients[&1].selections[&id];
+            assert_eq!((sels["a.txt"][0].anchor, sels["a.txt"][0].cursor), (4, 9));
+            assert_eq!((sels[&b][0].anchor, sels[&b][0].cursor), (3, 8));
+            assert!(!state.buffers.contains_key("ignored.txt"));
+            assert!(!state.buffers.contains_key("binary"));
+        }
+
+        // the results are the lines of the buffers, the lenses following their edits
+        core.insert(1, "0\n", InsertTarget::Before);
+        assert_eq!(
+            lock!(core).buffers["a.txt"].content.text(),
+            "1\n2\n0\nneedle\nneedle\n5\n6\n7\nneedle\n"
+        );
+        let key = lock!(core).clients[&1].view.borrow().key();
+        assert_eq!(key, format!("a.txt{{1..4,4..6,7..9}}|{}{{0..3}}", b));
+        assert!(core.grep(1, "(", CaseMode::Smart).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
     #[test]
     fn error_message_normalization() {
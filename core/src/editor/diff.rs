@@ -1,36 +1,32 @@
 use std::cmp::max;
 
-#[derive(Debug, PartialEq)]
+// above this many comparisons, the changed part is replaced as a whole instead of computing
+// the longest common subsequence, whose table would grow too large
+const LCS_MAX_CELLS: usize = 1 << 22;
+// unchanged lines shown around the changes of a line diff
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Diff {
     Left(usize),
     Both(usize),
     Right(usize),
 }
 
-fn precompute(b1: &[u8], b2: &[u8]) -> (usize, Option<usize>) {
-    let l1 = b1.len();
-    let l2 = b2.len();
-
-    let mut start = 0;
-    while start < l1 && start < l2 && b1[start] == b2[start] {
-        start += 1;
-    }
-
-    let mut end = 0;
-    while start < l1 - end && start < l2 - end && b1[l1 - 1 - end] == b2[l2 - 1 - end] {
-        end += 1;
-    }
-
-    if start == end || end == 0 {
-        (start, None)
-    } else {
-        (start, Some(end))
+impl Diff {
+    fn len(&self) -> usize {
+        match *self {
+            Diff::Left(len) | Diff::Both(len) | Diff::Right(len) => len,
+        }
     }
 }
 
-fn find_lcs(left: &[u8], right: &[u8]) -> Vec<u8> {
+fn find_lcs<T: Clone + PartialEq>(left: &[T], right: &[T]) -> Vec<T> {
     let el = left.len();
     let er = right.len();
+    if el == 0 || er == 0 {
+        return Vec::new();
+    }
 
     let mut mt = vec![0; el * er];
     for i in 0..el {
@@ -62,91 +58,165 @@ fn find_lcs(left: &[u8], right: &[u8]) -> Vec<u8> {
         let idx = i as usize;
         let jdx = j as usize;
         if left[idx] == right[jdx] {
-            lcs.insert(0, left[idx]);
+            lcs.push(left[idx].clone());
             i -= 1;
             j -= 1;
         } else if j == 0 && i == 0 {
             break;
-        } else if i == 0 || mt[idx * er + jdx - 1] > mt[(idx - 1) * er + jdx] {
+        } else if i == 0 || (j > 0 && mt[idx * er + jdx - 1] > mt[(idx - 1) * er + jdx]) {
             j -= 1;
         } else {
             i -= 1;
         }
     }
 
+    lcs.reverse();
     lcs
 }
 
-#[allow(clippy::many_single_char_names)]
-pub fn diff(left: &str, right: &str) -> Vec<Diff> {
-    let bleft = left.as_bytes();
-    let bright = right.as_bytes();
-    let mut diffs = Vec::new();
+fn push_diff(diffs: &mut Vec<Diff>, diff: Diff) {
+    if diff.len() == 0 {
+        return;
+    }
+    match (diffs.last_mut(), diff) {
+        (Some(Diff::Left(len)), Diff::Left(more))
+        | (Some(Diff::Both(len)), Diff::Both(more))
+        | (Some(Diff::Right(len)), Diff::Right(more)) => *len += more,
+        _ => diffs.push(diff),
+    }
+}
 
-    let (start, maybe_end) = precompute(bleft, bright);
-    if start > 0 {
-        diffs.push(Diff::Both(start));
-    }
-    let end = match maybe_end {
-        Some(len) => len,
-        None if start > 0 => return diffs,
-        None => 0,
-    };
-
-    let l = &bleft[start..bleft.len() - end];
-    let r = &bright[start..bright.len() - end];
-    let lcs = find_lcs(l, r);
-    let mut i = 0;
-    let mut j = 0;
-    let mut len;
-
-    let mut lcs_it = lcs.iter();
-    while let Some(&c) = lcs_it.next() {
-        len = 0;
-        while i < l.len() && l[i] != c {
-            len += 1;
-            i += 1;
-        }
-        if len > 0 {
-            diffs.push(Diff::Left(len));
-        }
+/// Diffs two sequences, the length of each item being given by `size`.
+fn diff_items<T, F>(left: &[T], right: &[T], size: F) -> Vec<Diff>
+where
+    T: Clone + PartialEq,
+    F: Fn(&T) -> usize,
+{
+    let total = |items: &[T]| items.iter().map(&size).sum();
+    let mut diffs = Vec::new();
 
-        len = 0;
-        while j < r.len() && r[j] != c {
-            len += 1;
-            j += 1;
-        }
-        if len > 0 {
-            diffs.push(Diff::Right(len));
-        }
+    let start = left
+        .iter()
+        .zip(right.iter())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let end = left[start..]
+        .iter()
+        .rev()
+        .zip(right[start..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    push_diff(&mut diffs, Diff::Both(total(&left[..start])));
 
-        len = 0;
-        let mut sub_c = c;
-        while l[i] == sub_c && r[j] == sub_c {
-            len += 1;
+    let l = &left[start..left.len() - end];
+    let r = &right[start..right.len() - end];
+    let (mut i, mut j) = (0, 0);
+    if l.len().saturating_mul(r.len()) <= LCS_MAX_CELLS {
+        for item in find_lcs(l, r) {
+            while l[i] != item {
+                push_diff(&mut diffs, Diff::Left(size(&l[i])));
+                i += 1;
+            }
+            while r[j] != item {
+                push_diff(&mut diffs, Diff::Right(size(&r[j])));
+                j += 1;
+            }
+            push_diff(&mut diffs, Diff::Both(size(&l[i])));
             i += 1;
             j += 1;
-            match lcs_it.next() {
-                Some(&v) => sub_c = v,
-                None => break,
-            }
-        }
-        if len > 0 {
-            diffs.push(Diff::Both(len));
         }
     }
+    push_diff(&mut diffs, Diff::Left(total(&l[i..])));
+    push_diff(&mut diffs, Diff::Right(total(&r[j..])));
+
+    push_diff(&mut diffs, Diff::Both(total(&left[left.len() - end..])));
+    diffs
+}
+
+/// Diffs two texts character by character, the lengths being in bytes.
+pub fn diff(left: &str, right: &str) -> Vec<Diff> {
+    let lchars = left.chars().collect::<Vec<_>>();
+    let rchars = right.chars().collect::<Vec<_>>();
+    diff_items(&lchars, &rchars, |c| c.len_utf8())
+}
+
+/// Diffs two sequences of lines, the lengths being in lines.
+pub fn diff_lines<T: Clone + PartialEq>(left: &[T], right: &[T]) -> Vec<Diff> {
+    diff_items(left, right, |_| 1)
+}
 
-    if i < l.len() - 1 {
-        diffs.push(Diff::Left(l.len() - i));
+fn push_lines(out: &mut String, prefix: char, lines: &[&str]) {
+    for line in lines {
+        out.push(prefix);
+        out.push_str(line);
+        out.push('\n');
     }
-    if j < r.len() - 1 {
-        diffs.push(Diff::Right(r.len() - j));
+}
+
+/// Lists the changed lines from `left` to `right` prefixed with `-` and `+`, surrounded by a few
+/// unchanged lines. Skipped unchanged lines are replaced with the line numbers of what follows,
+/// e.g. `@@ -12 +14 @@`.
+pub fn line_diff(left: &str, right: &str) -> String {
+    let llines = left.lines().collect::<Vec<_>>();
+    let rlines = right.lines().collect::<Vec<_>>();
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    let diffs = diff_items(&llines, &rlines, |_| 1);
+    for (n, diff) in diffs.iter().enumerate() {
+        match *diff {
+            Diff::Both(len) => {
+                let head = if n == 0 { 0 } else { len.min(DIFF_CONTEXT) };
+                let tail = if n + 1 == diffs.len() {
+                    0
+                } else {
+                    (len - head).min(DIFF_CONTEXT)
+                };
+                push_lines(&mut out, ' ', &llines[i..i + head]);
+                if head + tail < len && tail > 0 {
+                    out += &format!("@@ -{} +{} @@\n", i + len - tail + 1, j + len - tail + 1);
+                }
+                push_lines(&mut out, ' ', &llines[i + len - tail..i + len]);
+                i += len;
+                j += len;
+            }
+            Diff::Left(len) => {
+                push_lines(&mut out, '-', &llines[i..i + len]);
+                i += len;
+            }
+            Diff::Right(len) => {
+                push_lines(&mut out, '+', &rlines[j..j + len]);
+                j += len;
+            }
+        }
     }
+    out
+}
 
-    if end > 0 {
-        diffs.push(Diff::Both(end));
+/// Summarizes the lines to change to go from `left` to `right`, e.g. `+2 -1: first changed line`.
+pub fn line_summary(left: &str, right: &str) -> String {
+    if left == right {
+        return "no change".to_owned();
+    }
+    let llines = left.lines().collect::<Vec<_>>();
+    let rlines = right.lines().collect::<Vec<_>>();
+    let prefix = llines
+        .iter()
+        .zip(rlines.iter())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let suffix = llines[prefix..]
+        .iter()
+        .rev()
+        .zip(rlines[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let removed = &llines[prefix..llines.len() - suffix];
+    let added = &rlines[prefix..rlines.len() - suffix];
+    let summary = format!("+{} -{}", added.len(), removed.len());
+    match added.first().or_else(|| removed.first()) {
+        Some(line) => format!("{}: {}", summary, line.trim()),
+        None => summary,
     }
-    diffs
 }
 
 #[cfg(test)]
@@ -220,4 +290,38 @@ mod tests {
         let diffs = diff(left, right);
         assert_diff(left, right, diffs);
     }
+
+    #[test]
+    fn summary() {
+        assert_eq!(line_summary("a\nb\n", "a\nb\n"), "no change");
+        assert_eq!(line_summary("a\nb\nc\n", "a\nx\ny\nc\n"), "+2 -1: x");
+        assert_eq!(line_summary("a\nb\nc\n", "a\nc\n"), "+0 -1: b");
+        assert_eq!(line_summary("a\n", "a"), "+0 -0");
+    }
+
+    #[test]
+    fn edge_diffs() {
+        for (left, right) in &[
+            ("ab", "aXb"),
+            ("abcX", "abcY"),
+            ("a\n", "a\nb\n"),
+            ("", "new"),
+            ("old", ""),
+            ("caf\u{e9}", "caf\u{a9}"),
+        ] {
+            assert_diff(left, right, diff(left, right));
+        }
+        assert_eq!(diff("same", "same"), vec![Diff::Both(4)]);
+    }
+
+    #[test]
+    fn lines() {
+        let left = (1..=20).map(|n| format!("{}\n", n)).collect::<String>();
+        let right = left.replace("10\n", "ten\n");
+        assert_eq!(
+            line_diff(&left, &right),
+            "@@ -7 +7 @@\n 7\n 8\n 9\n-10\n+ten\n 11\n 12\n 13\n"
+        );
+        assert_eq!(line_diff("a\n", "a\nb\n"), " a\n+b\n");
+    }
 }
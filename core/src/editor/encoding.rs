@@ -0,0 +1,403 @@
+use std::fmt;
+use std::str::FromStr;
+
+const BOM_UTF8: &[u8] = &[0xef, 0xbb, 0xbf];
+const BOM_UTF16LE: &[u8] = &[0xff, 0xfe];
+const BOM_UTF16BE: &[u8] = &[0xfe, 0xff];
+// how far to look for NUL bytes when sniffing binary content
+const BINARY_SNIFF_LEN: usize = 8000;
+// invalid UTF-8 bytes are kept as the last code points of the plane 16 private use area,
+// content already using them not being read as lossless UTF-8
+const ESCAPE_BASE: u32 = 0x10ff00;
+const HEX_LINE_WIDTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    /// UTF-8 with a few invalid sequences, kept as is when saving.
+    Utf8Lossless,
+    Utf16Le,
+    Utf16LeBom,
+    Utf16Be,
+    Utf16BeBom,
+    Latin1,
+    /// Content shown as a read-only hex dump.
+    Binary,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Encoding::*;
+        let name = match self {
+            Utf8 => "utf-8",
+            Utf8Bom => "utf-8-bom",
+            Utf8Lossless => "utf-8 (lossless)",
+            Utf16Le => "utf-16le",
+            Utf16LeBom => "utf-16le-bom",
+            Utf16Be => "utf-16be",
+            Utf16BeBom => "utf-16be-bom",
+            Latin1 => "latin-1",
+            Binary => "binary",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Encoding {
+    /// Guesses the encoding of raw content: byte order marks first, then NUL bytes for UTF-16
+    /// or binary content, then UTF-8 validation. Invalid UTF-8 falls back to Latin-1, unless
+    /// the content also has valid multibyte sequences, in which case the invalid ones are stray
+    /// bytes.
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(BOM_UTF8) {
+            return Encoding::Utf8Bom;
+        }
+        if bytes.starts_with(BOM_UTF16LE) {
+            return Encoding::Utf16LeBom;
+        }
+        if bytes.starts_with(BOM_UTF16BE) {
+            return Encoding::Utf16BeBom;
+        }
+        let sniffed = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+        if sniffed.contains(&0) {
+            return detect_utf16(bytes, sniffed).unwrap_or(Encoding::Binary);
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            return Encoding::Utf8;
+        }
+        let has_multibyte = utf8_chunks(bytes).any(|(valid, _)| !valid.is_ascii());
+        let has_escapes = utf8_chunks(bytes).any(|(valid, _)| valid.chars().any(is_escape));
+        if has_multibyte && !has_escapes {
+            Encoding::Utf8Lossless
+        } else {
+            Encoding::Latin1
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        *self == Encoding::Binary
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        use Encoding::*;
+        match self {
+            Utf8 => String::from_utf8_lossy(bytes).into(),
+            Utf8Bom => {
+                String::from_utf8_lossy(bytes.strip_prefix(BOM_UTF8).unwrap_or(bytes)).into()
+            }
+            Utf8Lossless => utf8_chunks(bytes).fold(String::new(), |mut acc, (valid, invalid)| {
+                acc.push_str(valid);
+                acc.extend(
+                    invalid
+                        .iter()
+                        .filter_map(|&b| char::from_u32(ESCAPE_BASE + b as u32)),
+                );
+                acc
+            }),
+            Utf16Le | Utf16Be => String::from_utf16_lossy(&utf16_units(bytes, *self == Utf16Le)),
+            Utf16LeBom | Utf16BeBom => {
+                let body = bytes.get(2..).unwrap_or_default();
+                String::from_utf16_lossy(&utf16_units(body, *self == Utf16LeBom))
+            }
+            Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            Binary => hex_dump(bytes),
+        }
+    }
+
+    /// Converts text back to raw content. Returns `None` for characters the encoding can't
+    /// represent and for binary content, that can't be edited.
+    pub fn encode(&self, text: &str) -> Option<Vec<u8>> {
+        use Encoding::*;
+        match self {
+            Utf8 => Some(text.as_bytes().to_vec()),
+            Utf8Bom => Some([BOM_UTF8, text.as_bytes()].concat()),
+            Utf8Lossless => {
+                let mut bytes = Vec::with_capacity(text.len());
+                let mut buf = [0; 4];
+                for c in text.chars() {
+                    if is_escape(c) {
+                        bytes.push((c as u32 - ESCAPE_BASE) as u8);
+                    } else {
+                        bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+                    }
+                }
+                Some(bytes)
+            }
+            Utf16Le => Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Utf16LeBom => Some(
+                BOM_UTF16LE
+                    .iter()
+                    .copied()
+                    .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                    .collect(),
+            ),
+            Utf16Be => Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Utf16BeBom => Some(
+                BOM_UTF16BE
+                    .iter()
+                    .copied()
+                    .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                    .collect(),
+            ),
+            Latin1 => text
+                .chars()
+                .map(|c| {
+                    if (c as u32) < 0x100 {
+                        Some(c as u8)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            Binary => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LineEnding::*;
+        let name = match self {
+            Lf => "lf",
+            CrLf => "crlf",
+            Cr => "cr",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::CrLf),
+            "cr" => Ok(LineEnding::Cr),
+            _ => Err(format!("unknown line ending: {}", s)),
+        }
+    }
+}
+
+impl LineEnding {
+    /// Finds the line ending used the most in `text`, LF if it has no line break or on a tie.
+    pub fn detect(text: &str) -> LineEnding {
+        let crlf = text.matches("\r\n").count();
+        let cr = text.matches('\r').count() - crlf;
+        let lf = text.matches('\n').count() - crlf;
+        if lf >= crlf && lf >= cr {
+            LineEnding::Lf
+        } else if crlf >= cr {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Cr
+        }
+    }
+
+    /// Turns every line break, whatever its kind, into `\n`, the only one used in buffers.
+    pub fn normalize(text: &str) -> String {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    }
+
+    pub fn denormalize(&self, text: &str) -> String {
+        match self {
+            LineEnding::Lf => text.to_owned(),
+            LineEnding::CrLf => text.replace('\n', "\r\n"),
+            LineEnding::Cr => text.replace('\n', "\r"),
+        }
+    }
+}
+
+/// Tells UTF-16 without a byte order mark from binary content: in mostly ASCII text, one byte
+/// of every code unit is NUL, always the same one, and the whole content must decode.
+fn detect_utf16(bytes: &[u8], sniffed: &[u8]) -> Option<Encoding> {
+    if !bytes.chunks_exact(2).remainder().is_empty() {
+        return None;
+    }
+    let units = sniffed.len() / 2;
+    let nul_at = |i| sniffed.chunks_exact(2).filter(|pair| pair[i] == 0).count();
+    let (low_nul, high_nul) = (nul_at(0), nul_at(1));
+    let little_endian = if low_nul == 0 && high_nul * 2 >= units {
+        true
+    } else if high_nul == 0 && low_nul * 2 >= units {
+        false
+    } else {
+        return None;
+    };
+    let units = utf16_units(bytes, little_endian);
+    if char::decode_utf16(units).all(|c| c.is_ok()) {
+        Some(if little_endian {
+            Encoding::Utf16Le
+        } else {
+            Encoding::Utf16Be
+        })
+    } else {
+        None
+    }
+}
+
+fn utf16_units(bytes: &[u8], little_endian: bool) -> Vec<u16> {
+    bytes
+        .chunks(2)
+        .map(|pair| {
+            let pair = [pair[0], *pair.get(1).unwrap_or(&0)];
+            if little_endian {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            }
+        })
+        .collect()
+}
+
+/// Whether `c` stands for an invalid byte in lossless UTF-8.
+fn is_escape(c: char) -> bool {
+    c as u32 >= ESCAPE_BASE + 0x80
+}
+
+/// Splits bytes in successive valid UTF-8 parts, each followed by the invalid bytes after it.
+fn utf8_chunks(bytes: &[u8]) -> impl Iterator<Item = (&str, &[u8])> {
+    let mut rest = bytes;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let (valid, invalid_len) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(e) => {
+                let valid_len = e.valid_up_to();
+                let invalid_len = e.error_len().unwrap_or(rest.len() - valid_len);
+                let valid = std::str::from_utf8(&rest[..valid_len]).unwrap();
+                (valid, invalid_len)
+            }
+        };
+        let (invalid, next) = rest[valid.len()..].split_at(invalid_len);
+        rest = next;
+        Some((valid, invalid))
+    })
+}
+
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, line) in bytes.chunks(HEX_LINE_WIDTH).enumerate() {
+        let hex = line
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        dump += &format!(
+            "{:08x}  {:width$}  |{}|\n",
+            i * HEX_LINE_WIDTH,
+            hex,
+            ascii,
+            width = HEX_LINE_WIDTH * 3 - 1
+        );
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) -> (Encoding, String) {
+        let encoding = Encoding::detect(bytes);
+        let text = encoding.decode(bytes);
+        assert_eq!(encoding.encode(&text).unwrap(), bytes);
+        (encoding, text)
+    }
+
+    #[test]
+    fn detect_and_round_trip() {
+        assert_eq!(
+            round_trip("héllo\n".as_bytes()),
+            (Encoding::Utf8, "héllo\n".to_owned())
+        );
+        assert_eq!(
+            round_trip(b"\xef\xbb\xbfbom\n"),
+            (Encoding::Utf8Bom, "bom\n".to_owned())
+        );
+        assert_eq!(
+            round_trip(b"\xff\xfeh\x00\xe9\x00\n\x00"),
+            (Encoding::Utf16LeBom, "hé\n".to_owned())
+        );
+        assert_eq!(
+            round_trip(b"\xfe\xff\x00h\x00\xe9\x00\n"),
+            (Encoding::Utf16BeBom, "hé\n".to_owned())
+        );
+        assert_eq!(
+            round_trip(b"h\x00\xe9\x00\n\x00"),
+            (Encoding::Utf16Le, "hé\n".to_owned())
+        );
+        assert_eq!(
+            round_trip(b"\x00h\x00\xe9\x00\n"),
+            (Encoding::Utf16Be, "hé\n".to_owned())
+        );
+        assert_eq!(
+            round_trip(b"caf\xe9\n"),
+            (Encoding::Latin1, "café\n".to_owned())
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let bytes = b"caf\xc3\xa9 \xff\xfe end\n";
+        let (encoding, text) = round_trip(bytes);
+        assert_eq!(encoding, Encoding::Utf8Lossless);
+        assert!(text.starts_with("café "));
+        assert!(text.ends_with(" end\n"));
+
+        // a character standing for an invalid byte is already in the content
+        let bytes = ["\u{10ffff} café ".as_bytes(), b"\xff\n"].concat();
+        assert_eq!(round_trip(&bytes).0, Encoding::Latin1);
+    }
+
+    #[test]
+    fn binary() {
+        let bytes = b"\x7fELF\x00\x01";
+        assert_eq!(Encoding::detect(bytes), Encoding::Binary);
+        assert_eq!(
+            Encoding::Binary.decode(bytes),
+            format!("00000000  7f 45 4c 46 00 01{}  |.ELF..|\n", " ".repeat(30))
+        );
+        assert_eq!(Encoding::Binary.encode("anything"), None);
+    }
+
+    #[test]
+    fn line_endings() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\rb\rc"), LineEnding::Cr);
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("none"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\rb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\rb\rc\r\n"), LineEnding::Cr);
+
+        let text = LineEnding::normalize("a\r\nb\r\n");
+        assert_eq!(text, "a\nb\n");
+        assert_eq!(LineEnding::CrLf.denormalize(&text), "a\r\nb\r\n");
+        assert_eq!(LineEnding::normalize("a\rb\r"), "a\nb\n");
+        assert_eq!(LineEnding::normalize("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!("CRLF".parse(), Ok(LineEnding::CrLf));
+    }
+}
@@ -0,0 +1,278 @@
+use std::cell::RefCell;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+use regex::Regex;
+use remote::protocol::Face;
+use serde::Deserialize;
+
+use crate::editor::core::Error;
+use crate::editor::piece_table::Change;
+
+/// Bytes of a line shown with a face.
+pub type Span = (Range<usize>, Face);
+
+/// Grammars shipped with the editor, by file name.
+pub const GRAMMARS: &[(&str, &str)] = &[
+    ("lua.json", include_str!("../../grammars/lua.json")),
+    ("rust.json", include_str!("../../grammars/rust.json")),
+];
+
+#[derive(Deserialize)]
+struct GrammarFile {
+    name: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    rules: Vec<RuleFile>,
+}
+
+#[derive(Deserialize)]
+struct RuleFile {
+    face: Face,
+    pattern: String,
+}
+
+/// Rules giving a face to what their pattern matches in a line.
+#[derive(Debug)]
+pub struct Grammar {
+    pub name: String,
+    /// extensions of the files it highlights
+    pub extensions: Vec<String>,
+    rules: Vec<(Regex, Face)>,
+}
+
+impl Grammar {
+    /// Reads a grammar from a JSON file holding its name, extensions and rules.
+    pub fn load(path: &Path) -> Result<Grammar, Error> {
+        let json = fs::read_to_string(path).map_err(|e| Error::from_io(path, e))?;
+        Grammar::parse(path, &json)
+    }
+
+    /// Reads a grammar from `json`, the content of the file at `path`.
+    pub fn parse(path: &Path, json: &str) -> Result<Grammar, Error> {
+        let file: GrammarFile = serde_json::from_str(json).map_err(|e| Error::InvalidGrammar {
+            path: path.to_owned(),
+            error: e.to_string(),
+        })?;
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Ok((regex, rule.face)),
+                Err(error) => Err(Error::InvalidPattern {
+                    pattern: rule.pattern,
+                    error,
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Grammar {
+            name: file.name,
+            extensions: file.extensions,
+            rules,
+        })
+    }
+
+    /// Whether the file at `path` is highlighted with it.
+    pub fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e == ext))
+    }
+
+    /// Spans of `line`, in order. The leftmost match is kept, the first rule matching there
+    /// winning, and the rules go on after it. Only the part of a match captured by a group
+    /// named `face` is highlighted if the pattern has one.
+    pub fn highlight(&self, line: &str) -> Vec<Span> {
+        let mut spans = Vec::new();
+        // next match of each rule, searched again once the spans go past its start
+        let mut next: Vec<_> = self
+            .rules
+            .iter()
+            .map(|(regex, _)| find_from(regex, line, 0))
+            .collect();
+        loop {
+            let first = next
+                .iter()
+                .enumerate()
+                .filter_map(|(i, m)| m.as_ref().map(|range| (range.start, i)))
+                .min();
+            let (_, i) = match first {
+                Some(first) => first,
+                None => return spans,
+            };
+            let range = next[i].clone().unwrap();
+            let offset = range.end;
+            let (regex, face) = &self.rules[i];
+            if let Some(span) = face_part(regex, line, range) {
+                spans.push((span, face.clone()));
+            }
+            for (m, (regex, _)) in next.iter_mut().zip(&self.rules) {
+                if matches!(m, Some(range) if range.start < offset) {
+                    *m = find_from(regex, line, offset);
+                }
+            }
+        }
+    }
+}
+
+/// First match of `regex` in `line` that is not empty and starts at `offset` or after it.
+fn find_from(regex: &Regex, line: &str, mut offset: usize) -> Option<Range<usize>> {
+    while offset <= line.len() {
+        let m = regex.find_at(line, offset)?;
+        if !m.range().is_empty() {
+            return Some(m.range());
+        }
+        offset = m.end() + line[m.end()..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// Part of the match of `regex` at `range` to highlight.
+fn face_part(regex: &Regex, line: &str, range: Range<usize>) -> Option<Range<usize>> {
+    match regex.capture_names().position(|name| name == Some("face")) {
+        Some(group) => {
+            let mut locations = regex.capture_locations();
+            regex.captures_read_at(&mut locations, line, range.start)?;
+            locations
+                .get(group)
+                .map(|(start, end)| start..end)
+                .filter(|part| !part.is_empty())
+        }
+        None => Some(range),
+    }
+}
+
+type HighlightedLine = (String, Vec<Span>);
+
+/// Spans of the lines of a buffer, a line being highlighted again only once it changed.
+pub struct Highlighter {
+    grammar: Rc<Grammar>,
+    // text and spans of the lines highlighted so far
+    lines: RefCell<Vec<Option<HighlightedLine>>>,
+}
+
+impl Highlighter {
+    pub fn new(grammar: Rc<Grammar>) -> Highlighter {
+        Highlighter {
+            grammar,
+            lines: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn grammar(&self) -> &Rc<Grammar> {
+        &self.grammar
+    }
+
+    /// Spans of the line `index` of the buffer, counted from 0, whose text is `text`.
+    pub fn line(&self, index: usize, text: &str) -> Vec<Span> {
+        let mut lines = self.lines.borrow_mut();
+        if lines.len() <= index {
+            lines.resize(index + 1, None);
+        }
+        match &lines[index] {
+            Some((highlighted, spans)) if highlighted == text => spans.clone(),
+            _ => {
+                let spans = self.grammar.highlight(text);
+                lines[index] = Some((text.to_owned(), spans.clone()));
+                spans
+            }
+        }
+    }
+
+    /// Moves the highlighted lines along with `changes`, the lines they touch being forgotten.
+    pub fn follow_changes(&mut self, changes: &[Change]) {
+        let lines = self.lines.get_mut();
+        for change in changes {
+            if change.line >= lines.len() {
+                continue;
+            }
+            let end = lines.len().min(change.line + change.removed_lines + 1);
+            lines.splice(change.line..end, (0..=change.inserted_lines).map(|_| None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::piece_table::Edit;
+
+    fn grammar() -> Grammar {
+        let rules = [
+            (r"//.*", Face::Comment),
+            (r#""[^"]*""#, Face::String),
+            (r"\b(fn|let)\b", Face::Keyword),
+            (r"\b[0-9]+\b", Face::Number),
+            (r"(?P<face>\w+)\(", Face::Function),
+        ];
+        Grammar {
+            name: "test".to_owned(),
+            extensions: vec!["t".to_owned()],
+            rules: rules
+                .iter()
+                .map(|(pattern, face)| (Regex::new(pattern).unwrap(), face.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn highlight() {
+        let grammar = grammar();
+        assert_eq!(
+            grammar.highlight(r#"let s = "fn // 1"; // let 2"#),
+            vec![
+                (0..3, Face::Keyword),
+                (8..17, Face::String),
+                (19..27, Face::Comment),
+            ]
+        );
+        assert_eq!(
+            grammar.highlight("fn f() { g(12) }"),
+            vec![
+                (0..2, Face::Keyword),
+                (3..4, Face::Function),
+                (9..10, Face::Function),
+                (11..13, Face::Number),
+            ]
+        );
+        assert!(grammar.matches(Path::new("dir/file.t")));
+        assert!(!grammar.matches(Path::new("file.rs")));
+    }
+
+    #[test]
+    fn follow_changes() {
+        let mut highlighter = Highlighter::new(Rc::new(grammar()));
+        for (i, text) in ["fn", "let", "1", "2"].iter().enumerate() {
+            highlighter.line(i, text);
+        }
+        // a line broken in two
+        highlighter.follow_changes(&[Change {
+            edit: Edit {
+                offset: 4,
+                removed: 0,
+                inserted: 1,
+            },
+            line: 1,
+            removed_lines: 0,
+            inserted_lines: 1,
+        }]);
+        let highlighted = highlighter
+            .lines
+            .borrow()
+            .iter()
+            .map(|line| line.as_ref().map(|(text, _)| text.to_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            highlighted,
+            vec![
+                Some("fn".to_owned()),
+                None,
+                None,
+                Some("1".to_owned()),
+                Some("2".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::editor::diff::{diff, Diff};
+
+const HEADER: &str = "ced journal";
+// the journal is written again from scratch past this many times the size of the text
+const COMPACTION_RATIO: usize = 2;
+
+fn swap_name(file: &Path) -> String {
+    file.to_string_lossy().replace(['/', '\\', ':'], "%")
+}
+
+/// Where the journal of the unsaved changes made to `file` by the process `pid` goes, each
+/// process editing the file having its own.
+pub fn swap_path(dir: &Path, file: &Path, pid: u32) -> PathBuf {
+    dir.join(format!("{}.{}.swap", swap_name(file), pid))
+}
+
+/// Journals of `file` found in `dir`, whatever the process that wrote them.
+pub fn swap_paths(dir: &Path, file: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}.", swap_name(file));
+    let is_journal = |name: &str| {
+        name.strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".swap"))
+            .is_some_and(|pid| pid.parse::<u32>().is_ok())
+    };
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_str().is_some_and(is_journal))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Journal of the changes made to a buffer: a snapshot of its text followed by the edits made
+/// since, appended as `offset removed length\n` and the inserted bytes. The file stays locked
+/// while the journal is kept, telling the journals of running processes from leftovers.
+pub struct Journal {
+    path: PathBuf,
+    source: PathBuf,
+    file: File,
+    text: String,
+    written: usize,
+}
+
+impl Journal {
+    pub fn create(path: PathBuf, source: &Path, text: &str) -> io::Result<Journal> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::create(&path)?;
+        lock(&file)?;
+        let mut journal = Journal {
+            path,
+            source: source.to_owned(),
+            file,
+            text: String::new(),
+            written: 0,
+        };
+        journal.reset(text)?;
+        Ok(journal)
+    }
+
+    /// Writes the journal again from scratch, starting from `text`.
+    fn reset(&mut self, text: &str) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        let header = format!(
+            "{}\n{}\n{}\n",
+            HEADER,
+            std::process::id(),
+            self.source.display()
+        );
+        self.file.write_all(header.as_bytes())?;
+        self.written = 0;
+        self.write_edit(0, 0, text)?;
+        self.file.flush()?;
+        self.text = text.to_owned();
+        Ok(())
+    }
+
+    /// Appends the edits turning the last recorded text into `text`.
+    pub fn record(&mut self, text: &str) -> io::Result<()> {
+        if text == self.text {
+            return Ok(());
+        }
+        if self.written > COMPACTION_RATIO * text.len().max(self.text.len()) {
+            return self.reset(text);
+        }
+        let mut loffset = 0;
+        let mut roffset = 0;
+        for d in diff(&self.text, text) {
+            match d {
+                Diff::Left(len) => self.write_edit(loffset, len, "")?,
+                Diff::Right(len) => {
+                    self.write_edit(loffset, 0, &text[roffset..roffset + len])?;
+                    loffset += len;
+                    roffset += len;
+                }
+                Diff::Both(len) => {
+                    loffset += len;
+                    roffset += len;
+                }
+            }
+        }
+        self.file.flush()?;
+        self.text = text.to_owned();
+        Ok(())
+    }
+
+    fn write_edit(&mut self, offset: usize, removed: usize, inserted: &str) -> io::Result<()> {
+        let record = format!("{} {} {}\n", offset, removed, inserted.len());
+        self.file.write_all(record.as_bytes())?;
+        self.file.write_all(inserted.as_bytes())?;
+        self.written += record.len() + inserted.len();
+        Ok(())
+    }
+
+    pub fn remove(self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+}
+
+/// What is left of a journal.
+pub struct Recovery {
+    pub pid: u32,
+    // whether the journal is locked by the process writing it
+    held: bool,
+    pub source: PathBuf,
+    pub text: String,
+    pub time: SystemTime,
+}
+
+impl Recovery {
+    /// Replays a journal, ignoring a last edit cut short.
+    pub fn read(path: &Path) -> io::Result<Recovery> {
+        let held = is_locked(&File::open(path)?);
+        let bytes = fs::read(path)?;
+        let time = fs::metadata(path)?.modified()?;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a journal");
+        let (pid, source, mut rest) = read_header(&bytes).ok_or_else(invalid)?;
+
+        let mut text = Vec::new();
+        while let Some(eol) = rest.iter().position(|&b| b == b'\n') {
+            let numbers = std::str::from_utf8(&rest[..eol])
+                .ok()
+                .map(|line| {
+                    line.split(' ')
+                        .filter_map(|n| n.parse::<usize>().ok())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let (offset, removed, len) = match numbers[..] {
+                [offset, removed, len] => (offset, removed, len),
+                _ => break,
+            };
+            let body = &rest[eol + 1..];
+            if len > body.len() || offset + removed > text.len() {
+                break;
+            }
+            text.splice(offset..offset + removed, body[..len].iter().copied());
+            rest = &body[len..];
+        }
+
+        Ok(Recovery {
+            pid,
+            held,
+            source,
+            text: String::from_utf8_lossy(&text).into(),
+            time,
+        })
+    }
+
+    /// Whether the process that wrote the journal is gone, leaving the journal behind.
+    pub fn is_leftover(&self) -> bool {
+        self.pid != std::process::id() && !self.held
+    }
+}
+
+fn read_header(bytes: &[u8]) -> Option<(u32, PathBuf, &[u8])> {
+    let mut lines = bytes.splitn(4, |&b| b == b'\n');
+    if lines.next()? != HEADER.as_bytes() {
+        return None;
+    }
+    let pid = std::str::from_utf8(lines.next()?).ok()?.parse().ok()?;
+    let source = PathBuf::from(String::from_utf8_lossy(lines.next()?).as_ref());
+    Some((pid, source, lines.next()?))
+}
+
+/// Takes the lock of a journal, released when its file is closed, the process included.
+#[cfg(unix)]
+fn lock(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(unix)]
+fn is_locked(file: &File) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    // the shared lock goes away with the file
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) != 0 }
+}
+
+// without file locks, the journals of other processes are all taken for leftovers
+#[cfg(not(unix))]
+fn lock(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn is_locked(_file: &File) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let dir = std::env::temp_dir().join(format!("ced-test-{}-journal", std::process::id()));
+        let source = Path::new("/some/dir/file.txt");
+        let path = swap_path(&dir, source, std::process::id());
+        assert!(path.ends_with(format!("%some%dir%file.txt.{}.swap", std::process::id())));
+
+        let mut journal = Journal::create(path.clone(), source, "hello world\n").unwrap();
+        journal.record("hello big world\n").unwrap();
+        journal.record("héllo big\n").unwrap();
+
+        let recovery = Recovery::read(&path).unwrap();
+        assert_eq!(recovery.text, "héllo big\n");
+        assert_eq!(recovery.source, source);
+        assert!(!recovery.is_leftover());
+        assert_eq!(swap_paths(&dir, source), vec![path.clone()]);
+        assert!(swap_paths(&dir, Path::new("/some/dir/file")).is_empty());
+
+        // an edit cut short by a crash is dropped
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"0 0 10\nshort").unwrap();
+        assert_eq!(Recovery::read(&path).unwrap().text, "héllo big\n");
+
+        journal.remove().unwrap();
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction() {
+        let dir = std::env::temp_dir().join(format!("ced-test-{}-compact", std::process::id()));
+        let source = Path::new("/file");
+        let path = swap_path(&dir, source, std::process::id());
+        let mut journal = Journal::create(path.clone(), source, "a\n").unwrap();
+        for i in 1..50 {
+            journal.record(&format!("{}\n", i)).unwrap();
+        }
+        assert!(fs::metadata(&path).unwrap().len() < 64);
+        assert_eq!(Recovery::read(&path).unwrap().text, "49\n");
+        assert_eq!(Recovery::read(&path).unwrap().source, source);
+        assert!(Recovery::read(&path).unwrap().held);
+        journal.remove().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::str::FromStr;
+
+use remote::protocol::{Face, Text, TextFragment};
+use unicode_width::UnicodeWidthChar;
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// How lines wider than a client are shown.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Wrap {
+    /// Cut at the width, the client scrolling horizontally.
+    #[default]
+    None,
+    /// Wrapped at the width.
+    Char,
+    /// Wrapped after the last blank fitting in the width, at the width if there is none.
+    Word,
+}
+
+impl fmt::Display for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Wrap::*;
+        match self {
+            None => write!(f, "none"),
+            Char => write!(f, "char"),
+            Word => write!(f, "word"),
+        }
+    }
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Wrap::None),
+            "char" => Ok(Wrap::Char),
+            "word" => Ok(Wrap::Word),
+            _ => Err(format!("unknown wrap mode: {}", s)),
+        }
+    }
+}
+
+/// Columns taken by `c` when it starts at column `col`, tabs reaching the next multiple of
+/// `tab_width`.
+pub fn char_width(c: char, col: usize, tab_width: usize) -> usize {
+    if c == '\t' {
+        let tab_width = tab_width.max(1);
+        tab_width - col % tab_width
+    } else {
+        c.width().unwrap_or(0)
+    }
+}
+
+/// Columns taken by `text`.
+pub fn display_width(text: &str, tab_width: usize) -> usize {
+    text.chars()
+        .fold(0, |col, c| col + char_width(c, col, tab_width))
+}
+
+/// How the lines of a view are laid out for a client.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub wrap: Wrap,
+    /// columns of the client, lines being neither wrapped nor cut until it is known
+    pub width: Option<usize>,
+    /// first column shown when lines are not wrapped
+    pub column: usize,
+}
+
+impl Layout {
+    /// Scrolls horizontally just enough for the column `col` to be shown.
+    pub fn reveal(&mut self, col: usize) {
+        let width = match (self.wrap, self.width) {
+            (Wrap::None, Some(width)) => width.max(1),
+            _ => return,
+        };
+        if col < self.column {
+            self.column = col;
+        } else if col - self.column >= width {
+            self.column = col + 1 - width;
+        }
+    }
+
+    /// Splits `line` into the rows showing it, tabs being expanded to spaces.
+    pub fn rows(&self, line: &Text, tab_width: usize) -> Vec<Text> {
+        // cells of the line, a tab giving as many spaces as the columns it takes
+        let mut cells = Vec::new();
+        let mut col = 0;
+        for tf in line {
+            for c in tf.text.chars() {
+                let width = char_width(c, col, tab_width);
+                if c == '\t' {
+                    cells.extend((0..width).map(|_| (' ', 1, &tf.face)));
+                } else {
+                    cells.push((c, width, &tf.face));
+                }
+                col += width;
+            }
+        }
+
+        let width = self.width.unwrap_or(usize::MAX).max(1);
+        let mut rows = Vec::new();
+        match self.wrap {
+            Wrap::None => {
+                let mut col = 0;
+                let mut row = Vec::new();
+                for cell in cells {
+                    if col >= self.column && col + cell.1 <= self.column.saturating_add(width) {
+                        row.push(cell);
+                    }
+                    col += cell.1;
+                }
+                rows.push(row);
+            }
+            Wrap::Char | Wrap::Word => {
+                let mut row = Vec::new();
+                let mut row_width = 0;
+                for cell in cells {
+                    if row_width + cell.1 > width && !row.is_empty() {
+                        let mut next = Vec::new();
+                        if self.wrap == Wrap::Word && !cell.0.is_whitespace() {
+                            if let Some(blank) = row
+                                .iter()
+                                .rposition(|c: &(char, usize, _)| c.0.is_whitespace())
+                            {
+                                next = row.split_off(blank + 1);
+                            }
+                        }
+                        rows.push(row);
+                        row_width = next.iter().map(|c| c.1).sum();
+                        row = next;
+                    }
+                    row_width += cell.1;
+                    row.push(cell);
+                }
+                rows.push(row);
+            }
+        }
+        rows.into_iter().map(|row| to_text(&row)).collect()
+    }
+}
+
+/// Merges the cells of a row into fragments of the same face.
+fn to_text(cells: &[(char, usize, &Face)]) -> Text {
+    let mut fragments: Vec<TextFragment> = Vec::new();
+    for (c, _, face) in cells {
+        match fragments.last_mut() {
+            Some(tf) if tf.face == **face => tf.text.push(*c),
+            _ => fragments.push(TextFragment {
+                text: c.to_string(),
+                face: (*face).clone(),
+            }),
+        }
+    }
+    fragments.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(rows: Vec<Text>) -> Vec<String> {
+        rows.iter().map(|row| row.plain()).collect()
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(display_width("a\tb", 4), 5);
+        assert_eq!(display_width("abcd\t", 4), 8);
+        assert_eq!(display_width("日本", 4), 4);
+    }
+
+    #[test]
+    fn rows() {
+        let line = Text::from("one two three");
+        let mut layout = Layout {
+            width: Some(5),
+            ..Layout::default()
+        };
+        assert_eq!(plain(layout.rows(&line, 4)), vec!["one t"]);
+        layout.column = 4;
+        assert_eq!(plain(layout.rows(&line, 4)), vec!["two t"]);
+
+        layout.wrap = Wrap::Char;
+        assert_eq!(plain(layout.rows(&line, 4)), vec!["one t", "wo th", "ree"]);
+        layout.wrap = Wrap::Word;
+        assert_eq!(plain(layout.rows(&line, 4)), vec!["one ", "two ", "three"]);
+
+        // wide characters are not split
+        let wide = Text::from("日本語");
+        assert_eq!(plain(layout.rows(&wide, 4)), vec!["日本", "語"]);
+        let tab = Text::from("a\tb");
+        assert_eq!(plain(layout.rows(&tab, 4)), vec!["a   b"]);
+    }
+
+    #[test]
+    fn reveal() {
+        let mut layout = Layout {
+            width: Some(10),
+            ..Layout::default()
+        };
+        layout.reveal(12);
+        assert_eq!(layout.column, 3);
+        layout.reveal(5);
+        assert_eq!(layout.column, 3);
+        layout.reveal(1);
+        assert_eq!(layout.column, 1);
+    }
+}
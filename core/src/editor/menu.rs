@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::ops::Deref;
 
+use crate::editor::core::Core;
 use crate::editor::{Editor, EditorInfo};
 use regex::{CaptureLocations, Regex};
 use remote::jsonrpc;
@@ -22,7 +23,7 @@ pub trait Searchable {
 
 impl Searchable for String {
     fn field(&self) -> &str {
-        &self
+        self
     }
 }
 
@@ -101,7 +102,7 @@ impl<T: Searchable> fmt::Display for Candidate<T> {
 impl<T: Searchable> Ord for Candidate<T> {
     fn cmp(&self, other: &Candidate<T>) -> Ordering {
         if self == other {
-            other.object.field().cmp(&self.object.field())
+            other.object.field().cmp(self.object.field())
         } else if self.score > other.score {
             Ordering::Greater
         } else {
@@ -163,7 +164,7 @@ impl MenuFilter {
         let re = self.build_regex();
         let mut candidates: Vec<Candidate<T>> = items
             .iter()
-            .map(|ref i| Candidate::new(&re, (*i).clone()))
+            .map(|i| Candidate::new(&re, i.clone()))
             .collect();
         candidates.sort_by(|a, b| b.cmp(a));
         Candidates(candidates)
@@ -202,7 +203,7 @@ mod tests {
         let candidates: Candidates<String> = f.filter(&items);
         let res: Vec<String> = candidates
             .iter()
-            .map(|ref c| c.object.field().to_string())
+            .map(|c| c.object.field().to_string())
             .collect();
         assert_eq!(res, expected);
     }
@@ -228,7 +229,7 @@ mod tests {
         let candidates1: Candidates<String> = f.filter(&items1);
         let res1: Vec<String> = candidates1
             .iter()
-            .map(|ref c| c.object.field().to_string())
+            .map(|c| c.object.field().to_string())
             .collect();
 
         let items2 = vec![
@@ -246,7 +247,7 @@ mod tests {
         let candidates2: Candidates<String> = f.filter(&items2);
         let res2: Vec<String> = candidates2
             .iter()
-            .map(|ref c| c.object.field().to_string())
+            .map(|c| c.object.field().to_string())
             .collect();
 
         assert_eq!(res1, expected1);
@@ -317,10 +318,14 @@ impl Searchable for MenuEntry {
 
 pub type EntryProvider = fn(&EditorInfo) -> Vec<MenuEntry>;
 
+/// Provides entries depending on the state of a client, like its current view.
+pub type ClientEntryProvider = fn(&Core, usize) -> Vec<MenuEntry>;
+
 #[derive(Clone)]
 enum Source {
     Prompt(String, MenuAction),
     Provider(EntryProvider),
+    ClientProvider(ClientEntryProvider),
 }
 
 #[derive(Clone)]
@@ -341,6 +346,15 @@ impl Menu {
         }
     }
 
+    pub fn for_client(command: &str, title: &str, provider: ClientEntryProvider) -> Menu {
+        Menu {
+            command: command.to_string(),
+            title: title.to_string(),
+            source: Source::ClientProvider(provider),
+            entries: Vec::new(),
+        }
+    }
+
     pub fn prompt(command: &str, title: &str, message: &str, action: MenuAction) -> Menu {
         Menu {
             command: command.to_string(),
@@ -354,11 +368,11 @@ impl Menu {
         use Source::*;
         match self.source {
             Prompt(_, _) => true,
-            Provider(_) => false,
+            Provider(_) | ClientProvider(_) => false,
         }
     }
 
-    pub fn populate(&mut self, info: &EditorInfo) {
+    pub fn populate(&mut self, info: &EditorInfo, core: &Core, client_id: usize) {
         use Source::*;
         self.entries = match &self.source {
             Prompt(message, action) => vec![MenuEntry {
@@ -368,6 +382,7 @@ impl Menu {
                 action: *action,
             }],
             Provider(provider) => (provider)(info),
+            ClientProvider(provider) => (provider)(core, client_id),
         }
     }
 
@@ -2,29 +2,41 @@ mod buffer;
 mod command;
 mod core;
 mod diff;
+mod encoding;
+mod highlight;
+mod journal;
+mod layout;
 pub mod menu;
 mod piece_table;
 mod range;
+mod register;
+mod search;
 mod selection;
+mod session;
+mod theme;
 pub mod view;
 
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub use self::buffer::{Buffer, BufferSource};
 use self::command::default_commands;
-use self::core::{Core, Notifier};
+use self::core::{lua_error, Core, Notifier};
 pub use self::core::{BUFFER_DEBUG, BUFFER_SCRATCH};
+use self::highlight::{Grammar, GRAMMARS};
 use self::menu::Menu;
 pub use self::piece_table::Coords;
 use self::piece_table::PieceTable;
+use self::session::saved_path;
+use self::theme::{Theme, THEMES};
 use self::view::{Focus, Lens};
 pub use self::view::{View, ViewItem};
 use remote::jsonrpc::{Error, Id, JsonCodingError, Request, Response};
 use remote::protocol::{
     notifications::{self, Notification as _},
-    requests, KeyEvent,
+    requests, Key, KeyEvent,
 };
 use remote::response;
 
@@ -42,9 +54,34 @@ fn key_to_lua<'a>(lua: rlua::Context<'a>, event: &KeyEvent) -> rlua::Result<rlua
     table.set("shift", event.shift)?;
     table.set("value", event.key.to_string())?;
     table.set("display", event.to_string())?;
+    if let Key::Char(c) = event.key {
+        let text: String = if event.shift {
+            c.to_uppercase().collect()
+        } else {
+            c.to_string()
+        };
+        table.set("text", text)?;
+    }
     Ok(table)
 }
 
+/// Where the configuration of the user is read from, `$XDG_CONFIG_HOME/ced` on Linux.
+fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ced"))
+}
+
+/// Files of `dir` with the extension `ext`, in order, none if it cannot be read.
+fn files_with_extension(dir: &Path, ext: &str) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == ext))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
 struct LuaEditor {
     core: Core,
 }
@@ -67,13 +104,21 @@ impl rlua::UserData for LuaEditor {
                         text: v.get::<_, String>("text")?.into(),
                     })
                 }
-                items.sort_by(|a, b| a.index.cmp(&b.index));
+                items.sort_by_key(|a| a.index);
                 this.core
                     .get_notifier()
                     .notify(client, notifications::Status::new(items));
                 Ok(())
             },
         );
+        methods.add_method("macro_keys", |lua, this, register: Option<String>| {
+            this.core
+                .macro_keys(register.as_deref())
+                .map_err(lua_error)?
+                .iter()
+                .map(|key| key_to_lua(lua, key))
+                .collect::<rlua::Result<Vec<_>>>()
+        });
         methods.add_method(
             "show_hint",
             |_, this, (client, lines): (usize, Vec<String>)| {
@@ -126,6 +171,26 @@ impl Editor {
         });
         editor.core.add_view(view);
 
+        for (name, json) in GRAMMARS {
+            match Grammar::parse(Path::new(name), json) {
+                Ok(grammar) => {
+                    editor.core.add_grammar(grammar);
+                }
+                Err(e) => editor.core.debug(&e.to_string()),
+            }
+        }
+        for (name, content) in THEMES {
+            match Theme::parse(Path::new(name), content) {
+                Ok(theme) => {
+                    editor.core.add_theme(theme);
+                }
+                Err(e) => editor.core.debug(&e.to_string()),
+            }
+        }
+        if let Some(dir) = config_dir() {
+            editor.load_config(&dir);
+        }
+
         let lg_core = editor.core.clone();
         let lg_editor = LuaEditor::new(editor.core.clone());
         editor
@@ -151,6 +216,21 @@ impl Editor {
         self.core.cwd()
     }
 
+    /// Loads the grammars and the themes of the user from the `grammars` and `themes`
+    /// directories of `dir`, replacing the shipped ones of the same name.
+    pub fn load_config(&mut self, dir: &Path) {
+        for path in files_with_extension(&dir.join("grammars"), "json") {
+            if let Err(e) = self.core.load_grammar(&path) {
+                self.core.debug(&e.to_string());
+            }
+        }
+        for path in files_with_extension(&dir.join("themes"), "toml") {
+            if let Err(e) = self.core.load_theme(&path) {
+                self.core.debug(&e.to_string());
+            }
+        }
+    }
+
     pub fn exec_lua<F, R>(&mut self, source: &str, client_id: usize, f: F) -> rlua::Result<R>
     where
         F: FnOnce(rlua::Context) -> rlua::Result<R>,
@@ -170,14 +250,13 @@ impl Editor {
                 context.load(&env_source).exec()?;
                 f(context)
             })
-            .map_err(|e| {
+            .inspect_err(|e| {
                 let message = e.to_string();
                 self.core.debug(&format!(
                     "client {}: exec error: {}\n<<<<<<<\n{}\n>>>>>>>",
                     client_id, source, message
                 ));
                 self.core.error(client_id, "exec", &message);
-                e
             })
     }
 
@@ -207,6 +286,47 @@ impl Editor {
         ids
     }
 
+    /// Files to look after for changes made by other programs.
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        self.core.file_paths()
+    }
+
+    /// Reloads the buffers whose file changed on disk, then asks the clients showing the ones
+    /// with unsaved changes what to do with them.
+    pub fn files_changed(&mut self, paths: &[PathBuf]) {
+        let mut clients = Vec::new();
+        for name in self.core.check_files(paths) {
+            for client_id in self.core.clients_with_buffer(&name) {
+                self.core
+                    .message(client_id, &format!("changed on disk: {}", name));
+                if !clients.contains(&client_id) {
+                    clients.push(client_id);
+                }
+            }
+        }
+        for client_id in clients {
+            let params = requests::MenuParams {
+                command: "file_changed".to_owned(),
+                search: String::new(),
+            };
+            if let Err(e) = self.command_menu(client_id, &params) {
+                log::error!("{:?}", e);
+            }
+        }
+    }
+
+    pub fn set_swap_dir(&mut self, dir: PathBuf) {
+        self.core.set_swap_dir(dir);
+    }
+
+    pub fn write_journals(&mut self) {
+        self.core.write_journals();
+    }
+
+    pub fn close_journals(&mut self) {
+        self.core.close_journals();
+    }
+
     pub fn handle(&mut self, client_id: usize, line: &str) -> Result<Response, JsonCodingError> {
         let msg: Request = match line.parse() {
             Ok(req) => req,
@@ -224,10 +344,27 @@ impl Editor {
             "view-delete" => Response::new(msg.id.clone(), self.command_view_delete(client_id)),
             "view-add" => response!(msg, |params| self.command_view_add(client_id, params)),
             "view-remove" => response!(msg, |params| self.command_view_remove(client_id, params)),
+            "view-ack" => response!(msg, |params| self.command_view_ack(client_id, params)),
+            "view-patches" => Response::new(msg.id.clone(), self.command_view_patches(client_id)),
             "menu" => response!(msg, |params| self.command_menu(client_id, params)),
             "menu-select" => response!(msg, |params| self.command_menu_select(client_id, params)),
             "keys" => response!(msg, |params| self.command_keys(client_id, params)),
+            "resize" => response!(msg, |params| self.command_resize(client_id, params)),
+            "macro-replay" => {
+                response!(msg, |params| self.command_macro_replay(client_id, params))
+            }
             "exec" => response!(msg, |params| self.command_exec(client_id, params)),
+            "write" => response!(msg, |params| self.command_write(client_id, params)),
+            "write-all" => response!(msg, |params| self.command_write_all(client_id, params)),
+            "session-save" => response!(msg, |params| self.command_session_save(client_id, params)),
+            "session-restore" => {
+                response!(msg, |params| self
+                    .command_session_restore(client_id, params))
+            }
+            "undo" => Response::new(msg.id.clone(), self.command_undo(client_id)),
+            "redo" => Response::new(msg.id.clone(), self.command_redo(client_id)),
+            "jump-back" => Response::new(msg.id.clone(), self.command_jump_back(client_id)),
+            "jump-forward" => Response::new(msg.id.clone(), self.command_jump_forward(client_id)),
             method => {
                 self.core.error(
                     client_id,
@@ -244,7 +381,24 @@ impl Editor {
         client_id: usize,
         params: &<requests::Edit as requests::Request>::Params,
     ) -> Result<<requests::Edit as requests::Request>::Result, Error> {
-        self.core.edit(client_id, &params.name, params.scratch);
+        self.core
+            .edit(client_id, &params.name, params.scratch)
+            .map_err(|e| {
+                self.core.error(client_id, "edit", &e.to_string());
+                Error::invalid_request(&e.to_string())
+            })?;
+        let recoverable = self
+            .core
+            .recoveries()
+            .iter()
+            .any(|(name, _)| *name == params.name);
+        if recoverable {
+            let params = requests::MenuParams {
+                command: "recover".to_owned(),
+                search: String::new(),
+            };
+            self.command_menu(client_id, &params)?;
+        }
         Ok(())
     }
 
@@ -263,7 +417,7 @@ impl Editor {
         params: &<requests::View as requests::Request>::Params,
     ) -> Result<<requests::View as requests::Request>::Result, Error> {
         self.core
-            .view(client_id, &params)
+            .view(client_id, params)
             .map_err(|e| Error::invalid_request(&e.to_string()))
     }
 
@@ -281,7 +435,7 @@ impl Editor {
         params: &<requests::ViewAdd as requests::Request>::Params,
     ) -> Result<<requests::ViewAdd as requests::Request>::Result, Error> {
         self.core
-            .add_to_current_view(client_id, &params)
+            .add_to_current_view(client_id, params)
             .map_err(|e| Error::invalid_request(&e.to_string()))
     }
 
@@ -291,10 +445,33 @@ impl Editor {
         params: &<requests::ViewRemove as requests::Request>::Params,
     ) -> Result<<requests::ViewRemove as requests::Request>::Result, Error> {
         self.core
-            .remove_from_current_view(client_id, &params)
+            .remove_from_current_view(client_id, params)
             .map_err(|e| Error::invalid_request(&e.to_string()))
     }
 
+    pub fn command_view_ack(
+        &mut self,
+        client_id: usize,
+        params: &<requests::ViewAck as requests::Request>::Params,
+    ) -> Result<<requests::ViewAck as requests::Request>::Result, Error> {
+        if self.core.ack_view(client_id, *params) {
+            Ok(())
+        } else {
+            Err(Error::invalid_params(&format!(
+                "unknown view revision: {}",
+                params
+            )))
+        }
+    }
+
+    pub fn command_view_patches(
+        &mut self,
+        client_id: usize,
+    ) -> Result<<requests::ViewPatches as requests::Request>::Result, Error> {
+        self.core.enable_view_patches(client_id);
+        Ok(())
+    }
+
     pub fn command_menu(
         &mut self,
         client_id: usize,
@@ -312,7 +489,7 @@ impl Editor {
                     buffers: &self.core.buffers(),
                     views: &self.core.views(),
                 };
-                menu.populate(&info);
+                menu.populate(&info, &self.core, client_id);
             }
         }
         let menu = &self.command_map[&params.command];
@@ -351,27 +528,185 @@ impl Editor {
         params: &<requests::Keys as requests::Request>::Params,
     ) -> Result<<requests::Keys as requests::Request>::Result, Error> {
         for key in params {
+            // keys starting or stopping a recording are left out of it
+            let recording = self.core.recording(client_id).is_some();
             let result = self.exec_lua("keys", client_id, |lua| {
                 let handler = lua
                     .load(&format!("editor.clients[{}].key_handler", client_id))
                     .eval::<rlua::Table>()?;
                 let handle_func = handler.get::<_, rlua::Function>("handle")?;
-                handle_func.call::<_, ()>((handler, key_to_lua(lua, &key)))
+                handle_func.call::<_, ()>((handler, key_to_lua(lua, key)))
             });
             if let Err(e) = result {
                 self.core.error(client_id, "key handler", &e.to_string());
                 return Err(Error::internal_error(&e.to_string()));
             }
+            if recording {
+                self.core.record_key(client_id, &key.to_string());
+            }
         }
         Ok(())
     }
 
+    pub fn command_resize(
+        &mut self,
+        client_id: usize,
+        params: &<requests::Resize as requests::Request>::Params,
+    ) -> Result<<requests::Resize as requests::Request>::Result, Error> {
+        self.core.resize(client_id, params.width, params.height);
+        Ok(())
+    }
+
+    pub fn command_macro_replay(
+        &mut self,
+        client_id: usize,
+        params: &<requests::MacroReplay as requests::Request>::Params,
+    ) -> Result<<requests::MacroReplay as requests::Request>::Result, Error> {
+        let target = params.client.unwrap_or(client_id);
+        if !self.core.has_client(target) {
+            return Err(Error::invalid_params(&format!(
+                "unknown client: {}",
+                target
+            )));
+        }
+        let replayed = self
+            .exec_lua("macro", target, |lua| {
+                let editor = lua.globals().get::<_, rlua::Table>("editor")?;
+                let replay = editor.get::<_, rlua::Function>("replay_macro")?;
+                replay.call::<_, bool>((editor, target, params.register.clone(), params.count))
+            })
+            .map_err(|e| Error::internal_error(&e.to_string()))?;
+        if replayed {
+            Ok(())
+        } else {
+            Err(Error::invalid_params(&format!(
+                "no macro to replay in register: {}",
+                params.register.as_deref().unwrap_or("@")
+            )))
+        }
+    }
+
     pub fn command_exec(
         &mut self,
         client_id: usize,
         params: &<requests::Exec as requests::Request>::Params,
     ) -> Result<<requests::Exec as requests::Request>::Result, Error> {
-        self.exec_lua("exec", client_id, |lua| lua.load(params).exec())
-            .map_err(|e| Error::new(1, "exec error".to_string(), e.to_string()).unwrap())
+        // a scripted command is undone at once
+        self.core.begin_undo_group(client_id);
+        let result = self
+            .exec_lua("exec", client_id, |lua| lua.load(params).exec())
+            .map_err(|e| Error::new(1, "exec error".to_string(), e.to_string()).unwrap());
+        self.core.end_undo_group(client_id);
+        result
+    }
+
+    pub fn command_undo(
+        &mut self,
+        client_id: usize,
+    ) -> Result<<requests::Undo as requests::Request>::Result, Error> {
+        if !self.core.undo(client_id) {
+            self.core.message(client_id, "nothing to undo");
+        }
+        Ok(())
+    }
+
+    pub fn command_redo(
+        &mut self,
+        client_id: usize,
+    ) -> Result<<requests::Redo as requests::Request>::Result, Error> {
+        if !self.core.redo(client_id) {
+            self.core.message(client_id, "nothing to redo");
+        }
+        Ok(())
+    }
+
+    pub fn command_jump_back(
+        &mut self,
+        client_id: usize,
+    ) -> Result<<requests::JumpBack as requests::Request>::Result, Error> {
+        if !self.core.jump_back(client_id) {
+            self.core.message(client_id, "no older jump");
+        }
+        Ok(())
+    }
+
+    pub fn command_jump_forward(
+        &mut self,
+        client_id: usize,
+    ) -> Result<<requests::JumpForward as requests::Request>::Result, Error> {
+        if !self.core.jump_forward(client_id) {
+            self.core.message(client_id, "no newer jump");
+        }
+        Ok(())
+    }
+
+    fn report_written(&mut self, client_id: usize, paths: &[PathBuf]) {
+        let message = match paths {
+            [] => "nothing to write".to_owned(),
+            [path] => format!("written: {}", path.display()),
+            _ => format!("written: {} files", paths.len()),
+        };
+        self.core.message(client_id, &message);
+    }
+
+    pub fn command_write(
+        &mut self,
+        client_id: usize,
+        params: &<requests::Write as requests::Request>::Params,
+    ) -> Result<<requests::Write as requests::Request>::Result, Error> {
+        let paths = self
+            .core
+            .write(client_id, params.buffer.as_deref(), params.force)
+            .map_err(|e| Error::invalid_request(&e.to_string()))?;
+        self.report_written(client_id, &paths);
+        Ok(())
+    }
+
+    pub fn command_write_all(
+        &mut self,
+        client_id: usize,
+        params: &<requests::WriteAll as requests::Request>::Params,
+    ) -> Result<<requests::WriteAll as requests::Request>::Result, Error> {
+        let paths = self
+            .core
+            .write_all(params.force)
+            .map_err(|e| Error::invalid_request(&e.to_string()))?;
+        self.report_written(client_id, &paths);
+        Ok(())
+    }
+
+    pub fn command_session_save(
+        &mut self,
+        client_id: usize,
+        params: &<requests::SessionSave as requests::Request>::Params,
+    ) -> Result<<requests::SessionSave as requests::Request>::Result, Error> {
+        let name = params.name.as_deref().unwrap_or(&self.session_name);
+        self.core
+            .save_session(client_id, &saved_path(name))
+            .map_err(|e| Error::invalid_request(&e.to_string()))?;
+        self.core
+            .message(client_id, &format!("session saved: {}", name));
+        Ok(())
+    }
+
+    pub fn command_session_restore(
+        &mut self,
+        client_id: usize,
+        params: &<requests::SessionRestore as requests::Request>::Params,
+    ) -> Result<<requests::SessionRestore as requests::Request>::Result, Error> {
+        let name = params.name.as_deref().unwrap_or(&self.session_name);
+        self.core
+            .restore_session(client_id, &saved_path(name))
+            .map_err(|e| Error::invalid_request(&e.to_string()))?;
+        let info = EditorInfo {
+            session: &self.session_name,
+            cwd: &self.cwd(),
+            buffers: &[],
+            views: &[],
+        };
+        self.core.get_notifier().info_update(client_id, &info);
+        self.core
+            .message(client_id, &format!("session restored: {}", name));
+        Ok(())
     }
 }
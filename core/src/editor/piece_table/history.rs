@@ -0,0 +1,194 @@
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RevisionInfo {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub time: SystemTime,
+}
+
+struct Revision<T> {
+    info: RevisionInfo,
+    state: T,
+    // child to follow when redoing, the most recently created or left one
+    last_child: Option<usize>,
+}
+
+/// Undo tree keeping every revision of a state, branches included. Revision ids are given in
+/// chronological order, the root being 0.
+pub struct UndoTree<T> {
+    revisions: Vec<Revision<T>>,
+    current: usize,
+}
+
+impl<T> UndoTree<T> {
+    pub fn new(state: T, time: SystemTime) -> UndoTree<T> {
+        UndoTree {
+            revisions: vec![Revision {
+                info: RevisionInfo {
+                    id: 0,
+                    parent: None,
+                    time,
+                },
+                state,
+                last_child: None,
+            }],
+            current: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.revisions.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn get(&self, id: usize) -> Option<&T> {
+        self.revisions.get(id).map(|r| &r.state)
+    }
+
+    pub fn parent(&self, id: usize) -> Option<usize> {
+        self.revisions.get(id).and_then(|r| r.info.parent)
+    }
+
+    pub fn revisions(&self) -> impl Iterator<Item = RevisionInfo> + '_ {
+        self.revisions.iter().map(|r| r.info)
+    }
+
+    /// Adds a revision as a child of the current one and makes it current.
+    pub fn commit(&mut self, state: T, time: SystemTime) -> usize {
+        let id = self.revisions.len();
+        self.revisions.push(Revision {
+            info: RevisionInfo {
+                id,
+                parent: Some(self.current),
+                time,
+            },
+            state,
+            last_child: None,
+        });
+        self.revisions[self.current].last_child = Some(id);
+        self.current = id;
+        id
+    }
+
+    pub fn undo(&mut self) -> Option<&T> {
+        let parent = self.revisions[self.current].info.parent?;
+        self.revisions[parent].last_child = Some(self.current);
+        self.goto(parent)
+    }
+
+    pub fn redo(&mut self) -> Option<&T> {
+        let child = self.revisions[self.current].last_child?;
+        self.goto(child)
+    }
+
+    pub fn goto(&mut self, id: usize) -> Option<&T> {
+        if id == self.current || id >= self.revisions.len() {
+            return None;
+        }
+        // leave a redo path from the common ancestor down to the target
+        let mut child = id;
+        while let Some(parent) = self.revisions[child].info.parent {
+            self.revisions[parent].last_child = Some(child);
+            child = parent;
+        }
+        self.current = id;
+        Some(&self.revisions[id].state)
+    }
+
+    /// Revisions left going up from `from` to the closest ancestor it shares with `to`, and
+    /// the ones entered going down from there to `to`, in order.
+    pub fn path(&self, mut from: usize, mut to: usize) -> (Vec<usize>, Vec<usize>) {
+        let (mut up, mut down) = (Vec::new(), Vec::new());
+        // a parent always has a lower id than its children
+        while from != to {
+            if from > to {
+                up.push(from);
+                from = self.revisions[from].info.parent.unwrap();
+            } else {
+                down.push(to);
+                to = self.revisions[to].info.parent.unwrap();
+            }
+        }
+        down.reverse();
+        (up, down)
+    }
+
+    /// Finds the latest revision made at least `duration` before the current one.
+    pub fn earlier(&self, duration: Duration) -> usize {
+        let time = self.revisions[self.current].info.time;
+        time.checked_sub(duration)
+            .and_then(|limit| {
+                self.revisions
+                    .iter()
+                    .rev()
+                    .find(|r| r.info.time <= limit)
+                    .map(|r| r.info.id)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Finds the first revision made at least `duration` after the current one.
+    pub fn later(&self, duration: Duration) -> usize {
+        let time = self.revisions[self.current].info.time;
+        time.checked_add(duration)
+            .and_then(|limit| {
+                self.revisions
+                    .iter()
+                    .find(|r| r.info.time >= limit)
+                    .map(|r| r.info.id)
+            })
+            .unwrap_or(self.revisions.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn branches() {
+        let mut tree = UndoTree::new("a", at(0));
+        tree.commit("ab", at(1));
+        tree.commit("abc", at(2));
+        assert_eq!(tree.undo(), Some(&"ab"));
+        assert_eq!(tree.undo(), Some(&"a"));
+        assert_eq!(tree.undo(), None);
+
+        // a new edit after an undo keeps the previous branch
+        assert_eq!(tree.commit("ax", at(3)), 3);
+        assert_eq!(tree.undo(), Some(&"a"));
+        assert_eq!(tree.redo(), Some(&"ax"));
+        assert_eq!(tree.redo(), None);
+        assert_eq!(tree.path(3, 2), (vec![3], vec![1, 2]));
+        assert_eq!(tree.goto(2), Some(&"abc"));
+        assert_eq!(tree.parent(2), Some(1));
+
+        // redo follows the branch that was visited last
+        tree.undo();
+        tree.undo();
+        assert_eq!(tree.redo(), Some(&"ab"));
+        assert_eq!(tree.redo(), Some(&"abc"));
+    }
+
+    #[test]
+    fn time_travel() {
+        let mut tree = UndoTree::new(0, at(0));
+        tree.commit(1, at(60));
+        tree.commit(2, at(120));
+        tree.commit(3, at(400));
+
+        assert_eq!(tree.earlier(Duration::from_secs(300)), 1);
+        assert_eq!(tree.earlier(Duration::from_secs(1000)), 0);
+        tree.goto(1);
+        assert_eq!(tree.later(Duration::from_secs(30)), 2);
+        assert_eq!(tree.later(Duration::from_secs(1000)), 3);
+    }
+}
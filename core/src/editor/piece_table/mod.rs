@@ -1,14 +1,20 @@
+mod history;
 mod navigator;
+mod object;
 mod position;
 
 use std::cmp::{max, Ordering};
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, SystemTime};
 
 use crate::editor::diff::{diff, Diff};
 use crate::editor::range::{OffsetRange, Range};
 use bstr::ByteSlice;
-pub use navigator::Navigator;
-pub use position::{Position, PositionIterator};
+pub use history::RevisionInfo;
+use history::UndoTree;
+pub use navigator::{Navigator, WordChars};
+pub use object::TextObject;
+pub use position::Position;
 use rbtset::{Consecutive, Node, RBTreeSet};
 
 #[derive(Clone, Copy, Debug, Eq)]
@@ -165,14 +171,104 @@ impl From<(usize, usize)> for Coords {
     }
 }
 
+/// A replacement made on a text: `removed` bytes at `offset` replaced with `inserted` ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edit {
+    pub offset: usize,
+    pub removed: usize,
+    pub inserted: usize,
+}
+
+impl Edit {
+    /// Where an offset of the text goes, offsets inside the removed part collapsing onto
+    /// `offset`.
+    pub fn remap(&self, pos: usize) -> usize {
+        if pos < self.offset {
+            pos
+        } else if pos < self.offset + self.removed {
+            self.offset
+        } else {
+            pos - self.removed + self.inserted
+        }
+    }
+}
+
+/// An edit made on a piece table, along with the `removed_lines` line breaks it removed from
+/// line `line`, counted from 0, and the `inserted_lines` ones it inserted there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Change {
+    pub edit: Edit,
+    pub line: usize,
+    pub removed_lines: usize,
+    pub inserted_lines: usize,
+}
+
+impl Change {
+    /// The change undoing it, made on the text it leads to.
+    pub fn inverse(&self) -> Change {
+        Change {
+            edit: Edit {
+                offset: self.edit.offset,
+                removed: self.edit.inserted,
+                inserted: self.edit.removed,
+            },
+            line: self.line,
+            removed_lines: self.inserted_lines,
+            inserted_lines: self.removed_lines,
+        }
+    }
+
+    /// Where a range of lines goes, the lines of the edit being kept whole in it. The range
+    /// never becomes empty.
+    pub fn remap_lines(&self, lines: &std::ops::Range<usize>) -> std::ops::Range<usize> {
+        let last = self.line + self.removed_lines;
+        let start = if lines.start > last {
+            lines.start - self.removed_lines + self.inserted_lines
+        } else {
+            lines.start.min(self.line)
+        };
+        let end = if lines.end <= self.line {
+            lines.end
+        } else if lines.end > last {
+            lines.end - self.removed_lines + self.inserted_lines
+        } else {
+            self.line + self.inserted_lines + 1
+        };
+        start..end.max(start + 1)
+    }
+}
+
+/// Pieces of a revision of the content, with the changes made to its parent to get it.
+#[derive(Clone)]
+struct Snapshot {
+    pieces: RBTreeSet<Piece>,
+    changes: Vec<Change>,
+}
+
+impl Snapshot {
+    fn new(pieces: RBTreeSet<Piece>) -> Snapshot {
+        Snapshot {
+            pieces,
+            changes: Vec::new(),
+        }
+    }
+}
+
 pub struct PieceTable {
     original: Vec<u8>,
     added: Vec<u8>,
     pieces: RBTreeSet<Piece>,
     newlines: BTreeSet<usize>,
     last_action: Option<Action>,
-    undos: Vec<RBTreeSet<Piece>>,
-    redos: Vec<RBTreeSet<Piece>>,
+    history: UndoTree<Snapshot>,
+    // time of the last change not yet committed to the history
+    pending: Option<SystemTime>,
+    // changes not yet committed to the history
+    uncommitted: Vec<Change>,
+    // named offsets following the edits
+    marks: HashMap<char, usize>,
+    // changes made since the last call to `take_changes`
+    changes: Vec<Change>,
 }
 
 impl PieceTable {
@@ -188,11 +284,14 @@ impl PieceTable {
         PieceTable {
             original: text.into_bytes(),
             added: Vec::new(),
+            history: UndoTree::new(Snapshot::new(pieces.clone()), SystemTime::now()),
             pieces,
             newlines,
             last_action: None,
-            undos: Vec::new(),
-            redos: Vec::new(),
+            pending: None,
+            uncommitted: Vec::new(),
+            marks: HashMap::new(),
+            changes: Vec::new(),
         }
     }
 
@@ -203,8 +302,11 @@ impl PieceTable {
             pieces: RBTreeSet::new(),
             newlines: BTreeSet::new(),
             last_action: None,
-            undos: Vec::new(),
-            redos: Vec::new(),
+            history: UndoTree::new(Snapshot::new(RBTreeSet::new()), SystemTime::now()),
+            pending: None,
+            uncommitted: Vec::new(),
+            marks: HashMap::new(),
+            changes: Vec::new(),
         }
     }
 
@@ -218,8 +320,13 @@ impl PieceTable {
 
     fn commit(&mut self) {
         self.pieces.repack();
-        self.undos.push(self.pieces.clone());
-        self.redos.clear();
+        if let Some(time) = self.pending.take() {
+            let snapshot = Snapshot {
+                pieces: self.pieces.clone(),
+                changes: std::mem::take(&mut self.uncommitted),
+            };
+            self.history.commit(snapshot, time);
+        }
         self.last_action = None;
     }
 
@@ -233,36 +340,126 @@ impl PieceTable {
         }
     }
 
-    fn start_bulk(&mut self) {
+    /// Groups all the following operations in a single undo step until `end_bulk` is called.
+    pub fn start_bulk(&mut self) {
         self.action(Action::Bulk);
     }
 
-    fn end_bulk(&mut self) {
+    pub fn end_bulk(&mut self) {
         self.last_action = None;
     }
 
-    fn undo(&mut self) -> bool {
-        if let Some(pieces) = self.undos.pop() {
-            self.redos.push(self.pieces.clone());
-            self.pieces = pieces;
-            self.last_action = None;
-            true
+    /// Id of the revision of the current content. Changes not committed yet get the id of the
+    /// revision they will be committed as.
+    pub fn revision(&self) -> usize {
+        if self.pending.is_some() {
+            self.history.len()
         } else {
-            false
+            self.history.current()
         }
     }
 
-    fn redo(&mut self) -> bool {
-        if let Some(pieces) = self.redos.pop() {
-            self.undos.push(self.pieces.clone());
-            self.pieces = pieces;
-            self.last_action = None;
-            true
+    /// Id of the revision the changes not committed yet, or the next ones, are committed as.
+    pub fn pending_revision(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn has_pending_changes(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn parent_revision(&self, id: usize) -> Option<usize> {
+        self.history.parent(id)
+    }
+
+    pub fn revisions(&self) -> Vec<RevisionInfo> {
+        self.history.revisions().collect()
+    }
+
+    pub fn revision_text(&self, id: usize) -> Option<String> {
+        if id == self.revision() {
+            Some(self.text())
         } else {
-            false
+            self.history.get(id).map(|snapshot| {
+                String::from_utf8_lossy(&self.pieces_bytes(&snapshot.pieces)).into()
+            })
         }
     }
 
+    fn reindex_newlines(&mut self) {
+        let newlines = self
+            .pieces
+            .values()
+            .flat_map(|p| {
+                self.piece_buffer(&p)[p.start..p.end()]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &b)| b == b'\n')
+                    .map(move |(i, _)| p.offset + i)
+            })
+            .collect();
+        self.newlines = newlines;
+    }
+
+    /// Puts back the pieces of the current revision of the history, left from revision
+    /// `from`, recording the changes undone and redone on the way. Returns false if the
+    /// revision did not change.
+    fn restore(&mut self, from: usize) -> bool {
+        let to = self.history.current();
+        if to == from {
+            return false;
+        }
+        let (undone, redone) = self.history.path(from, to);
+        let mut changes = Vec::new();
+        for id in undone {
+            let snapshot = self.history.get(id).unwrap();
+            changes.extend(snapshot.changes.iter().rev().map(Change::inverse));
+        }
+        for id in redone {
+            changes.extend_from_slice(&self.history.get(id).unwrap().changes);
+        }
+        for change in changes {
+            self.push_change(change);
+        }
+        self.pieces = self.history.get(to).unwrap().pieces.clone();
+        self.reindex_newlines();
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.commit();
+        let from = self.history.current();
+        self.history.undo();
+        self.restore(from)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.commit();
+        let from = self.history.current();
+        self.history.redo();
+        self.restore(from)
+    }
+
+    pub fn goto_revision(&mut self, id: usize) -> bool {
+        self.commit();
+        let from = self.history.current();
+        self.history.goto(id);
+        self.restore(from)
+    }
+
+    /// Goes back to the state the content had `duration` before the current revision.
+    pub fn earlier(&mut self, duration: Duration) -> bool {
+        self.commit();
+        let id = self.history.earlier(duration);
+        self.goto_revision(id)
+    }
+
+    pub fn later(&mut self, duration: Duration) -> bool {
+        self.commit();
+        let id = self.history.later(duration);
+        self.goto_revision(id)
+    }
+
     fn range(&self, range: &dyn Range) -> Option<Vec<u8>> {
         self.pieces
             .get_node(&Piece::offset(range.start()))
@@ -284,6 +481,64 @@ impl PieceTable {
             })
     }
 
+    fn piece_buffer(&self, piece: &Piece) -> &[u8] {
+        if piece.original {
+            &self.original
+        } else {
+            &self.added
+        }
+    }
+
+    /// Bytes of the piece holding `offset`, from `offset` to the end of the piece.
+    fn piece_bytes_from(&self, offset: usize) -> Option<&[u8]> {
+        let piece = *self.pieces.get_node(&Piece::offset(offset))?.data();
+        let bytes = &self.piece_buffer(&piece)[piece.start + offset - piece.offset..piece.end()];
+        Some(bytes).filter(|bytes| !bytes.is_empty())
+    }
+
+    /// Offset and bytes of the piece holding the byte before `offset`, up to `offset`.
+    fn piece_bytes_before(&self, offset: usize) -> Option<(usize, &[u8])> {
+        let piece = *self
+            .pieces
+            .get_node(&Piece::offset(offset.checked_sub(1)?))?
+            .data();
+        let bytes = &self.piece_buffer(&piece)[piece.start..piece.start + offset - piece.offset];
+        Some((piece.offset, bytes)).filter(|(_, bytes)| !bytes.is_empty())
+    }
+
+    /// Characters from `offset` to the end, with their offset, read one piece at a time.
+    pub fn chars_from(&self, offset: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut next = offset;
+        let mut chars: Option<(usize, bstr::CharIndices<'_>)> = None;
+        std::iter::from_fn(move || loop {
+            if let Some((start, ref mut piece_chars)) = chars {
+                if let Some((i, _, c)) = piece_chars.next() {
+                    return Some((start + i, c));
+                }
+            }
+            let bytes = self.piece_bytes_from(next)?;
+            chars = Some((next, bytes.char_indices()));
+            next += bytes.len();
+        })
+    }
+
+    /// Characters before `offset` back to the beginning, with their offset, read one piece at
+    /// a time.
+    pub fn chars_before(&self, offset: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut end = offset;
+        let mut chars: Option<(usize, bstr::CharIndices<'_>)> = None;
+        std::iter::from_fn(move || loop {
+            if let Some((start, ref mut piece_chars)) = chars {
+                if let Some((i, _, c)) = piece_chars.next_back() {
+                    return Some((start + i, c));
+                }
+            }
+            let (start, bytes) = self.piece_bytes_before(end)?;
+            chars = Some((start, bytes.char_indices()));
+            end = start;
+        })
+    }
+
     fn join(&self, sep: &str) -> String {
         self.pieces
             .values()
@@ -304,6 +559,24 @@ impl PieceTable {
         self.join("")
     }
 
+    pub fn bytes(&self) -> Vec<u8> {
+        self.pieces_bytes(&self.pieces)
+    }
+
+    fn pieces_bytes(&self, pieces: &RBTreeSet<Piece>) -> Vec<u8> {
+        pieces
+            .values()
+            .flat_map(|p| {
+                let buffer = if p.original {
+                    &self.original
+                } else {
+                    &self.added
+                };
+                buffer[p.start..p.end()].to_vec()
+            })
+            .collect()
+    }
+
     pub fn text_range(&self, range: &dyn Range) -> Option<String> {
         // TODO handle decoding error
         self.range(range)
@@ -315,7 +588,7 @@ impl PieceTable {
     }
 
     pub fn line_count(&self) -> usize {
-        if self.newlines.get(&self.max_offset()).is_some() {
+        if self.newlines.contains(&self.max_offset()) {
             self.newlines.len()
         } else {
             self.newlines.len() + 1
@@ -363,7 +636,7 @@ impl PieceTable {
             let lineno = idx + 1;
             return Some(Coords {
                 l: lineno,
-                c: self.line_length(lineno) + 1,
+                c: self.line_bytes(lineno).unwrap().graphemes().count() + 1,
             });
         }
 
@@ -376,10 +649,14 @@ impl PieceTable {
                 let col = indices.range(..offset - nli).count();
                 Coords { l: lineno, c: col }
             }
-            None => Coords {
-                l: lineno,
-                c: offset + 1,
-            },
+            None => {
+                let line = self.line_bytes(lineno).unwrap();
+                let col = line
+                    .grapheme_indices()
+                    .take_while(|(i, _, _)| *i <= offset)
+                    .count();
+                Coords { l: lineno, c: col }
+            }
         })
     }
 
@@ -493,6 +770,7 @@ impl PieceTable {
         } else {
             0
         };
+        self.record(offset, 0, &text);
         let index = self.added.len();
         self.pieces.insert(Piece {
             offset,
@@ -505,6 +783,60 @@ impl PieceTable {
         self.added.extend(text.into_bytes());
     }
 
+    pub fn set_mark(&mut self, name: char, offset: usize) {
+        self.marks.insert(name, offset);
+    }
+
+    /// The offset of the mark, kept inside the content when it moved to another revision.
+    pub fn mark(&self, name: char) -> Option<usize> {
+        self.marks
+            .get(&name)
+            .map(|&offset| offset.min(self.max_offset()))
+    }
+
+    pub fn marks(&self) -> Vec<(char, usize)> {
+        let mut marks = self
+            .marks
+            .keys()
+            .filter_map(|&name| Some((name, self.mark(name)?)))
+            .collect::<Vec<_>>();
+        marks.sort_unstable();
+        marks
+    }
+
+    /// Takes the changes made to the content since the last call, in the order they were made,
+    /// each offset taking the previous changes into account.
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Records the replacement of the `removed` bytes at `offset` with `text`, before it is made.
+    fn record(&mut self, offset: usize, removed: usize, text: &str) {
+        if removed == 0 && text.is_empty() {
+            return;
+        }
+        self.pending = Some(SystemTime::now());
+        let change = Change {
+            edit: Edit {
+                offset,
+                removed,
+                inserted: text.len(),
+            },
+            line: self.newlines.range(..offset).count(),
+            removed_lines: self.newlines.range(offset..offset + removed).count(),
+            inserted_lines: text.matches('\n').count(),
+        };
+        self.uncommitted.push(change);
+        self.push_change(change);
+    }
+
+    fn push_change(&mut self, change: Change) {
+        for mark in self.marks.values_mut() {
+            *mark = change.edit.remap(*mark);
+        }
+        self.changes.push(change);
+    }
+
     fn shift_offset_after(&mut self, node: &Node<Piece>, value: i64) {
         if value != 0 {
             for n in self.pieces.iter_from(node).skip(1) {
@@ -516,6 +848,7 @@ impl PieceTable {
     pub fn insert(&mut self, offset: usize, text: String) {
         self.action(Action::Insert);
         if let Some(ref mut node) = self.pieces.get_node(&Piece::offset(offset)) {
+            self.record(offset, 0, &text);
             let added_start = self.added.len();
             self.added.extend(text.as_bytes());
             let new = Piece {
@@ -552,12 +885,14 @@ impl PieceTable {
 
         // special case: deleting exactly the whole content
         if range.start() == 0 && range.len() == self.len() {
+            self.record(0, range.len(), "");
             self.pieces.clear();
             self.newlines.clear();
             return;
         }
 
         if let Some(start_node) = self.pieces.get_node(&Piece::offset(range.start())) {
+            self.record(range.start(), range.len(), "");
             let pieces = self
                 .pieces
                 .values_from(&start_node)
@@ -577,9 +912,10 @@ impl PieceTable {
             }
             if let Some(ln) = last_node {
                 self.shift_offset_after(&ln, -(range.len() as i64));
-            } else {
-                start_node.apply(|n| n.offset -= range.len());
-                self.shift_offset_after(&start_node, -(range.len() as i64));
+            } else if let Some(next) = self.pieces.get_node(&Piece::offset(range.end())) {
+                // whole pieces removed, the following ones still have their former offsets
+                next.apply(|n| n.offset -= range.len());
+                self.shift_offset_after(&next, -(range.len() as i64));
             }
             self.newlines = self
                 .newlines
@@ -604,19 +940,38 @@ impl PieceTable {
         self.end_bulk();
     }
 
-    pub fn apply_diff(&mut self, text: &str) {
+    /// Changes the content to `text` with the fewest edits, returned in the order they were
+    /// applied, each offset taking the previous edits into account.
+    pub fn apply_diff(&mut self, text: &str) -> Vec<Edit> {
         let original = self.text();
-        // TODO trailing newline optimization
         let diffs = diff(&original, text);
+        let mut edits: Vec<Edit> = Vec::new();
         let mut loffset = 0;
         let mut roffset = 0;
 
         self.start_bulk();
         for diff in diffs {
             match diff {
-                Diff::Left(len) => self.delete(&OffsetRange::new(loffset, len)),
+                Diff::Left(len) => {
+                    self.delete(&OffsetRange::new(loffset, len));
+                    edits.push(Edit {
+                        offset: loffset,
+                        removed: len,
+                        inserted: 0,
+                    });
+                }
                 Diff::Right(len) => {
                     self.insert(loffset, text[roffset..roffset + len].to_owned());
+                    match edits.last_mut() {
+                        Some(edit) if edit.offset == loffset && edit.inserted == 0 => {
+                            edit.inserted = len
+                        }
+                        _ => edits.push(Edit {
+                            offset: loffset,
+                            removed: 0,
+                            inserted: len,
+                        }),
+                    }
                     loffset += len;
                     roffset += len;
                 }
@@ -627,6 +982,7 @@ impl PieceTable {
             }
         }
         self.end_bulk();
+        edits
     }
 
     pub fn navigate(&self, from: impl Into<Option<Coords>>) -> Option<Navigator<'_>> {
@@ -689,6 +1045,17 @@ mod tests {
         assert_eq!(pieces.text(), "🦊 the fox jumps over the dog 🐶, so quick");
     }
 
+    #[test]
+    fn delete_whole_pieces() {
+        let mut pieces = PieceTable::with_text("one three\n".to_owned());
+        pieces.insert(4, "two ".to_owned());
+        pieces.delete(&OffsetRange::new(0, 8));
+        assert_eq!(pieces.text(), "three\n");
+        pieces.insert(5, "!".to_owned());
+        assert_eq!(pieces.text(), "three!\n");
+        assert_eq!(pieces.line_count(), 1);
+    }
+
     #[test]
     fn delete_empty() {
         let mut pieces = PieceTable::new();
@@ -726,6 +1093,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn marks_follow_edits() {
+        let mut pieces = PieceTable::with_text("one two three\n".to_owned());
+        pieces.set_mark('a', 4);
+        pieces.set_mark('b', 8);
+        pieces.set_mark('c', 12);
+        pieces.insert(4, "and ".to_owned());
+        assert_eq!(pieces.marks(), vec![('a', 8), ('b', 12), ('c', 16)]);
+        pieces.delete(&(10..16));
+        assert_eq!(pieces.marks(), vec![('a', 8), ('b', 10), ('c', 10)]);
+        pieces.delete(&(0..14));
+        assert_eq!(pieces.mark('a'), Some(0));
+        assert_eq!(pieces.mark('d'), None);
+    }
+
+    #[test]
+    fn changes() {
+        let mut pieces = PieceTable::with_text("one\ntwo\n".to_owned());
+        pieces.insert(4, "2\n".to_owned());
+        pieces.delete(&(0..4));
+        assert_eq!(
+            pieces.take_changes(),
+            vec![
+                Change {
+                    edit: Edit {
+                        offset: 4,
+                        removed: 0,
+                        inserted: 2
+                    },
+                    line: 1,
+                    removed_lines: 0,
+                    inserted_lines: 1,
+                },
+                Change {
+                    edit: Edit {
+                        offset: 0,
+                        removed: 4,
+                        inserted: 0
+                    },
+                    line: 0,
+                    removed_lines: 1,
+                    inserted_lines: 0,
+                },
+            ]
+        );
+        assert!(pieces.take_changes().is_empty());
+
+        // moving to another revision is described as edits too
+        assert!(pieces.undo());
+        assert_eq!(pieces.text(), "one\n2\ntwo\n");
+        let deletion = Change {
+            edit: Edit {
+                offset: 0,
+                removed: 4,
+                inserted: 0,
+            },
+            line: 0,
+            removed_lines: 1,
+            inserted_lines: 0,
+        };
+        assert_eq!(pieces.take_changes(), vec![deletion.inverse()]);
+        assert!(pieces.redo());
+        assert_eq!(pieces.take_changes(), vec![deletion]);
+        // through another branch
+        assert!(pieces.goto_revision(0));
+        pieces.insert(0, "zero\n".to_owned());
+        pieces.take_changes();
+        assert!(pieces.goto_revision(1));
+        assert_eq!(pieces.text(), "one\n2\ntwo\n");
+        assert_eq!(
+            pieces
+                .take_changes()
+                .iter()
+                .map(|c| (c.edit.offset, c.edit.removed, c.edit.inserted))
+                .collect::<Vec<_>>(),
+            vec![(0, 5, 0), (4, 0, 2)]
+        );
+        let change = Change {
+            edit: Edit {
+                offset: 2,
+                removed: 5,
+                inserted: 1,
+            },
+            line: 0,
+            removed_lines: 2,
+            inserted_lines: 0,
+        };
+        assert_eq!(change.remap_lines(&(0..1)), 0..1);
+        assert_eq!(change.remap_lines(&(1..2)), 0..1);
+        assert_eq!(change.remap_lines(&(3..5)), 1..3);
+    }
+
     #[test]
     fn apply_diff() {
         let mut pieces = PieceTable::new();
@@ -741,6 +1200,18 @@ mod tests {
 
         print!("{}", pieces.pieces.dump_tree_as_dot());
         assert_eq!(pieces.text(), new_text);
+
+        let mut pieces = PieceTable::with_text("one\ntwo\n".to_owned());
+        let edits = pieces.apply_diff("one\n2\n3\n");
+        assert_eq!(pieces.text(), "one\n2\n3\n");
+        assert_eq!(
+            edits,
+            vec![Edit {
+                offset: 4,
+                removed: 3,
+                inserted: 3
+            }]
+        );
     }
 
     #[test]
@@ -808,6 +1279,81 @@ mod tests {
         assert_eq!(pieces.text(), new_text);
     }
 
+    #[test]
+    fn undo_newlines() {
+        let mut pieces = PieceTable::with_text("first\nsecond\n".to_owned());
+        pieces.insert(6, "inserted\n".to_owned());
+        assert_eq!(pieces.line_count(), 3);
+        assert!(pieces.undo());
+        assert_eq!(pieces.line_count(), 2);
+        assert_eq!(pieces.line_text(2), Some("second".into()));
+        assert!(pieces.redo());
+        assert_eq!(pieces.line_text(2), Some("inserted".into()));
+    }
+
+    #[test]
+    fn bulk_undo() {
+        let mut pieces = PieceTable::with_text("text\n".to_owned());
+        pieces.start_bulk();
+        pieces.insert(0, "some ".to_owned());
+        pieces.delete(&OffsetRange::new(0, 1));
+        pieces.append("more\n".to_owned());
+        pieces.end_bulk();
+        assert_eq!(pieces.text(), "ome text\nmore\n");
+        assert_eq!(pieces.revision(), 1);
+        assert!(pieces.undo());
+        assert_eq!(pieces.text(), "text\n");
+        assert_eq!(pieces.revision(), 0);
+        assert!(pieces.redo());
+        assert_eq!(pieces.text(), "ome text\nmore\n");
+    }
+
+    #[test]
+    fn chars_around() {
+        let mut pieces = PieceTable::with_text("hé\nwo".to_owned());
+        pieces.insert(4, "ça ".to_owned());
+        pieces.append("rld".to_owned());
+        assert_eq!(pieces.text(), "hé\nça world");
+        let forward = pieces.chars_from(1).collect::<Vec<_>>();
+        assert_eq!(forward[..3], [(1, 'é'), (3, '\n'), (4, 'ç')]);
+        assert_eq!(forward.last(), Some(&(12, 'd')));
+        assert_eq!(forward.len(), 10);
+        let backward = pieces.chars_before(6).collect::<Vec<_>>();
+        assert_eq!(backward, vec![(4, 'ç'), (3, '\n'), (1, 'é'), (0, 'h')]);
+        assert_eq!(pieces.chars_from(13).next(), None);
+        assert_eq!(pieces.chars_before(0).next(), None);
+    }
+
+    #[test]
+    fn empty_bulk() {
+        let mut pieces = PieceTable::with_text("text\n".to_owned());
+        pieces.start_bulk();
+        pieces.insert(0, String::new());
+        pieces.end_bulk();
+        assert_eq!(pieces.revision(), 0);
+        assert_eq!(pieces.revisions().len(), 1);
+        assert!(!pieces.undo());
+    }
+
+    #[test]
+    fn undo_branches() {
+        let mut pieces = PieceTable::with_text("text\n".to_owned());
+        pieces.insert(0, "a ".to_owned());
+        assert!(pieces.undo());
+        pieces.insert(0, "the ".to_owned());
+        assert_eq!(pieces.revision(), 2);
+        assert!(!pieces.redo());
+
+        assert!(pieces.goto_revision(1));
+        assert_eq!(pieces.text(), "a text\n");
+        assert_eq!(pieces.revision_text(2).unwrap(), "the text\n");
+        assert_eq!(pieces.parent_revision(2), Some(0));
+        assert!(pieces.earlier(Duration::from_secs(3600)));
+        assert_eq!(pieces.text(), "text\n");
+        assert!(pieces.later(Duration::from_secs(3600)));
+        assert_eq!(pieces.text(), "the text\n");
+    }
+
     #[test]
     fn newline_caching() {
         let mut pieces = PieceTable::new();
@@ -936,6 +1482,15 @@ doit servir de tombeau"#;
         assert_eq!(pieces.coord_to_offset((3, 14).into()), None);
     }
 
+    #[test]
+    fn coordinates_unicode_columns() {
+        let pieces = PieceTable::with_text("é🦊 fox\n❓ why".to_owned());
+        assert_eq!(pieces.offset_to_coord(2), Some((1, 2).into())); // 🦊 offset
+        assert_eq!(pieces.offset_to_coord(6), Some((1, 3).into())); // after 🦊
+        assert_eq!(pieces.offset_to_coord(10), Some((1, 7).into())); // newline
+        assert_eq!(pieces.offset_to_coord(14), Some((2, 2).into())); // after ❓
+    }
+
     #[test]
     fn char_at() {
         let text = r#"Natoque.
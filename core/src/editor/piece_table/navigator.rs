@@ -5,6 +5,41 @@ use bstr::ByteSlice;
 use crate::editor::piece_table::{Coords, PieceTable, Position};
 
 const BEGIN: Coords = Coords { l: 1, c: 1 };
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Tells which characters make words.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WordChars {
+    /// Alphanumeric characters and the given ones, the other non-blank characters making
+    /// words of their own.
+    Class(String),
+    /// Any non-blank character.
+    Big,
+}
+
+impl Default for WordChars {
+    fn default() -> Self {
+        WordChars::Class("_".to_owned())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum CharKind {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+impl WordChars {
+    pub(super) fn kind(&self, c: char) -> CharKind {
+        match self {
+            _ if c.is_whitespace() => CharKind::Blank,
+            WordChars::Big => CharKind::Word,
+            WordChars::Class(extra) if c.is_alphanumeric() || extra.contains(c) => CharKind::Word,
+            WordChars::Class(_) => CharKind::Punctuation,
+        }
+    }
+}
 
 pub struct Navigator<'a> {
     pub table: &'a PieceTable,
@@ -58,7 +93,7 @@ impl Navigator<'_> {
     pub fn next(&mut self) -> &mut Self {
         if self.cursor == self.table.max_coord() {
             return self;
-        } else if self.table.char_at(self.cursor).map_or(false, |c| c == "\n") {
+        } else if self.table.char_at(self.cursor).is_some_and(|c| c == "\n") {
             self.cursor = Coords {
                 l: self.cursor.l + 1,
                 c: 1,
@@ -66,7 +101,8 @@ impl Navigator<'_> {
             self.target_col = 1;
         } else {
             let offset = self.table.coord_to_offset(self.cursor).unwrap();
-            if let Some(coord) = self.table.offset_to_coord(offset + 1) {
+            let len = self.table.char_at_offset(offset).map_or(1, |g| g.len());
+            if let Some(coord) = self.table.offset_to_coord(offset + len) {
                 self.cursor = coord;
                 self.target_col = coord.c;
             }
@@ -125,6 +161,173 @@ impl Navigator<'_> {
         self.target_col = self.cursor.c;
         self
     }
+
+    fn goto_offset(&mut self, offset: usize) -> &mut Self {
+        if let Some(coords) = self.table.offset_to_coord(offset) {
+            self.cursor = coords;
+            self.target_col = coords.c;
+        }
+        self
+    }
+
+    /// Moves to the beginning of the next word, or to the end if there is none.
+    pub fn next_word_start(&mut self, words: &WordChars) -> &mut Self {
+        let offset = self.pos().offset;
+        let mut chars = self.table.chars_from(offset);
+        let first = match chars.next() {
+            Some((_, c)) => words.kind(c),
+            None => return self,
+        };
+        let target = chars
+            .map(|(i, c)| (i, words.kind(c)))
+            .skip_while(|&(_, kind)| kind == first)
+            .find(|&(_, kind)| kind != CharKind::Blank)
+            .map_or(self.table.max_offset(), |(i, _)| i);
+        self.goto_offset(target)
+    }
+
+    /// Moves to the end of the word, or of the next one when already there.
+    pub fn next_word_end(&mut self, words: &WordChars) -> &mut Self {
+        let offset = self.pos().offset;
+        let mut chars = self
+            .table
+            .chars_from(offset)
+            .skip(1)
+            .map(|(i, c)| (i, words.kind(c)))
+            .skip_while(|&(_, kind)| kind == CharKind::Blank)
+            .peekable();
+        let (mut target, kind) = match chars.peek() {
+            Some(&first) => first,
+            None => return self,
+        };
+        for (i, k) in chars {
+            if k != kind {
+                break;
+            }
+            target = i;
+        }
+        self.goto_offset(target)
+    }
+
+    /// Moves to the beginning of the word, or of the previous one when already there.
+    pub fn previous_word_start(&mut self, words: &WordChars) -> &mut Self {
+        let offset = self.pos().offset;
+        let mut chars = self
+            .table
+            .chars_before(offset)
+            .map(|(i, c)| (i, words.kind(c)))
+            .skip_while(|&(_, kind)| kind == CharKind::Blank)
+            .peekable();
+        let (mut target, kind) = match chars.peek() {
+            Some(&first) => first,
+            None => return self.begin(),
+        };
+        for (i, k) in chars {
+            if k != kind {
+                break;
+            }
+            target = i;
+        }
+        self.goto_offset(target)
+    }
+
+    pub(super) fn is_blank_line(&self, l: usize) -> bool {
+        match self.table.line_bytes(l) {
+            Some(bytes) => bytes.iter().all(u8::is_ascii_whitespace),
+            None => true,
+        }
+    }
+
+    /// Moves to the blank line after the paragraph, or to the end if there is none.
+    pub fn next_paragraph(&mut self) -> &mut Self {
+        let line_count = self.table.line_count();
+        let mut l = self.cursor.l;
+        while l <= line_count && self.is_blank_line(l) {
+            l += 1;
+        }
+        while l <= line_count && !self.is_blank_line(l) {
+            l += 1;
+        }
+        if l > line_count {
+            return self.end();
+        }
+        self.cursor = Coords { l, c: 1 };
+        self.target_col = 1;
+        self
+    }
+
+    /// Moves to the blank line before the paragraph, or to the beginning if there is none.
+    pub fn previous_paragraph(&mut self) -> &mut Self {
+        let mut l = self.cursor.l;
+        while l >= 1 && self.is_blank_line(l) {
+            l -= 1;
+        }
+        while l >= 1 && !self.is_blank_line(l) {
+            l -= 1;
+        }
+        if l < 1 {
+            return self.begin();
+        }
+        self.cursor = Coords { l, c: 1 };
+        self.target_col = 1;
+        self
+    }
+
+    /// Moves to the bracket matching the one under the cursor, or the next one on the line.
+    /// Stays put if there is no such bracket or it is unbalanced.
+    pub fn matching_bracket(&mut self) -> &mut Self {
+        let offset = self.pos().offset;
+        let found = self
+            .table
+            .chars_from(offset)
+            .take_while(|&(_, c)| c != '\n')
+            .find_map(|(i, c)| {
+                BRACKETS
+                    .iter()
+                    .find(|&&(open, close)| c == open || c == close)
+                    .map(|&pair| (i, c, pair))
+            });
+        let (start, bracket, (open, close)) = match found {
+            Some(found) => found,
+            None => return self,
+        };
+
+        let mut depth = 0;
+        let step = |depth: &mut usize, c: char, inner: char, outer: char| {
+            if c == inner {
+                *depth += 1;
+            } else if c == outer {
+                *depth -= 1;
+            }
+            *depth == 0
+        };
+        let target = if bracket == open {
+            self.table
+                .chars_from(start)
+                .find(|&(_, c)| step(&mut depth, c, open, close))
+        } else {
+            self.table
+                .chars_before(start + bracket.len_utf8())
+                .find(|&(_, c)| step(&mut depth, c, close, open))
+        }
+        .map(|(i, _)| i);
+        match target {
+            Some(target) => self.goto_offset(target),
+            None => self,
+        }
+    }
+
+    /// Moves to the first non-blank character of the line, or to its end if it is blank.
+    pub fn first_non_blank(&mut self) -> &mut Self {
+        let line = self.table.line_bytes(self.cursor.l).unwrap_or_default();
+        let blanks = line
+            .graphemes()
+            .take_while(|g| g.chars().all(char::is_whitespace))
+            .count();
+        self.cursor.c = blanks + 1;
+        self.target_col = self.cursor.c;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -132,7 +335,7 @@ mod tests {
     use super::*;
 
     fn make_table() -> PieceTable {
-        let lines = vec![
+        let lines = [
             "Nam quis nulla.",                                           // 1 - 15
             "Integer malesuada. In in enim a arcu imperdiet malesuada.", // 2 - 57
             "Sed vel lectus. Donec odio",                                // 3 - 26
@@ -169,6 +372,20 @@ mod tests {
         assert_eq!(nv.pos().coords, (2, 1).into());
     }
 
+    #[test]
+    fn char_unicode() {
+        let table = PieceTable::with_text("é🦊a\nb".to_owned());
+
+        let mut nv = table.navigate(None).unwrap();
+        nv.next();
+        assert_eq!(nv.pos().coords, (1, 2).into());
+        assert_eq!(nv.pos().grapheme, Some("🦊".into()));
+        nv.next();
+        assert_eq!(nv.pos().coords, (1, 3).into());
+        nv.next().next();
+        assert_eq!(nv.pos().coords, (2, 1).into());
+    }
+
     #[test]
     fn line() {
         let table = make_table();
@@ -197,4 +414,126 @@ mod tests {
         nv.begin();
         assert_eq!(nv.pos().coords, (1, 1).into());
     }
+
+    fn offsets<F>(text: &str, from: usize, mut step: F) -> Vec<usize>
+    where
+        F: FnMut(&mut Navigator),
+    {
+        let table = PieceTable::with_text(text.to_owned());
+        let mut nv = table.navigate(table.offset_to_coord(from)).unwrap();
+        let mut offsets = Vec::new();
+        for _ in 0..4 {
+            step(&mut nv);
+            offsets.push(nv.pos().offset);
+        }
+        offsets
+    }
+
+    #[test]
+    fn words() {
+        let text = "foo_bar(baz), qux-1\n  é\n";
+        let words = WordChars::default();
+        assert_eq!(
+            offsets(text, 0, |nv| {
+                nv.next_word_start(&words);
+            }),
+            [7, 8, 11, 14]
+        );
+        assert_eq!(
+            offsets(text, 0, |nv| {
+                nv.next_word_end(&words);
+            }),
+            [6, 7, 10, 12]
+        );
+        assert_eq!(
+            offsets(text, 22, |nv| {
+                nv.previous_word_start(&words);
+            }),
+            [18, 17, 14, 11]
+        );
+
+        let dashed = WordChars::Class("_-".to_owned());
+        assert_eq!(
+            offsets(text, 14, |nv| {
+                nv.next_word_start(&dashed);
+            }),
+            [22, 24, 24, 24]
+        );
+        assert_eq!(
+            offsets(text, 0, |nv| {
+                nv.next_word_start(&WordChars::Big);
+            }),
+            [14, 22, 24, 24]
+        );
+        assert_eq!(
+            offsets(text, 0, |nv| {
+                nv.next_word_end(&WordChars::Big);
+            }),
+            [12, 18, 22, 22]
+        );
+        assert_eq!(
+            offsets(text, 22, |nv| {
+                nv.previous_word_start(&WordChars::Big);
+            }),
+            [14, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn paragraphs() {
+        let text = "a\nb\n\n\nc\n \nd";
+        assert_eq!(
+            offsets(text, 0, |nv| {
+                nv.next_paragraph();
+            }),
+            [4, 8, 10, 10]
+        );
+        assert_eq!(
+            offsets(text, 10, |nv| {
+                nv.previous_paragraph();
+            }),
+            [8, 5, 0, 0]
+        );
+    }
+
+    #[test]
+    fn brackets() {
+        let text = "if (a[0] == (b)) {\n}";
+        assert_eq!(
+            offsets(text, 0, |nv| {
+                nv.matching_bracket();
+            }),
+            [15, 3, 15, 3]
+        );
+        assert_eq!(
+            offsets(text, 5, |nv| {
+                nv.matching_bracket();
+            }),
+            [7, 5, 7, 5]
+        );
+        assert_eq!(
+            offsets(text, 16, |nv| {
+                nv.matching_bracket();
+            }),
+            [19, 17, 19, 17]
+        );
+        assert_eq!(
+            offsets("(()\n", 0, |nv| {
+                nv.matching_bracket();
+            }),
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn first_non_blank() {
+        let table = PieceTable::with_text("a\n\t  b\n   \n".to_owned());
+        let mut nv = table.navigate(Coords { l: 2, c: 4 }).unwrap();
+        nv.first_non_blank();
+        assert_eq!(nv.pos().coords, (2, 4).into());
+        nv.line_end().first_non_blank();
+        assert_eq!(nv.pos().coords, (2, 4).into());
+        nv.next_line().first_non_blank();
+        assert_eq!(nv.pos().coords, (3, 4).into());
+    }
 }
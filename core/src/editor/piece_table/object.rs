@@ -0,0 +1,348 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::editor::piece_table::navigator::CharKind;
+use crate::editor::piece_table::{Coords, Navigator, WordChars};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextObject {
+    Parentheses,
+    Brackets,
+    Braces,
+    AngleBrackets,
+    DoubleQuotes,
+    SingleQuotes,
+    Backquotes,
+    Word,
+    BigWord,
+    Paragraph,
+    Indent,
+}
+
+impl TextObject {
+    pub const ALL: [TextObject; 11] = [
+        TextObject::Parentheses,
+        TextObject::Brackets,
+        TextObject::Braces,
+        TextObject::AngleBrackets,
+        TextObject::DoubleQuotes,
+        TextObject::SingleQuotes,
+        TextObject::Backquotes,
+        TextObject::Word,
+        TextObject::BigWord,
+        TextObject::Paragraph,
+        TextObject::Indent,
+    ];
+
+    fn name(self) -> &'static str {
+        use TextObject::*;
+        match self {
+            Parentheses => "parentheses",
+            Brackets => "brackets",
+            Braces => "braces",
+            AngleBrackets => "angle_brackets",
+            DoubleQuotes => "double_quotes",
+            SingleQuotes => "single_quotes",
+            Backquotes => "backquotes",
+            Word => "word",
+            BigWord => "big_word",
+            Paragraph => "paragraph",
+            Indent => "indent",
+        }
+    }
+}
+
+impl fmt::Display for TextObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TextObject {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TextObject::ALL
+            .iter()
+            .find(|o| o.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown text object: {}", s))
+    }
+}
+
+impl Navigator<'_> {
+    /// Finds the object around the cursor, returning the offsets of its first and last
+    /// characters. Inner objects leave out delimiters and surrounding blanks.
+    pub fn object(
+        &self,
+        object: TextObject,
+        inner: bool,
+        words: &WordChars,
+    ) -> Option<RangeInclusive<usize>> {
+        use TextObject::*;
+        match object {
+            Parentheses => self.delimited('(', ')', inner),
+            Brackets => self.delimited('[', ']', inner),
+            Braces => self.delimited('{', '}', inner),
+            AngleBrackets => self.delimited('<', '>', inner),
+            DoubleQuotes => self.quoted('"', inner),
+            SingleQuotes => self.quoted('\'', inner),
+            Backquotes => self.quoted('`', inner),
+            Word => self.word(words, inner),
+            BigWord => self.word(&WordChars::Big, inner),
+            Paragraph => self.paragraph(inner),
+            Indent => self.indent(inner),
+        }
+    }
+
+    fn delimited(&self, open: char, close: char, inner: bool) -> Option<RangeInclusive<usize>> {
+        let offset = self.pos().offset;
+        let cursor_end = offset + self.table.chars_from(offset).next()?.1.len_utf8();
+
+        let mut depth = 0;
+        let start = self
+            .table
+            .chars_before(cursor_end)
+            .find(|&(i, c)| {
+                if c == close && i != offset {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                }
+                false
+            })?
+            .0;
+        let mut depth = 0;
+        let end = self
+            .table
+            .chars_from(start + 1)
+            .find(|&(_, c)| {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                }
+                false
+            })?
+            .0;
+        Self::strip(start, end, inner)
+    }
+
+    fn quoted(&self, quote: char, inner: bool) -> Option<RangeInclusive<usize>> {
+        let pos = self.pos();
+        let line = self.table.line_text(pos.coords.l)?;
+        let line_start = self.table.coord_to_offset(Coords {
+            l: pos.coords.l,
+            c: 1,
+        })?;
+        let offset = pos.offset;
+        let quotes = line
+            .char_indices()
+            .filter(|&(i, c)| c == quote && !line[..i].ends_with('\\'))
+            .map(|(i, _)| line_start + i)
+            .collect::<Vec<_>>();
+        let (start, end) = quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|&(start, end)| start <= offset && offset <= end)?;
+        Self::strip(start, end, inner)
+    }
+
+    // the delimiters are single byte characters
+    fn strip(start: usize, end: usize, inner: bool) -> Option<RangeInclusive<usize>> {
+        if !inner {
+            Some(start..=end)
+        } else if end > start + 1 {
+            Some(start + 1..=end - 1)
+        } else {
+            None
+        }
+    }
+
+    fn word(&self, words: &WordChars, inner: bool) -> Option<RangeInclusive<usize>> {
+        let offset = self.pos().offset;
+        let (_, c) = self
+            .table
+            .chars_from(offset)
+            .next()
+            .filter(|&(_, c)| c != '\n')?;
+        let kind = words.kind(c);
+        let same =
+            |kind: CharKind| move |&(_, c): &(usize, char)| c != '\n' && words.kind(c) == kind;
+
+        let mut start = self
+            .table
+            .chars_before(offset)
+            .take_while(same(kind))
+            .last()
+            .map_or(offset, |(i, _)| i);
+        let mut last = self
+            .table
+            .chars_from(offset)
+            .take_while(same(kind))
+            .last()
+            .map_or(offset, |(i, _)| i);
+        if !inner && kind != CharKind::Blank {
+            let trailing = self
+                .table
+                .chars_from(last)
+                .skip(1)
+                .take_while(same(CharKind::Blank))
+                .last();
+            match trailing {
+                Some((i, _)) => last = i,
+                None => {
+                    start = self
+                        .table
+                        .chars_before(start)
+                        .take_while(same(CharKind::Blank))
+                        .last()
+                        .map_or(start, |(i, _)| i)
+                }
+            }
+        }
+        Some(start..=last)
+    }
+
+    /// Offsets of the first character of line `first` and of the line break ending `last`.
+    fn lines_span(&self, first: usize, last: usize) -> Option<RangeInclusive<usize>> {
+        let start = self.table.coord_to_offset(Coords { l: first, c: 1 })?;
+        let end = if last < self.table.line_count() {
+            self.table.coord_to_offset(Coords { l: last + 1, c: 1 })? - 1
+        } else {
+            self.table.max_offset()
+        };
+        Some(start..=end)
+    }
+
+    fn paragraph(&self, inner: bool) -> Option<RangeInclusive<usize>> {
+        let line_count = self.table.line_count();
+        let l = self.pos().coords.l;
+        let blank = self.is_blank_line(l);
+        let mut first = l;
+        while first > 1 && self.is_blank_line(first - 1) == blank {
+            first -= 1;
+        }
+        let mut last = l;
+        while last < line_count && self.is_blank_line(last + 1) == blank {
+            last += 1;
+        }
+        if !inner && !blank {
+            while last < line_count && self.is_blank_line(last + 1) {
+                last += 1;
+            }
+        }
+        self.lines_span(first, last)
+    }
+
+    fn indentation(&self, l: usize) -> Option<usize> {
+        let line = self.table.line_bytes(l)?;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            None
+        } else {
+            Some(
+                line.iter()
+                    .take_while(|b| **b == b' ' || **b == b'\t')
+                    .count(),
+            )
+        }
+    }
+
+    fn indent(&self, inner: bool) -> Option<RangeInclusive<usize>> {
+        let line_count = self.table.line_count();
+        let l = self.pos().coords.l;
+        let level = self.indentation(l)?;
+        let within = |l: usize| match self.indentation(l) {
+            Some(i) => i >= level,
+            None => true,
+        };
+        let mut first = l;
+        while first > 1 && within(first - 1) {
+            first -= 1;
+        }
+        let mut last = l;
+        while last < line_count && within(last + 1) {
+            last += 1;
+        }
+        if inner {
+            while self.indentation(first).is_none() {
+                first += 1;
+            }
+            while self.indentation(last).is_none() {
+                last -= 1;
+            }
+        }
+        self.lines_span(first, last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::piece_table::PieceTable;
+
+    fn select(text: &str, offset: usize, object: TextObject, inner: bool) -> Option<String> {
+        let table = PieceTable::with_text(text.to_owned());
+        let nv = table.navigate(table.offset_to_coord(offset)).unwrap();
+        nv.object(object, inner, &WordChars::default())
+            .map(|range| text[range].to_owned())
+    }
+
+    #[test]
+    fn delimiters() {
+        let text = "f(a, (b), [c]) \"d\\\"e\" 'f'\n";
+        let sel = |offset, object, inner| select(text, offset, object, inner);
+        assert_eq!(
+            sel(2, TextObject::Parentheses, true).unwrap(),
+            "a, (b), [c]"
+        );
+        assert_eq!(
+            sel(1, TextObject::Parentheses, false).unwrap(),
+            "(a, (b), [c])"
+        );
+        assert_eq!(
+            sel(13, TextObject::Parentheses, false).unwrap(),
+            "(a, (b), [c])"
+        );
+        assert_eq!(sel(6, TextObject::Parentheses, true).unwrap(), "b");
+        assert_eq!(sel(11, TextObject::Brackets, false).unwrap(), "[c]");
+        assert_eq!(sel(2, TextObject::Brackets, false), None);
+        assert_eq!(sel(17, TextObject::DoubleQuotes, true).unwrap(), "d\\\"e");
+        assert_eq!(sel(24, TextObject::SingleQuotes, false).unwrap(), "'f'");
+        assert_eq!(sel(14, TextObject::SingleQuotes, false), None);
+        assert_eq!(select("()\n", 0, TextObject::Parentheses, true), None);
+    }
+
+    #[test]
+    fn words() {
+        let text = "let foo_bar = 1;\n";
+        let sel = |offset, object, inner| select(text, offset, object, inner).unwrap();
+        assert_eq!(sel(6, TextObject::Word, true), "foo_bar");
+        assert_eq!(sel(6, TextObject::Word, false), "foo_bar ");
+        assert_eq!(sel(14, TextObject::Word, false), " 1");
+        assert_eq!(sel(15, TextObject::Word, false), ";");
+        assert_eq!(sel(12, TextObject::BigWord, true), "=");
+        assert_eq!(sel(3, TextObject::Word, true), " ");
+        assert_eq!(select(text, 16, TextObject::Word, true), None);
+    }
+
+    #[test]
+    fn lines() {
+        let text = "a\nb\n\n\nfn f() {\n    x\n\n    y\n}\n";
+        let sel = |offset, object, inner| select(text, offset, object, inner).unwrap();
+        assert_eq!(sel(2, TextObject::Paragraph, true), "a\nb\n");
+        assert_eq!(sel(0, TextObject::Paragraph, false), "a\nb\n\n\n");
+        assert_eq!(sel(4, TextObject::Paragraph, true), "\n\n");
+        assert_eq!(sel(20, TextObject::Indent, true), "    x\n\n    y\n");
+        assert_eq!(sel(20, TextObject::Indent, false), "    x\n\n    y\n");
+        assert_eq!(sel(0, TextObject::Indent, true), text);
+        assert_eq!(select(text, 4, TextObject::Indent, true), None);
+    }
+}
@@ -7,6 +7,7 @@ pub struct Position {
     pub grapheme: Option<String>,
 }
 
+#[allow(dead_code)]
 pub struct PositionIterator<'a> {
     table: &'a PieceTable,
     nv: Navigator<'a>,
@@ -15,6 +16,7 @@ pub struct PositionIterator<'a> {
 }
 
 impl PositionIterator<'_> {
+    #[allow(dead_code)]
     fn pos(&self) -> Position {
         Position {
             offset: self.offset,
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+
+use crate::editor::core::Error;
+
+/// Register used when none is given.
+pub const REGISTER_DEFAULT: char = '"';
+/// Register bridged to the system clipboard once a clipboard is configured.
+pub const REGISTER_CLIPBOARD: char = '+';
+/// Register macros are recorded in when none is given.
+pub const REGISTER_MACRO: char = '@';
+/// Register discarding what is written to it.
+pub const REGISTER_BLACK_HOLE: char = '_';
+
+/// Shell commands copying their input to the system clipboard and printing its content,
+/// e.g. `wl-copy` and `wl-paste -n`.
+#[derive(Clone, Debug)]
+pub struct Clipboard {
+    pub copy: String,
+    pub paste: String,
+}
+
+impl Clipboard {
+    pub fn copy(&self, text: &str) -> Result<(), Error> {
+        let run = || -> io::Result<()> {
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(&self.copy)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes())?;
+            }
+            check_status(child.wait()?)
+        };
+        run().map_err(|error| Error::Clipboard {
+            command: self.copy.clone(),
+            error,
+        })
+    }
+
+    pub fn paste(&self) -> Result<String, Error> {
+        let run = || -> io::Result<String> {
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(&self.paste)
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .output()?;
+            check_status(output.status)?;
+            String::from_utf8(output.stdout)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        run().map_err(|error| Error::Clipboard {
+            command: self.paste.clone(),
+            error,
+        })
+    }
+}
+
+fn check_status(status: ExitStatus) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("exited with {}", status)))
+    }
+}
+
+/// Named registers, each one holding a value per selection, or a key per value for macros.
+#[derive(Debug, Default)]
+pub struct Registers {
+    values: HashMap<char, Vec<String>>,
+    // registers holding a recorded macro
+    macros: HashSet<char>,
+    pub clipboard: Option<Clipboard>,
+}
+
+impl Registers {
+    pub fn get(&self, name: char) -> Result<Vec<String>, Error> {
+        match (name, &self.clipboard) {
+            (REGISTER_BLACK_HOLE, _) => Ok(Vec::new()),
+            (REGISTER_CLIPBOARD, Some(clipboard)) => Ok(vec![clipboard.paste()?]),
+            _ => Ok(self.values.get(&name).cloned().unwrap_or_default()),
+        }
+    }
+
+    /// Stores `values` in the register, the clipboard getting them all concatenated.
+    pub fn set(&mut self, name: char, values: Vec<String>) -> Result<(), Error> {
+        match (name, &self.clipboard) {
+            (REGISTER_BLACK_HOLE, _) => Ok(()),
+            (REGISTER_CLIPBOARD, Some(clipboard)) => clipboard.copy(&values.concat()),
+            _ => {
+                self.values.insert(name, values);
+                self.macros.remove(&name);
+                Ok(())
+            }
+        }
+    }
+
+    /// The clipboard the register is bridged to, if it is the clipboard register and one is
+    /// configured.
+    pub fn clipboard_of(&self, name: char) -> Option<Clipboard> {
+        self.clipboard
+            .clone()
+            .filter(|_| name == REGISTER_CLIPBOARD)
+    }
+
+    /// Stores the keys of a macro, always in a plain register.
+    pub fn set_macro(&mut self, name: char, keys: Vec<String>) {
+        if name != REGISTER_BLACK_HOLE {
+            self.values.insert(name, keys);
+            self.macros.insert(name);
+        }
+    }
+
+    /// The registers holding a macro, with its keys.
+    pub fn macros(&self) -> HashMap<char, Vec<String>> {
+        self.macros
+            .iter()
+            .filter_map(|name| Some((*name, self.values.get(name)?.clone())))
+            .collect()
+    }
+}
+
+/// Parses the name of a register, a single character, `default` if there is none.
+pub fn register_name(name: Option<&str>, default: char) -> Result<char, Error> {
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(default),
+    };
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(Error::InvalidRegister {
+            name: name.to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_registers() {
+        let mut registers = Registers::default();
+        assert!(registers.get('a').unwrap().is_empty());
+        registers
+            .set('a', vec!["x".to_owned(), "y".to_owned()])
+            .unwrap();
+        assert_eq!(registers.get('a').unwrap(), vec!["x", "y"]);
+        registers
+            .set(REGISTER_BLACK_HOLE, vec!["z".to_owned()])
+            .unwrap();
+        assert!(registers.get(REGISTER_BLACK_HOLE).unwrap().is_empty());
+
+        registers.set_macro('a', vec!["i".to_owned(), "esc".to_owned()]);
+        registers.set_macro('b', vec!["x".to_owned()]);
+        registers.set('b', vec!["y".to_owned()]).unwrap();
+        assert_eq!(
+            registers.macros(),
+            vec![('a', vec!["i".to_owned(), "esc".to_owned()])]
+                .into_iter()
+                .collect()
+        );
+
+        assert_eq!(
+            register_name(None, REGISTER_DEFAULT).unwrap(),
+            REGISTER_DEFAULT
+        );
+        assert_eq!(register_name(Some("é"), REGISTER_DEFAULT).unwrap(), 'é');
+        assert!(register_name(Some("ab"), REGISTER_DEFAULT).is_err());
+        assert!(register_name(Some(""), REGISTER_DEFAULT).is_err());
+    }
+
+    #[test]
+    fn clipboard() {
+        let file = std::env::temp_dir().join(format!("ced-test-{}-clipboard", std::process::id()));
+        let mut registers = Registers::default();
+        registers
+            .set(REGISTER_CLIPBOARD, vec!["kept".to_owned()])
+            .unwrap();
+        assert_eq!(registers.get(REGISTER_CLIPBOARD).unwrap(), vec!["kept"]);
+
+        registers.clipboard = Some(Clipboard {
+            copy: format!("cat > {}", file.display()),
+            paste: format!("cat {}", file.display()),
+        });
+        registers
+            .set(REGISTER_CLIPBOARD, vec!["a\n".to_owned(), "b\n".to_owned()])
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a\nb\n");
+        assert_eq!(registers.get(REGISTER_CLIPBOARD).unwrap(), vec!["a\nb\n"]);
+        std::fs::remove_file(&file).unwrap();
+
+        let error = registers.get(REGISTER_CLIPBOARD).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("clipboard command failed: cat "));
+    }
+}
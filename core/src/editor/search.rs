@@ -0,0 +1,140 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless the pattern has an uppercase letter.
+    #[default]
+    Smart,
+}
+
+impl fmt::Display for CaseMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CaseMode::*;
+        match self {
+            Sensitive => write!(f, "sensitive"),
+            Insensitive => write!(f, "insensitive"),
+            Smart => write!(f, "smart"),
+        }
+    }
+}
+
+impl FromStr for CaseMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sensitive" => Ok(CaseMode::Sensitive),
+            "insensitive" => Ok(CaseMode::Insensitive),
+            "smart" => Ok(CaseMode::Smart),
+            _ => Err(format!("unknown case mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+pub fn build_regex(pattern: &str, case: CaseMode) -> Result<Regex, regex::Error> {
+    let insensitive = match case {
+        CaseMode::Sensitive => false,
+        CaseMode::Insensitive => true,
+        CaseMode::Smart => !pattern.chars().any(char::is_uppercase),
+    };
+    RegexBuilder::new(pattern)
+        .case_insensitive(insensitive)
+        .multi_line(true)
+        .build()
+}
+
+/// Non-empty matches of `regex` in `text`, empty ones leading nowhere.
+pub fn find_all(regex: &Regex, text: &str) -> Vec<Range<usize>> {
+    regex
+        .find_iter(text)
+        .filter(|m| !m.as_str().is_empty())
+        .map(|m| m.range())
+        .collect()
+}
+
+/// Finds the match after or before `offset`, wrapping around the text if there is none in
+/// that direction. Also returns whether it wrapped.
+pub fn find_next(
+    regex: &Regex,
+    text: &str,
+    offset: usize,
+    direction: Direction,
+) -> Option<(Range<usize>, bool)> {
+    let matches = find_all(regex, text);
+    let found = match direction {
+        Direction::Forward => matches.iter().find(|m| m.start > offset),
+        Direction::Backward => matches.iter().rev().find(|m| m.start < offset),
+    };
+    match (found, direction) {
+        (Some(m), _) => Some((m.clone(), false)),
+        (None, Direction::Forward) => matches.first().map(|m| (m.clone(), true)),
+        (None, Direction::Backward) => matches.last().map(|m| (m.clone(), true)),
+    }
+}
+
+/// Offset of the last character of the non-empty `range` of `text`, selections including
+/// the character under their cursor.
+pub fn last_char_offset(text: &str, range: &Range<usize>) -> usize {
+    text[range.clone()]
+        .char_indices()
+        .last()
+        .map_or(range.start, |(i, _)| range.start + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_modes() {
+        let text = "Foo foo FOO";
+        let count = |pattern, case| find_all(&build_regex(pattern, case).unwrap(), text).len();
+        assert_eq!(count("foo", CaseMode::Sensitive), 1);
+        assert_eq!(count("foo", CaseMode::Insensitive), 3);
+        assert_eq!(count("foo", CaseMode::Smart), 3);
+        assert_eq!(count("Foo", CaseMode::Smart), 1);
+        assert_eq!("SMART".parse(), Ok(CaseMode::Smart));
+        assert!("other".parse::<CaseMode>().is_err());
+    }
+
+    #[test]
+    fn next_match() {
+        let regex = build_regex("a+", CaseMode::Sensitive).unwrap();
+        let text = "a b aa b a";
+        assert_eq!(
+            find_next(&regex, text, 0, Direction::Forward),
+            Some((4..6, false))
+        );
+        assert_eq!(
+            find_next(&regex, text, 4, Direction::Forward),
+            Some((9..10, false))
+        );
+        assert_eq!(
+            find_next(&regex, text, 9, Direction::Forward),
+            Some((0..1, true))
+        );
+        assert_eq!(
+            find_next(&regex, text, 9, Direction::Backward),
+            Some((4..6, false))
+        );
+        assert_eq!(
+            find_next(&regex, text, 0, Direction::Backward),
+            Some((9..10, true))
+        );
+
+        let empty = build_regex("x*", CaseMode::Sensitive).unwrap();
+        assert_eq!(find_next(&empty, text, 0, Direction::Forward), None);
+    }
+}
@@ -1,3 +1,6 @@
+use crate::editor::piece_table::Edit;
+use crate::editor::range::Range;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Selection {
     pub anchor: usize,
@@ -36,19 +39,107 @@ impl Selection {
         }
     }
 
+    /// Remaps the selection after the `removed` bytes at `offset` were replaced by `inserted`
+    /// bytes. Positions inside the removed part collapse onto `offset`.
+    pub fn shift(&mut self, offset: usize, removed: usize, inserted: usize) {
+        let edit = Edit {
+            offset,
+            removed,
+            inserted,
+        };
+        self.anchor = edit.remap(self.anchor);
+        self.cursor = edit.remap(self.cursor);
+    }
+
+    /// Keeps both ends of the selection within offset `n`, returning by how much its end moved.
     pub fn clamp_to(&mut self, n: usize) -> usize {
-        if n <= self.end() {
-            let delta;
-            if self.anchor < self.cursor {
-                delta = self.cursor - n;
-                self.cursor = n;
-            } else {
-                delta = self.anchor - n;
-                self.anchor = n;
+        let delta = self.end().saturating_sub(n);
+        self.anchor = self.anchor.min(n);
+        self.cursor = self.cursor.min(n);
+        delta
+    }
+}
+
+/// Merges the overlapping selections, `range` giving the bytes each one covers. The result
+/// is in buffer order, starting from the selection holding the main one, which is the first
+/// and whose direction wins.
+pub fn merge_overlapping<F>(sels: &mut Vec<Selection>, range: F)
+where
+    F: Fn(&Selection) -> std::ops::Range<usize>,
+{
+    let main = match sels.first() {
+        Some(s) => *s,
+        None => return,
+    };
+    let mut sorted = sels.clone();
+    sorted.sort_by_key(|s| (s.begin(), s.end()));
+    let mut merged: Vec<(Selection, std::ops::Range<usize>)> = Vec::new();
+    let mut main_index = 0;
+    for s in sorted {
+        let r = range(&s);
+        match merged.last_mut() {
+            Some((last, last_range)) if last_range.overlap(&r) => {
+                let (begin, end) = (last.begin(), last.end().max(s.end()));
+                let forward = if s == main {
+                    s.anchor <= s.cursor
+                } else {
+                    last.anchor <= last.cursor
+                };
+                if forward {
+                    last.anchor = begin;
+                    last.cursor = end;
+                } else {
+                    last.anchor = end;
+                    last.cursor = begin;
+                }
+                last_range.end = last_range.end.max(r.end);
             }
-            delta
-        } else {
-            0
+            _ => merged.push((s, r)),
+        }
+        if s == main {
+            main_index = merged.len() - 1;
+        }
+    }
+    merged.rotate_left(main_index);
+    *sels = merged.into_iter().map(|(s, _)| s).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sel(anchor: usize, cursor: usize) -> Selection {
+        Selection {
+            anchor,
+            cursor,
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn merge() {
+        let range = |s: &Selection| s.begin()..s.end() + 1;
+        let mut sels = vec![sel(6, 4), sel(0, 2), sel(3, 5), sel(8, 8), sel(7, 7)];
+        merge_overlapping(&mut sels, range);
+        assert_eq!(sels, vec![sel(6, 3), sel(7, 7), sel(8, 8), sel(0, 2)]);
+
+        let mut sels = vec![sel(0, 0), sel(0, 0)];
+        merge_overlapping(&mut sels, range);
+        assert_eq!(sels, vec![sel(0, 0)]);
+    }
+
+    #[test]
+    fn shift() {
+        let mut s = sel(4, 8);
+        s.shift(10, 0, 3);
+        assert_eq!(s, sel(4, 8));
+        s.shift(8, 0, 3);
+        assert_eq!(s, sel(4, 11));
+        s.shift(0, 0, 2);
+        assert_eq!(s, sel(6, 13));
+        s.shift(5, 4, 0);
+        assert_eq!(s, sel(5, 9));
+        s.shift(2, 10, 1);
+        assert_eq!(s, sel(2, 2));
+    }
 }
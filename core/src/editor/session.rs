@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::editor::selection::Selection;
+use crate::editor::view::Lens;
+
+/// Where the session saved under `name` goes, kept apart from the temporary files of the
+/// running sessions so that it outlives a reboot.
+pub fn saved_path(name: &str) -> PathBuf {
+    let dir = dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ced")
+        .join("sessions");
+    dir.join(format!("{}.json", name.replace(['/', '\\', ':'], "%")))
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub enum BufferSnapshot {
+    File { name: String, path: PathBuf },
+    Scratch { name: String, content: String },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct SelectionSnapshot {
+    pub anchor: usize,
+    pub cursor: usize,
+}
+
+impl From<&Selection> for SelectionSnapshot {
+    fn from(s: &Selection) -> Self {
+        SelectionSnapshot {
+            anchor: s.anchor,
+            cursor: s.cursor,
+        }
+    }
+}
+
+impl From<&SelectionSnapshot> for Selection {
+    fn from(s: &SelectionSnapshot) -> Self {
+        Selection {
+            anchor: s.anchor,
+            cursor: s.cursor,
+            ..Selection::new()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ViewSnapshot {
+    pub lenses: Vec<Lens>,
+    pub selections: HashMap<String, Vec<SelectionSnapshot>>,
+}
+
+/// What is needed to bring back a session: its buffers and views, from the oldest to the
+/// latest, the last one being shown, and the recorded macros. The selections are the ones of a
+/// single client. Unsaved changes are left to the journals.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct SessionSnapshot {
+    pub cwd: PathBuf,
+    pub buffers: Vec<BufferSnapshot>,
+    pub views: Vec<ViewSnapshot>,
+    #[serde(default)]
+    pub macros: HashMap<char, Vec<String>>,
+}
+
+impl SessionSnapshot {
+    pub fn read(path: &Path) -> io::Result<SessionSnapshot> {
+        let content = fs::read(path)?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// Writes the snapshot through a temporary file renamed over `path`, a failed write leaving
+    /// the previous snapshot whole.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        let written = fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)
+            .and_then(|_| fs::rename(&tmp_path, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::Focus;
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("ced-test-{}-session", std::process::id()));
+        let path = dir.join("saved.json");
+        let snapshot = SessionSnapshot {
+            cwd: PathBuf::from("/some/dir"),
+            buffers: vec![
+                BufferSnapshot::File {
+                    name: "file.txt".to_owned(),
+                    path: PathBuf::from("/some/dir/file.txt"),
+                },
+                BufferSnapshot::Scratch {
+                    name: "*notes*".to_owned(),
+                    content: "to do\n".to_owned(),
+                },
+            ],
+            views: vec![ViewSnapshot {
+                lenses: vec![
+                    Lens {
+                        buffer: "file.txt".to_owned(),
+                        focus: Focus::Range(2..5),
+                    },
+                    Lens {
+                        buffer: "*notes*".to_owned(),
+                        focus: Focus::Whole,
+                    },
+                ],
+                selections: vec![(
+                    "file.txt".to_owned(),
+                    vec![SelectionSnapshot {
+                        anchor: 3,
+                        cursor: 7,
+                    }],
+                )]
+                .into_iter()
+                .collect(),
+            }],
+            macros: vec![('@', vec!["i".to_owned(), "esc".to_owned()])]
+                .into_iter()
+                .collect(),
+        };
+        snapshot.write(&path).unwrap();
+        assert_eq!(SessionSnapshot::read(&path).unwrap(), snapshot);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::write(&path, "{").unwrap();
+        let error = SessionSnapshot::read(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saved_path_escapes_name() {
+        assert!(saved_path("team/work").ends_with("ced/sessions/team%work.json"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use remote::protocol::{
+    notifications::{ThemeParams, ThemeParamsFace},
+    Color, Face, Style,
+};
+use serde::Deserialize;
+
+use crate::editor::core::Error;
+
+pub const DEFAULT_THEME: &str = "default";
+
+/// Themes shipped with the editor, by file name.
+pub const THEMES: &[(&str, &str)] = &[
+    ("dark.toml", include_str!("../../themes/dark.toml")),
+    ("light.toml", include_str!("../../themes/light.toml")),
+];
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    faces: HashMap<String, Style>,
+}
+
+/// Styles of the faces, the faces it leaves out keeping the style of the default theme.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    faces: Vec<(Face, Style)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let fg = |color| Style {
+            foreground: Some(color),
+            ..Style::default()
+        };
+        Theme {
+            name: DEFAULT_THEME.to_owned(),
+            faces: vec![
+                (Face::Error, fg(Color::Red)),
+                (
+                    Face::Match,
+                    Style {
+                        underline: true,
+                        ..Style::default()
+                    },
+                ),
+                (
+                    Face::Selection,
+                    Style {
+                        reverse: true,
+                        ..Style::default()
+                    },
+                ),
+                (Face::Comment, fg(Color::DarkGrey)),
+                (Face::Constant, fg(Color::Magenta)),
+                (Face::Function, fg(Color::Blue)),
+                (Face::Keyword, fg(Color::Yellow)),
+                (Face::Number, fg(Color::Magenta)),
+                (Face::String, fg(Color::Green)),
+                (Face::Type, fg(Color::Cyan)),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    /// Reads a theme from a TOML file whose `faces` table gives the style of each face. The
+    /// theme is named after the file.
+    pub fn load(path: &Path) -> Result<Theme, Error> {
+        let content = fs::read_to_string(path).map_err(|e| Error::from_io(path, e))?;
+        Theme::parse(path, &content)
+    }
+
+    /// Reads a theme from `content`, the content of the file at `path`.
+    pub fn parse(path: &Path, content: &str) -> Result<Theme, Error> {
+        let invalid = |error: String| Error::InvalidTheme {
+            path: path.to_owned(),
+            error,
+        };
+        let file: ThemeFile = toml::from_str(content).map_err(|e| invalid(e.to_string()))?;
+        let mut faces = file
+            .faces
+            .into_iter()
+            .map(|(name, style)| {
+                Face::ALL
+                    .iter()
+                    .find(|face| face.to_string().eq_ignore_ascii_case(&name))
+                    .map(|face| (face.clone(), style))
+                    .ok_or_else(|| invalid(format!("unknown face: {}", name)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        faces.sort_by_key(|(face, _)| Face::ALL.iter().position(|f| f == face));
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Theme { name, faces })
+    }
+
+    pub fn style(&self, face: &Face) -> Style {
+        let find = |faces: &[(Face, Style)]| {
+            faces
+                .iter()
+                .find(|(f, _)| f == face)
+                .map(|(_, style)| style.clone())
+        };
+        find(&self.faces)
+            .or_else(|| find(&Theme::default().faces))
+            .unwrap_or_default()
+    }
+
+    /// The style of every face.
+    pub fn to_notification_params(&self) -> ThemeParams {
+        ThemeParams {
+            name: self.name.clone(),
+            faces: Face::ALL
+                .iter()
+                .map(|face| ThemeParamsFace {
+                    face: face.clone(),
+                    style: self.style(face),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load() {
+        let name = format!("ced-test-{}-theme", std::process::id());
+        let path = std::env::temp_dir().join(format!("{}.toml", name));
+        fs::write(
+            &path,
+            r##"
+            [faces.keyword]
+            foreground = "#ff8000"
+            bold = true
+
+            [faces.Selection]
+            background = "dark_blue"
+            "##,
+        )
+        .unwrap();
+        let theme = Theme::load(&path).unwrap();
+        assert_eq!(theme.name, name);
+        assert_eq!(
+            theme.style(&Face::Keyword),
+            Style {
+                foreground: Some(Color::Rgb(255, 128, 0)),
+                bold: true,
+                ..Style::default()
+            }
+        );
+        assert_eq!(
+            theme.style(&Face::Selection),
+            Style {
+                background: Some(Color::DarkBlue),
+                ..Style::default()
+            }
+        );
+        // left to the default theme
+        assert_eq!(
+            theme.style(&Face::Error),
+            Theme::default().style(&Face::Error)
+        );
+        assert_eq!(theme.to_notification_params().faces.len(), Face::ALL.len());
+
+        fs::write(&path, "[faces.nope]\nbold = true\n").unwrap();
+        assert!(matches!(
+            Theme::load(&path),
+            Err(Error::InvalidTheme { .. })
+        ));
+        fs::write(&path, "[faces.error]\nforeground = \"orange\"\n").unwrap();
+        assert!(matches!(
+            Theme::load(&path),
+            Err(Error::InvalidTheme { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
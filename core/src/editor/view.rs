@@ -1,17 +1,29 @@
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
 use std::ops::Range;
 
+use crate::editor::diff::{diff_lines, Diff};
+use crate::editor::layout::Layout;
+use crate::editor::piece_table::Change;
+use crate::editor::search::find_all;
 use crate::editor::selection::Selection;
 use crate::editor::Buffer;
 use ornament::Decorator;
+use regex::Regex;
 use remote::protocol::{
-    notifications::{ViewParams, ViewParamsItem, ViewParamsLens},
+    notifications::{
+        ViewParams, ViewParamsItem, ViewParamsLens, ViewPatchItem, ViewPatchLens, ViewPatchLines,
+        ViewPatchParams,
+    },
     Face,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+// views sent to a client that it can still acknowledge, the oldest ones being forgotten
+const MAX_UNACKED_VIEWS: usize = 32;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Focus {
     Range(Range<usize>),
     Whole,
@@ -59,7 +71,7 @@ impl Ord for Focus {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Lens {
     pub buffer: String,
     pub focus: Focus,
@@ -74,6 +86,25 @@ impl LensGroup {
         self.0.sort_by(|a, b| a.focus.cmp(&b.focus));
     }
 
+    /// Moves the range lenses along with the changes made to their buffer, keeping them within
+    /// its `line_count` lines. Returns whether any of them moved.
+    fn follow_changes(&mut self, changes: &[Change], line_count: usize) -> bool {
+        let mut moved = false;
+        for lens in &mut self.0 {
+            if let Focus::Range(range) = &mut lens.focus {
+                let new = changes
+                    .iter()
+                    .fold(range.clone(), |range, change| change.remap_lines(&range));
+                let end = new.end.min(line_count).max(1);
+                let new = new.start.min(end - 1)..end;
+                moved |= new != *range;
+                *range = new;
+            }
+        }
+        self.0.sort_by(|a, b| a.focus.cmp(&b.focus));
+        moved
+    }
+
     pub fn focus(&self) -> Focus {
         match (&self.first().unwrap().focus, &self.last().unwrap().focus) {
             (Focus::Whole, _) | (_, Focus::Whole) => Focus::Whole,
@@ -82,7 +113,7 @@ impl LensGroup {
     }
 }
 
-impl<'a> std::ops::Deref for LensGroup {
+impl std::ops::Deref for LensGroup {
     type Target = Vec<Lens>;
 
     fn deref(&self) -> &Self::Target {
@@ -96,8 +127,13 @@ pub enum ViewItem {
     Lens(Lens),
 }
 
+/// Lenses on buffers. A view is known by an id given when it is added to the editor, that
+/// stays the same while its lenses move, unlike its key describing them.
 #[derive(Clone, Debug, Default)]
-pub struct View(BTreeMap<String, LensGroup>);
+pub struct View {
+    id: String,
+    lenses: BTreeMap<String, LensGroup>,
+}
 
 impl View {
     pub fn for_buffer(buffer: &str) -> View {
@@ -109,9 +145,17 @@ impl View {
         view
     }
 
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
     pub fn key(&self) -> String {
         let mut parts = Vec::new();
-        for (buffer, group) in &self.0 {
+        for (buffer, group) in &self.lenses {
             let coords = group
                 .iter()
                 .map(|lens| lens.focus.to_string())
@@ -122,23 +166,31 @@ impl View {
     }
 
     pub fn add_lens(&mut self, lens: Lens) {
-        self.0
+        self.lenses
             .entry(lens.buffer.clone())
-            .or_insert_with(LensGroup::default)
+            .or_default()
             .add(lens);
     }
 
+    /// Moves the range lenses on `buffer` along with the changes made to it, keeping them within
+    /// its `line_count` lines. Returns whether any of them moved.
+    pub fn follow_changes(&mut self, buffer: &str, changes: &[Change], line_count: usize) -> bool {
+        self.lenses
+            .get_mut(buffer)
+            .is_some_and(|group| group.follow_changes(changes, line_count))
+    }
+
     pub fn remove_lens_group(&mut self, buffer: &str) -> Option<LensGroup> {
-        self.0.remove(buffer)
+        self.lenses.remove(buffer)
     }
 
     pub fn buffers(&self) -> Vec<&String> {
-        self.0.keys().collect()
+        self.lenses.keys().collect()
     }
 
     pub fn as_vec(&self) -> Vec<ViewItem> {
         let mut list = Vec::new();
-        for (buffer, group) in &self.0 {
+        for (buffer, group) in &self.lenses {
             list.push(ViewItem::Header((buffer.to_string(), group.focus())));
             for lens in group.iter() {
                 list.push(ViewItem::Lens(lens.clone()));
@@ -148,27 +200,63 @@ impl View {
     }
 
     pub fn contains_buffer(&self, buffer: &str) -> bool {
-        self.0.contains_key(buffer)
+        self.lenses.contains_key(buffer)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.len() == 0
+        self.lenses.len() == 0
+    }
+
+    /// Number of lines of the view, the lines of its lenses following one another.
+    pub fn line_count(&self, buffers: &HashMap<String, Buffer>) -> usize {
+        self.lenses
+            .values()
+            .flat_map(|group| group.iter())
+            .map(|lens| lens_len(lens, &buffers[&lens.buffer]))
+            .sum()
     }
 
+    /// Line of the view showing `line` of `buffer`, none if no lens shows it.
+    pub fn view_line(
+        &self,
+        buffers: &HashMap<String, Buffer>,
+        buffer: &str,
+        line: usize,
+    ) -> Option<usize> {
+        let mut start = 0;
+        for lens in self.lenses.values().flat_map(|group| group.iter()) {
+            let len = lens_len(lens, &buffers[&lens.buffer]);
+            let focus = lens.focus.start();
+            if lens.buffer == buffer && (focus..focus + len).contains(&line) {
+                return Some(start + line - focus);
+            }
+            start += len;
+        }
+        None
+    }
+
+    /// Builds the notification showing the view from its line `window.start` in
+    /// `window.len()` rows, the header of each lens shown taking one of them and a wrapped line
+    /// one per row.
     pub fn to_notification_params(
         &self,
         buffers: &HashMap<String, Buffer>,
         selections: Option<&HashMap<String, Vec<Selection>>>,
+        search: Option<&Regex>,
+        window: Range<usize>,
+        layout: &Layout,
+        tab_width: usize,
     ) -> ViewParams {
         let mut params = Vec::new();
         let mut current = ViewParamsItem::default();
-        let mut flush = false;
+        // line of the view the next lens starts at, and rows left to show it
+        let mut line = 0;
+        let mut rows = window.len();
         for item in self.as_vec() {
             match item {
                 ViewItem::Header((buffer, focus)) => {
-                    if flush {
-                        params.push(current);
-                        current = ViewParamsItem::default();
+                    if !current.lenses.is_empty() {
+                        params.push(std::mem::take(&mut current));
                     }
                     match focus {
                         Focus::Range(range) => {
@@ -183,10 +271,19 @@ impl View {
                             current.end = b.line_count();
                         }
                     }
-                    flush = true;
                 }
                 ViewItem::Lens(lens) => {
                     let buffer = &buffers[&lens.buffer];
+                    let len = lens_len(&lens, buffer);
+                    let start = window.start.max(line);
+                    // a lens is shown with a header of its own or of its buffer
+                    let end = (line + len).min(start.saturating_add(rows.saturating_sub(1)));
+                    let shown = start..end;
+                    let first = lens.focus.start() + shown.start - line;
+                    line += len;
+                    if shown.is_empty() {
+                        continue;
+                    }
                     let sels = selections.and_then(|ss| ss.get(&lens.buffer));
                     let mut selected: HashMap<usize, Vec<_>> = HashMap::new();
                     if let Some(ss) = sels {
@@ -236,51 +333,325 @@ impl View {
                             }
                         }
                     }
-                    let lines = buffer
-                        .lines(lens.focus.clone())
+                    let decorated = buffer
+                        .lines(Focus::Range(first..first + shown.len()))
                         .iter()
                         .enumerate()
                         .map(|(i, line)| {
                             let l = line.to_owned() + " ";
                             let mut deco = Decorator::with_text(&l);
-                            if let Some(ranges) = selected.get(&i) {
-                                for range in ranges {
-                                    match *range {
-                                        (Some(start), Some(end)) => {
-                                            deco.set(Face::Selection, start..end);
-                                        }
-                                        (Some(start), None) => {
-                                            deco.set(Face::Selection, start..l.len());
-                                        }
-                                        (None, Some(end)) => {
-                                            deco.set(Face::Selection, 0..end);
-                                        }
-                                        (None, None) => {
-                                            deco.set(Face::Selection, 0..l.len());
-                                        }
+                            let sel_ranges = selected
+                                .get(&(i + first))
+                                .into_iter()
+                                .flatten()
+                                .map(|range| match *range {
+                                    (Some(start), Some(end)) => start..end,
+                                    (Some(start), None) => start..l.len(),
+                                    (None, Some(end)) => 0..end,
+                                    (None, None) => 0..l.len(),
+                                })
+                                .collect::<Vec<_>>();
+                            for range in &sel_ranges {
+                                deco.set(Face::Selection, range.clone());
+                            }
+                            // decorations cannot be nested, matches are left out of selections
+                            // and highlights out of both
+                            let mut covered = sel_ranges;
+                            if let Some(regex) = search {
+                                for m in find_all(regex, line) {
+                                    for part in uncovered(m, &covered) {
+                                        deco.set(Face::Match, part.clone());
+                                        covered.push(part);
+                                    }
+                                }
+                            }
+                            if let Some(highlighter) = &buffer.highlighter {
+                                for (range, face) in highlighter.line(i + first, line) {
+                                    for part in uncovered(range, &covered) {
+                                        deco.set(face.clone(), part);
                                     }
                                 }
                             }
                             deco.build()
                         })
-                        .collect();
+                        .collect::<Vec<_>>();
+                    // the rows of wrapped lines past the ones left are cut
+                    let mut lines = Vec::new();
+                    let mut continuations = Vec::new();
+                    for line in &decorated {
+                        let wrapped = layout.rows(line, tab_width);
+                        continuations.extend(lines.len() + 1..lines.len() + wrapped.len());
+                        lines.extend(wrapped);
+                    }
+                    lines.truncate(rows - 1);
+                    continuations.retain(|&row| row < lines.len());
+                    rows -= lines.len() + 1;
                     current.lenses.push(ViewParamsLens {
                         lines,
-                        first_line_num: lens.focus.start() + 1,
+                        first_line_num: first + 1,
+                        continuations,
                     });
                 }
             }
         }
-        if flush {
+        if !current.lenses.is_empty() {
             params.push(current);
         }
         params
     }
 }
 
+/// Number of lines of the view shown in `params`, rows continuing a wrapped line left out.
+pub fn shown_lines(params: &ViewParams) -> usize {
+    params
+        .iter()
+        .flat_map(|item| &item.lenses)
+        .map(|lens| lens.lines.len() - lens.continuations.len())
+        .sum()
+}
+
+/// Number of lines shown by `lens`, whose buffer is `buffer`.
+fn lens_len(lens: &Lens, buffer: &Buffer) -> usize {
+    match &lens.focus {
+        Focus::Range(range) => range.len(),
+        Focus::Whole => buffer.line_count(),
+    }
+}
+
+/// Lines of its view a client shows, the lines of the lenses following one another.
+#[derive(Clone, Debug, Default)]
+pub struct Viewport {
+    /// first line shown
+    pub top: usize,
+    /// number of rows shown, headers included, all the lines until the client reports it
+    pub height: Option<usize>,
+    // view and main cursor of each of its buffers the last time they were followed, and the
+    // buffer whose cursor moved last
+    view: String,
+    cursors: HashMap<String, usize>,
+    focused: Option<String>,
+}
+
+impl Viewport {
+    /// The top and as many lines as rows shown, see `View::to_notification_params`.
+    pub fn window(&self) -> Range<usize> {
+        let end = self
+            .height
+            .map_or(usize::MAX, |h| self.top.saturating_add(h));
+        self.top..end
+    }
+
+    /// Sets the number of lines shown, the main cursor being shown again on the next follow.
+    pub fn set_height(&mut self, height: usize) {
+        self.height = Some(height.max(1));
+        self.cursors.clear();
+    }
+
+    /// Moves the top by `delta` lines, staying within the `line_count` lines of the view.
+    pub fn scroll(&mut self, delta: isize, line_count: usize) {
+        let top = self.top.saturating_add_signed(delta);
+        self.top = top.min(line_count.saturating_sub(1));
+    }
+
+    /// Scrolls just enough for the line `line` to be shown.
+    pub fn reveal(&mut self, line: usize) {
+        let height = self.height.unwrap_or(usize::MAX);
+        if line < self.top {
+            self.top = line;
+        } else if line - self.top >= height {
+            self.top = line + 1 - height;
+        }
+    }
+
+    /// Puts the line of the main cursor that moved last in the middle of the viewport.
+    /// `cursors` are the main cursors of the buffers of the view, see `follow`.
+    pub fn center(&mut self, cursors: &[(String, usize, Option<usize>)]) {
+        let line = cursors
+            .iter()
+            .find(|c| Some(&c.0) == self.focused.as_ref())
+            .or_else(|| cursors.first())
+            .and_then(|c| c.2);
+        if let Some(line) = line {
+            self.top = line.saturating_sub(self.height.unwrap_or(0) / 2);
+        }
+    }
+
+    /// Scrolls to show the main cursor which moved since the last call, the view `view_id`
+    /// coming into sight from its top. `cursors` give for each buffer of the view the offset
+    /// of its main cursor and the line of the view it is shown on. Returns the one followed.
+    pub fn follow<'a>(
+        &mut self,
+        view_id: &str,
+        cursors: &'a [(String, usize, Option<usize>)],
+    ) -> Option<&'a (String, usize, Option<usize>)> {
+        if self.view != view_id {
+            self.view = view_id.to_owned();
+            self.top = 0;
+            self.cursors.clear();
+            self.focused = None;
+        }
+        let moved = cursors
+            .iter()
+            .filter(|c| self.cursors.get(&c.0) != Some(&c.1))
+            .min_by_key(|c| Some(&c.0) != self.focused.as_ref());
+        if let Some((buffer, _, line)) = moved {
+            self.focused = Some(buffer.to_owned());
+            if let Some(line) = line {
+                self.reveal(*line);
+            }
+        }
+        self.cursors = cursors
+            .iter()
+            .map(|(buffer, offset, _)| (buffer.to_owned(), *offset))
+            .collect();
+        moved
+    }
+}
+
+/// A view sent to a client, whole or as a patch.
+#[derive(Debug)]
+pub enum ViewUpdate {
+    Whole(ViewParams),
+    Patch(ViewPatchParams),
+}
+
+/// Views sent to a client, each one as a patch against the last one it acknowledged once it
+/// asked for patches.
+#[derive(Clone, Debug, Default)]
+pub struct ViewSync {
+    patches: bool,
+    revision: usize,
+    acked: Option<(usize, ViewParams)>,
+    // views sent since the acknowledged one, oldest first
+    sent: VecDeque<(usize, ViewParams)>,
+}
+
+impl ViewSync {
+    /// Sends the next views as patches.
+    pub fn enable_patches(&mut self) {
+        self.patches = true;
+    }
+
+    /// Sends `view` as a patch if the client asked for them, whole otherwise.
+    pub fn update(&mut self, view: ViewParams) -> ViewUpdate {
+        if self.patches {
+            ViewUpdate::Patch(self.patch(view))
+        } else {
+            ViewUpdate::Whole(view)
+        }
+    }
+
+    /// Makes the patch sending `view` as the next revision.
+    pub fn patch(&mut self, view: ViewParams) -> ViewPatchParams {
+        self.revision += 1;
+        let (base, items) = match &self.acked {
+            Some((revision, base)) => (Some(*revision), diff_view(base, &view)),
+            None => (None, diff_view(&[], &view)),
+        };
+        self.sent.push_back((self.revision, view));
+        if self.sent.len() > MAX_UNACKED_VIEWS {
+            self.sent.pop_front();
+        }
+        ViewPatchParams {
+            base,
+            revision: self.revision,
+            items,
+        }
+    }
+
+    /// Records that the client holds the view of `revision`. Returns false if it is not one
+    /// of the views waiting for an acknowledgement.
+    pub fn ack(&mut self, revision: usize) -> bool {
+        match self.sent.iter().position(|(r, _)| *r == revision) {
+            Some(index) => {
+                self.sent.drain(..index);
+                self.acked = self.sent.pop_front();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Changes turning the lines of the `base` view into those of `view`, each lens being patched
+/// from the lens at the same place in the same buffer.
+fn diff_view(base: &[ViewParamsItem], view: &[ViewParamsItem]) -> Vec<ViewPatchItem> {
+    view.iter()
+        .map(|item| {
+            let old = base.iter().find(|old| old.buffer == item.buffer);
+            let lenses = item
+                .lenses
+                .iter()
+                .enumerate()
+                .map(|(i, lens)| {
+                    let old_lines = old
+                        .and_then(|old| old.lenses.get(i))
+                        .map(|old| old.lines.as_slice());
+                    let mut changes: Vec<ViewPatchLines> = Vec::new();
+                    let mut pos = 0;
+                    for d in diff_lines(old_lines.unwrap_or_default(), &lens.lines) {
+                        match d {
+                            Diff::Both(len) => pos += len,
+                            Diff::Left(len) => changes.push(ViewPatchLines {
+                                start: pos,
+                                removed: len,
+                                lines: Vec::new(),
+                            }),
+                            Diff::Right(len) => {
+                                let lines = lens.lines[pos..pos + len].to_vec();
+                                match changes.last_mut() {
+                                    Some(last) if last.start == pos && last.lines.is_empty() => {
+                                        last.lines = lines
+                                    }
+                                    _ => changes.push(ViewPatchLines {
+                                        start: pos,
+                                        removed: 0,
+                                        lines,
+                                    }),
+                                }
+                                pos += len;
+                            }
+                        }
+                    }
+                    ViewPatchLens {
+                        first_line_num: lens.first_line_num,
+                        base: old_lines.map(|_| i),
+                        changes,
+                        continuations: lens.continuations.clone(),
+                    }
+                })
+                .collect();
+            ViewPatchItem {
+                buffer: item.buffer.to_owned(),
+                start: item.start,
+                end: item.end,
+                lenses,
+            }
+        })
+        .collect()
+}
+
+/// Parts of `range` outside all of the `covered` ranges.
+fn uncovered(range: Range<usize>, covered: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut parts = vec![range];
+    for c in covered {
+        parts = parts
+            .into_iter()
+            .flat_map(|p| {
+                let before = p.start..p.end.min(c.start);
+                let after = p.start.max(c.end)..p.end;
+                vec![before, after]
+            })
+            .filter(|p| !p.is_empty())
+            .collect();
+    }
+    parts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::editor::layout::{Wrap, DEFAULT_TAB_WIDTH};
 
     #[test]
     fn key() {
@@ -313,4 +684,300 @@ mod tests {
         });
         assert_eq!(double.key(), "buffer1{10..12,20..51}|buffer2{*}");
     }
+
+    #[test]
+    fn search_highlighting() {
+        let mut buffers = HashMap::new();
+        buffers.insert(
+            "test".to_owned(),
+            Buffer::new_scratch("test".to_owned(), "one two one\n".to_owned()),
+        );
+        let regex = Regex::new("one").unwrap();
+        let params = View::for_buffer("test").to_notification_params(
+            &buffers,
+            None,
+            Some(&regex),
+            0..usize::MAX,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        let faces = params[0].lenses[0].lines[0]
+            .iter()
+            .map(|tf| (tf.text.as_str(), tf.face.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            faces,
+            vec![
+                ("one", Face::Match),
+                (" two ", Face::Default),
+                ("one", Face::Match),
+                (" ", Face::Default),
+            ]
+        );
+    }
+
+    #[test]
+    fn mixed_line_endings() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("ced-test-{}-view-mixed", std::process::id()));
+        std::fs::write(&path, "first\r\nsecond\nthird\r\n").unwrap();
+        let mut buffers = HashMap::new();
+        buffers.insert("test".to_owned(), Buffer::new_file(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let params = View::for_buffer("test").to_notification_params(
+            &buffers,
+            None,
+            None,
+            0..usize::MAX,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        let lines = params[0].lenses[0]
+            .lines
+            .iter()
+            .map(|line| line.iter().map(|tf| tf.text.as_str()).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec!["first ", "second ", "third "]);
+    }
+
+    #[test]
+    fn selection_in_match() {
+        let mut buffers = HashMap::new();
+        buffers.insert(
+            "test".to_owned(),
+            Buffer::new_scratch("test".to_owned(), "one two\n".to_owned()),
+        );
+        let mut selections = HashMap::new();
+        let sel = Selection {
+            anchor: 5,
+            cursor: 5,
+            ..Selection::new()
+        };
+        selections.insert("test".to_owned(), vec![sel]);
+        let regex = Regex::new("two").unwrap();
+        let params = View::for_buffer("test").to_notification_params(
+            &buffers,
+            Some(&selections),
+            Some(&regex),
+            0..usize::MAX,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        let faces = params[0].lenses[0].lines[0]
+            .iter()
+            .map(|tf| (tf.text.as_str(), tf.face.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            faces,
+            vec![
+                ("one ", Face::Default),
+                ("t", Face::Match),
+                ("w", Face::Selection),
+                ("o", Face::Match),
+                (" ", Face::Default),
+            ]
+        );
+    }
+
+    #[test]
+    fn selection_across_match() {
+        let mut buffers = HashMap::new();
+        buffers.insert(
+            "test".to_owned(),
+            Buffer::new_scratch("test".to_owned(), "one two\n".to_owned()),
+        );
+        let mut selections = HashMap::new();
+        let sel = Selection {
+            anchor: 2,
+            cursor: 4,
+            ..Selection::new()
+        };
+        selections.insert("test".to_owned(), vec![sel]);
+        let regex = Regex::new("one|two").unwrap();
+        let params = View::for_buffer("test").to_notification_params(
+            &buffers,
+            Some(&selections),
+            Some(&regex),
+            0..usize::MAX,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        let faces = params[0].lenses[0].lines[0]
+            .iter()
+            .map(|tf| (tf.text.as_str(), tf.face.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            faces,
+            vec![
+                ("on", Face::Match),
+                ("e t", Face::Selection),
+                ("wo", Face::Match),
+                (" ", Face::Default),
+            ]
+        );
+    }
+
+    #[test]
+    fn range_lens_selection() {
+        let mut buffers = HashMap::new();
+        buffers.insert(
+            "test".to_owned(),
+            Buffer::new_scratch("test".to_owned(), "a\nb\nc\n".to_owned()),
+        );
+        let mut view = View::default();
+        view.add_lens(Lens {
+            buffer: "test".to_owned(),
+            focus: Focus::Range(1..3),
+        });
+        let mut selections = HashMap::new();
+        let sel = Selection {
+            anchor: 4,
+            cursor: 4,
+            ..Selection::new()
+        };
+        selections.insert("test".to_owned(), vec![sel]);
+        let params = view.to_notification_params(
+            &buffers,
+            Some(&selections),
+            None,
+            0..usize::MAX,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        let lines = &params[0].lenses[0].lines;
+        assert_eq!(lines[0].iter().next().unwrap().face, Face::Default);
+        assert_eq!(lines[1].iter().next().unwrap().face, Face::Selection);
+    }
+
+    #[test]
+    fn window() {
+        let mut buffers = HashMap::new();
+        for name in ["a", "b"] {
+            buffers.insert(
+                name.to_owned(),
+                Buffer::new_scratch(name.to_owned(), "1\n2\n3\n4\n".to_owned()),
+            );
+        }
+        let mut view = View::for_buffer("a");
+        view.add_lens(Lens {
+            buffer: "b".to_owned(),
+            focus: Focus::Range(1..3),
+        });
+        assert_eq!(view.line_count(&buffers), 6);
+        assert_eq!(view.view_line(&buffers, "b", 2), Some(5));
+        assert_eq!(view.view_line(&buffers, "b", 0), None);
+
+        let params = view.to_notification_params(
+            &buffers,
+            None,
+            None,
+            3..7,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        let shown = params
+            .iter()
+            .map(|item| (item.buffer.as_str(), item.lenses[0].first_line_num))
+            .collect::<Vec<_>>();
+        assert_eq!(shown, vec![("a", 4), ("b", 2)]);
+        assert_eq!(params[1].lenses[0].lines.len(), 1);
+        // the header of a lens takes a row
+        let params = view.to_notification_params(
+            &buffers,
+            None,
+            None,
+            3..5,
+            &Layout::default(),
+            DEFAULT_TAB_WIDTH,
+        );
+        assert_eq!(params.len(), 1);
+        assert_eq!(shown_lines(&params), 1);
+    }
+
+    #[test]
+    fn view_sync() {
+        let view = |lines: &[&str]| -> ViewParams {
+            let mut buffers = HashMap::new();
+            buffers.insert(
+                "test".to_owned(),
+                Buffer::new_scratch("test".to_owned(), lines.concat()),
+            );
+            View::for_buffer("test").to_notification_params(
+                &buffers,
+                None,
+                None,
+                0..usize::MAX,
+                &Layout::default(),
+                DEFAULT_TAB_WIDTH,
+            )
+        };
+        let first = view(&["a\n", "b\n", "c\n"]);
+        let second = view(&["a\n", "x\n", "c\n", "d\n"]);
+
+        let mut sync = ViewSync::default();
+        assert!(matches!(sync.update(first.clone()), ViewUpdate::Whole(_)));
+        sync.enable_patches();
+        let patch = match sync.update(first.clone()) {
+            ViewUpdate::Patch(patch) => patch,
+            ViewUpdate::Whole(_) => panic!("view sent whole"),
+        };
+        assert_eq!(patch.base, None);
+        assert_eq!(format!("{:?}", patch.apply(&[])), format!("{:?}", first));
+
+        assert!(sync.ack(1));
+        assert!(!sync.ack(2));
+        let patch = sync.patch(second.clone());
+        assert_eq!((patch.base, patch.revision), (Some(1), 2));
+        let changes = &patch.items[0].lenses[0].changes;
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| (c.start, c.removed, c.lines.len()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1, 1), (3, 0, 1)]
+        );
+        assert_eq!(
+            format!("{:?}", patch.apply(&first)),
+            format!("{:?}", second)
+        );
+    }
+
+    #[test]
+    fn wrapped_lines() {
+        let mut buffers = HashMap::new();
+        buffers.insert(
+            "test".to_owned(),
+            Buffer::new_scratch("test".to_owned(), "abcdef\nab\n".to_owned()),
+        );
+        let layout = Layout {
+            wrap: Wrap::Char,
+            width: Some(4),
+            column: 0,
+        };
+        let params = View::for_buffer("test").to_notification_params(
+            &buffers,
+            None,
+            None,
+            0..usize::MAX,
+            &layout,
+            DEFAULT_TAB_WIDTH,
+        );
+        let lens = &params[0].lenses[0];
+        let rows = lens.lines.iter().map(|row| row.plain()).collect::<Vec<_>>();
+        assert_eq!(rows, vec!["abcd", "ef ", "ab "]);
+        assert_eq!(lens.continuations, vec![1]);
+
+        // the rows of a wrapped line count in the height
+        let params = View::for_buffer("test").to_notification_params(
+            &buffers,
+            None,
+            None,
+            0..3,
+            &layout,
+            DEFAULT_TAB_WIDTH,
+        );
+        assert_eq!(params[0].lenses[0].lines.len(), 2);
+        assert_eq!(shown_lines(&params), 1);
+    }
 }
@@ -8,6 +8,7 @@ pub mod stackmap;
 pub mod server;
 pub mod clients;
 pub mod script;
+pub mod watcher;
 
 #[cfg(feature = "term")]
 pub mod tui;
@@ -26,8 +26,8 @@ pub fn exec_scripts(filenames: &[&str]) -> io::Result<()> {
         editor
             .exec_lua(fname, CLIENT_ID, |lua| lua.load(&source).exec())
             .map_err(|e| {
-                eprintln!("lua error: {}: {}", fname, e.to_string());
-                io::Error::new(io::ErrorKind::Other, "invalid lua script")
+                eprintln!("lua error: {}: {}", fname, e);
+                io::Error::other("invalid lua script")
             })?;
     }
 
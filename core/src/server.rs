@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::fs;
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
 
 use crate::editor::Editor;
+use crate::watcher::FileWatcher;
 use async_channel::{unbounded, Receiver, Sender};
 use async_executor::LocalExecutor;
 use futures_lite::*;
@@ -41,12 +45,14 @@ impl BroadcastMessage {
 }
 
 const FIRST_CLIENT_ID: usize = 1;
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug)]
 enum Event {
     Join((usize, ServerStream)),
     Leave(usize),
     Message((usize, String)),
+    Tick,
 }
 
 pub struct Server {
@@ -66,9 +72,11 @@ impl Server {
         future::block_on(stream.write_all(format!("{}\n", message).as_bytes()))
     }
 
-    async fn handle_events(ex: Arc<LocalExecutor<'_>>, session: String, receiver: Receiver<Event>) {
+    async fn handle_events(ex: Rc<LocalExecutor<'_>>, session: String, receiver: Receiver<Event>) {
         let (bsender, breceiver) = unbounded();
         let mut editor = Editor::new(&session, bsender);
+        editor.set_swap_dir(Session::swap_dir());
+        let mut watcher = FileWatcher::new();
         let clients = Arc::new(RwLock::new(HashMap::<usize, ServerStream>::new()));
 
         let bclients = Arc::clone(&clients);
@@ -92,7 +100,9 @@ impl Server {
 
         log::trace!("starting client event loop");
         while let Ok(event) = receiver.recv().await {
-            log::trace!("client event: {:?}", event);
+            if !matches!(event, Event::Tick) {
+                log::trace!("client event: {:?}", event);
+            }
             let mut is_leave_event = false;
             match event {
                 Event::Join((client_id, stream)) => {
@@ -123,6 +133,13 @@ impl Server {
                     }
                     Err(e) => log::error!("{}: {:?}", e, raw),
                 },
+                Event::Tick => {
+                    let changed = watcher.changes(&editor.watched_files());
+                    if !changed.is_empty() {
+                        editor.files_changed(&changed);
+                    }
+                    editor.write_journals();
+                }
             }
             for client_id in editor.removed_clients() {
                 clients
@@ -132,10 +149,12 @@ impl Server {
                     .expect("remove client from index");
                 log::info!("client {}: quit", client_id);
             }
-            if clients.read().expect("lock client index").len() == 0 && is_leave_event {
+            if clients.read().expect("lock client index").is_empty() && is_leave_event {
                 break;
             }
         }
+        // unsaved changes stay in their journal, to be recovered by the next server
+        editor.close_journals();
     }
 
     async fn read_client(
@@ -164,7 +183,7 @@ impl Server {
     }
 
     async fn serve(
-        ex: Arc<LocalExecutor<'_>>,
+        ex: Rc<LocalExecutor<'_>>,
         session: Session,
         sender: Sender<Event>,
     ) -> io::Result<()> {
@@ -192,11 +211,20 @@ impl Server {
         Ok(())
     }
 
+    fn spawn_ticks(sender: Sender<Event>) {
+        thread::spawn(move || {
+            while future::block_on(sender.send(Event::Tick)).is_ok() {
+                thread::sleep(TICK_INTERVAL);
+            }
+        });
+    }
+
     pub fn run(&self) -> io::Result<()> {
-        let ex = Arc::new(LocalExecutor::new());
+        let ex = Rc::new(LocalExecutor::new());
         log::trace!("spawning server task");
         future::block_on(ex.run(async {
             let (sender, receiver) = unbounded();
+            Self::spawn_ticks(sender.clone());
             ex.spawn(Self::serve(ex.clone(), self.session.clone(), sender))
                 .detach();
             Self::handle_events(ex.clone(), self.session.to_string(), receiver).await;
@@ -41,13 +41,18 @@ where
         self.map.get(idx)
     }
 
+    /// Keys from the latest to the oldest.
+    pub fn stack(&self) -> &[K] {
+        &self.stack
+    }
+
     pub fn set_last(&mut self, k: K) -> Result<(), InvalidOp> {
         if !self.map.contains_key(&k) {
             return Err(InvalidOp {
                 reason: "key does not exist",
             });
         }
-        if let Some(idx) = self.stack.iter().position(|ref e| **e == k) {
+        if let Some(idx) = self.stack.iter().position(|e| *e == k) {
             self.stack.remove(idx);
         }
         self.stack.insert(0, k);
@@ -62,15 +67,24 @@ where
     }
 
     pub fn remove(&mut self, k: &K) -> Option<V> {
-        if let Some(idx) = self.stack.iter().position(|ref e| **e == *k) {
+        if let Some(idx) = self.stack.iter().position(|e| e == k) {
             self.stack.remove(idx);
-            self.map.remove(&k)
+            self.map.remove(k)
         } else {
             None
         }
     }
 }
 
+impl<K, V> Default for StackMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<K, V> Deref for StackMap<K, V> {
     type Target = HashMap<K, V>;
 
@@ -6,12 +6,15 @@ use crossterm::{
     cursor,
     event::{Event as CEvent, EventStream, KeyCode as CKeyCode, KeyModifiers as CKeyModifiers},
     execute, queue,
-    style::{style, Print, PrintStyledContent, Stylize},
+    style::{self, style, Print, PrintStyledContent, Stylize},
     terminal::{self, Clear, ClearType},
     Result as CTResult,
 };
 use futures_lite::*;
-use remote::protocol::{notifications::HintParams, Face, Key, KeyEvent, TextFragment};
+use remote::protocol::{
+    notifications::{HintParams, ThemeParams},
+    Color, Key, KeyEvent, TextFragment,
+};
 use remote::{Connection, ConnectionEvent, Menu, Session};
 
 fn logline(msg: impl std::fmt::Display) {
@@ -44,6 +47,8 @@ impl From<CEvent> for ToKey {
                 CKeyCode::Down => Some(Key::Down),
                 CKeyCode::Left => Some(Key::Left),
                 CKeyCode::Right => Some(Key::Right),
+                CKeyCode::PageUp => Some(Key::PageUp),
+                CKeyCode::PageDown => Some(Key::PageDown),
 
                 _ => None,
             };
@@ -59,13 +64,52 @@ impl From<CEvent> for ToKey {
     }
 }
 
-fn format_text(tf: &TextFragment) -> String {
-    match tf.face {
-        Face::Default => tf.text.to_owned(),
-        Face::Error => style(&tf.text).red().to_string(),
-        Face::Selection => style(&tf.text).reverse().to_string(),
-        _ => tf.text.to_owned(),
+fn term_color(color: Color) -> style::Color {
+    use style::Color as C;
+    match color {
+        Color::Black => C::Black,
+        Color::DarkGrey => C::DarkGrey,
+        Color::Red => C::Red,
+        Color::DarkRed => C::DarkRed,
+        Color::Green => C::Green,
+        Color::DarkGreen => C::DarkGreen,
+        Color::Yellow => C::Yellow,
+        Color::DarkYellow => C::DarkYellow,
+        Color::Blue => C::Blue,
+        Color::DarkBlue => C::DarkBlue,
+        Color::Magenta => C::Magenta,
+        Color::DarkMagenta => C::DarkMagenta,
+        Color::Cyan => C::Cyan,
+        Color::DarkCyan => C::DarkCyan,
+        Color::White => C::White,
+        Color::Grey => C::Grey,
+        Color::Rgb(r, g, b) => C::Rgb { r, g, b },
+    }
+}
+
+/// Renders `tf` with the style its face has in `theme`.
+fn format_text(theme: &ThemeParams, tf: &TextFragment) -> String {
+    let face_style = theme.style(&tf.face);
+    let mut content = style(tf.text.as_str());
+    if let Some(color) = face_style.foreground {
+        content = content.with(term_color(color));
+    }
+    if let Some(color) = face_style.background {
+        content = content.on(term_color(color));
     }
+    if face_style.bold {
+        content = content.bold();
+    }
+    if face_style.italic {
+        content = content.italic();
+    }
+    if face_style.underline {
+        content = content.underlined();
+    }
+    if face_style.reverse {
+        content = content.reverse();
+    }
+    content.to_string()
 }
 
 #[derive(Debug)]
@@ -79,6 +123,12 @@ enum Event {
     Resize((u16, u16)),
 }
 
+/// Rows of the view shown in a terminal of `size`, once the status line is drawn. The server
+/// leaves room for the headers.
+fn view_height(size: (u16, u16)) -> usize {
+    (size.1 as usize).saturating_sub(1)
+}
+
 pub struct Term {
     connection: Connection,
     exit_pending: bool,
@@ -86,7 +136,7 @@ pub struct Term {
 }
 
 impl Term {
-    pub fn new(session: Session, filenames: &[&str]) -> Term {
+    pub fn new(session: Session, restore: Option<&str>, filenames: &[&str]) -> Term {
         logline("----------");
         let mut term = Term {
             connection: Connection::new(session),
@@ -94,6 +144,12 @@ impl Term {
             last_size: terminal::size().expect("get terminal"),
         };
         logline("new connection");
+        let (width, _) = term.last_size;
+        term.connection
+            .resize(width as usize, view_height(term.last_size));
+        if let Some(name) = restore {
+            term.connection.session_restore(Some(name.to_owned()));
+        }
         for fname in filenames {
             term.connection.edit(fname.to_string(), false);
         }
@@ -141,7 +197,9 @@ impl Term {
                     logline(format!("new message: {:?}", msg));
                     use ConnectionEvent::*;
                     match msg {
-                        Echo(_) | View(_) => tx.send(Event::DrawView).await.expect("send event"),
+                        Echo(_) | Theme(_) | View(_) => {
+                            tx.send(Event::DrawView).await.expect("send event")
+                        }
                         Hint(hint) => tx.send(Event::DrawHint(hint)).await.expect("send event"),
                         Info(_, _) => {}
                         Menu(menu) => tx.send(Event::DrawMenu(menu)).await.expect("send event"),
@@ -174,6 +232,7 @@ impl Term {
         }));
     }
 
+    #[allow(dead_code)]
     fn debug(&mut self, message: &str) {
         self.connection.exec(&format!("editor:debug({})", message));
     }
@@ -192,23 +251,35 @@ impl Term {
         let mut content = Vec::new();
         'outer: for item in &state.view {
             let buffer = &item.buffer;
-            let coords = format!("{}:{}", item.start, item.end);
-            let padding = "-".repeat(width as usize - 5 - buffer.len() - coords.len());
-            content.push(format!("-[{}][{}]{}", buffer, coords, padding));
-            i += 1;
+            let header = |start: usize, end: usize| {
+                let coords = format!("{}:{}", start, end);
+                let padding = "-".repeat(width as usize - 5 - buffer.len() - coords.len());
+                format!("-[{}][{}]{}", buffer, coords, padding)
+            };
+            // lenses far apart in a buffer get their own header
+            let split = item.lenses.len() > 1;
+            if !split {
+                content.push(header(item.start, item.end));
+                i += 1;
+            }
 
             for lens in &item.lenses {
+                if split {
+                    if i == (height - 1) {
+                        break 'outer;
+                    }
+                    // rows continuing a wrapped line are not lines of the buffer
+                    let line_count = lens.lines.len() - lens.continuations.len();
+                    let last_line_num = lens.first_line_num + line_count.max(1) - 1;
+                    content.push(header(lens.first_line_num, last_line_num));
+                    i += 1;
+                }
                 for line in &lens.lines {
                     if i == (height - 1) {
                         break 'outer;
                     }
-                    let rendered = line.render(format_text);
-                    let line_view = if line.text_len() > width as usize {
-                        &rendered[..width as usize]
-                    } else {
-                        &rendered
-                    };
-                    content.push(line_view.to_string());
+                    // lines are wrapped or cut at the width by the server
+                    content.push(line.render(|tf| format_text(&state.theme, tf)));
                     i += 1;
                 }
             }
@@ -224,12 +295,13 @@ impl Term {
         let mut stdout = io::stdout();
         let (width_w, height_w) = self.last_size;
 
+        let theme = self.connection.state().theme;
         let width_h = hint.text[0].plain().chars().count();
         let x = width_w - width_h as u16;
         let mut y = height_w - hint.text.len() as u16;
         for line in &hint.text {
             // TODO clamp to screen size (or less?)
-            let text = line.render(format_text);
+            let text = line.render(|tf| format_text(&theme, tf));
             queue!(
                 stdout,
                 cursor::MoveTo(x, y),
@@ -248,6 +320,7 @@ impl Term {
     fn draw_menu(&mut self, menu: &Menu) -> CTResult<()> {
         let mut stdout = io::stdout();
         let (width, height) = self.last_size;
+        let theme = self.connection.state().theme;
         let title = format!("{}:{}", menu.title, menu.search);
         let padding = " ".repeat(width as usize - title.len());
 
@@ -263,15 +336,12 @@ impl Term {
                 if i == display_size {
                     break;
                 }
-                let item = &menu.entries[i].text.render(|tf| match tf.face {
-                    Face::Match => style(&tf.text).underlined().to_string(),
-                    _ => tf.text.to_owned(),
-                });
+                let item = &menu.entries[i].text.render(|tf| format_text(&theme, tf));
 
                 let item_view = if item.len() > width as usize {
                     &item[..width as usize]
                 } else {
-                    &item
+                    item
                 };
                 let item_print = if i == menu.selected {
                     PrintStyledContent(style(item_view).reverse())
@@ -295,6 +365,7 @@ impl Term {
         let (width, height) = self.last_size;
         let state = self.connection.state();
 
+        let theme = state.theme;
         let echo = state.echo.unwrap_or_default();
         let status = state
             .status
@@ -303,15 +374,19 @@ impl Term {
             .collect::<Vec<String>>()
             .join("·");
         let text = if echo.text_len() >= width as usize {
-            echo.render(format_text)
+            echo.render(|tf| format_text(&theme, tf))
         } else if echo.text_len() + status.len() >= width as usize {
             let skip = echo.text_len() - width as usize + 1;
-            format!("{} {}", echo.render(format_text), &status[skip..])
+            format!(
+                "{} {}",
+                echo.render(|tf| format_text(&theme, tf)),
+                &status[skip..]
+            )
         } else {
             let padding = width as usize - echo.text_len() - status.len();
             format!(
                 "{}{}{}",
-                echo.render(format_text),
+                echo.render(|tf| format_text(&theme, tf)),
                 " ".repeat(padding),
                 status
             )
@@ -332,6 +407,7 @@ impl Term {
         let current = (w, h);
         if self.last_size != current {
             self.last_size = current;
+            self.connection.resize(w as usize, view_height(current));
             match self.connection.state().menu {
                 Some(menu) => self.draw_menu(&menu)?,
                 None => self.draw_view()?,
@@ -417,6 +493,16 @@ impl Term {
                     ctrl: true,
                     ..
                 } => self.do_menu("", ""),
+                KeyEvent {
+                    key: Key::Char('s'),
+                    ctrl: true,
+                    ..
+                } => self.connection.write(false),
+                KeyEvent {
+                    key: Key::Char('s'),
+                    alt: true,
+                    ..
+                } => self.connection.session_save(None),
                 KeyEvent {
                     key: Key::Char('v'),
                     ctrl: true,
@@ -433,6 +519,17 @@ impl Term {
                 k @ KeyEvent {
                     key: Key::Escape, ..
                 } => self.connection.keys(k),
+                k @ KeyEvent {
+                    key: Key::Enter, ..
+                } => self.connection.keys(k),
+                k @ KeyEvent {
+                    key: Key::Backspace,
+                    ..
+                } => self.connection.keys(k),
+                k @ KeyEvent {
+                    key: Key::PageUp | Key::PageDown,
+                    ..
+                } => self.connection.keys(k),
                 _ => {}
             }
         }
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Tells which of the open files may have been changed by another program. Their directories
+/// are watched with the native mechanism of the platform, when watching fails every file is
+/// reported each time, leaving the comparison of modification times to tell the real changes.
+pub struct FileWatcher {
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<notify::Event>>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        let (sender, events) = channel();
+        FileWatcher {
+            watcher: notify::recommended_watcher(sender)
+                .map_err(|e| log::warn!("file watching unavailable, polling instead: {}", e))
+                .ok(),
+            events,
+            dirs: HashSet::new(),
+        }
+    }
+
+    /// Returns the files among `files` that may have changed since the last call.
+    pub fn changes(&mut self, files: &[PathBuf]) -> Vec<PathBuf> {
+        self.watched_changes(files)
+            .unwrap_or_else(|| files.to_vec())
+    }
+
+    fn watched_changes(&mut self, files: &[PathBuf]) -> Option<Vec<PathBuf>> {
+        let watcher = self.watcher.as_mut()?;
+        let dirs = files
+            .iter()
+            .filter_map(|f| f.parent())
+            .collect::<HashSet<_>>();
+        self.dirs.retain(|dir| {
+            let keep = dirs.contains(dir.as_path());
+            if !keep {
+                let _ = watcher.unwatch(dir);
+            }
+            keep
+        });
+        // files in newly watched directories may have changed before the watch started
+        let mut unsure = HashSet::new();
+        for dir in dirs {
+            if self.dirs.contains(dir) {
+                continue;
+            }
+            unsure.insert(dir);
+            match watcher.watch(dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.dirs.insert(dir.to_owned());
+                }
+                Err(e) => log::warn!("cannot watch {}: {}", dir.display(), e),
+            }
+        }
+
+        let mut paths = HashSet::new();
+        let mut complete = true;
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if event.need_rescan() => complete = false,
+                Ok(event) => {
+                    for path in event.paths {
+                        // a watched directory that is gone is watched again if it comes back
+                        if matches!(event.kind, EventKind::Remove(_)) && self.dirs.remove(&path) {
+                            complete = false;
+                        }
+                        paths.insert(path);
+                    }
+                }
+                Err(e) => {
+                    log::warn!("file watching error: {}", e);
+                    complete = false;
+                }
+            }
+        }
+        if !complete {
+            return Some(files.to_vec());
+        }
+        Some(
+            files
+                .iter()
+                .filter(|f| {
+                    paths.contains(*f) || f.parent().map(|d| unsure.contains(d)).unwrap_or(true)
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn external_change() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("ced-test-{}-watcher", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let watched = dir.join("watched");
+        let other = dir.join("other");
+        fs::write(&watched, "before").unwrap();
+        fs::write(&other, "before").unwrap();
+
+        let mut watcher = FileWatcher::new();
+        let files = vec![watched.clone(), other.clone()];
+        // the first look at a directory reports all of its files
+        assert_eq!(watcher.changes(&files), files);
+        assert!(watcher.changes(&files).is_empty());
+
+        fs::write(&watched, "after").unwrap();
+        // events come from another thread
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut changed = Vec::new();
+        while changed.is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            changed = watcher.changes(&files);
+        }
+        assert_eq!(changed, vec![watched]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn polling_fallback() {
+        let files = vec![PathBuf::from("/nowhere/file")];
+        let mut watcher = FileWatcher::new();
+        assert_eq!(watcher.changes(&files), files);
+        assert_eq!(watcher.changes(&files), files);
+    }
+}
@@ -63,6 +63,7 @@ impl SyncClient {
                 break;
             }
             if let ClientEvent::Notification(noti) = ev.unwrap() {
+                // patches are never asked for, views are sent whole
                 if let "view" = noti.method.as_str() {
                     self.state.view = noti.params().unwrap().unwrap();
                     break; // FIXME don't stop on special case
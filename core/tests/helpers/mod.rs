@@ -1,10 +1,14 @@
+// shared by the test crates, each using only some of the helpers
+#![allow(dead_code)]
+
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
 
 use async_channel::{bounded, Receiver};
 use ced::editor::Editor;
 use ced::remote::jsonrpc::Notification;
-use ced::remote::protocol::notifications::ViewParams;
+use ced::remote::protocol::notifications::{ThemeParams, ViewParams, ViewPatchParams};
 use ced::server::BroadcastMessage;
 use futures_lite::*;
 
@@ -20,6 +24,10 @@ pub fn root() -> PathBuf {
 pub struct State {
     rx: Receiver<BroadcastMessage>,
     pub view: ViewParams,
+    pub view_revision: usize,
+    // every view received, patches being made against any acknowledged one
+    views: HashMap<usize, ViewParams>,
+    pub theme: ThemeParams,
 }
 
 impl State {
@@ -27,12 +35,32 @@ impl State {
         State {
             rx,
             view: ViewParams::default(),
+            view_revision: 0,
+            views: HashMap::new(),
+            theme: ThemeParams::default(),
         }
     }
 
     fn update(&mut self, message: &Notification) {
+        if message.method.as_str() == "theme" {
+            self.theme = message.params().unwrap().unwrap();
+        }
         if message.method.as_str() == "view" {
-            self.view = message.params().unwrap().unwrap()
+            self.view = message.params().unwrap().unwrap();
+            self.view_revision = 0;
+        }
+        if message.method.as_str() == "view-patch" {
+            let patch: ViewPatchParams = message.params().unwrap().unwrap();
+            let base = match patch.base {
+                Some(base) => self
+                    .views
+                    .get(&base)
+                    .expect("patch against an unknown view"),
+                None => &ViewParams::new(),
+            };
+            self.view = patch.apply(base);
+            self.view_revision = patch.revision;
+            self.views.insert(patch.revision, self.view.clone());
         }
     }
 
@@ -0,0 +1,63 @@
+mod helpers;
+
+use ced::remote::protocol::Face;
+
+const CLIENT_ID: usize = 1;
+
+/// Faces of the first line shown to the client, with their text.
+fn faces(editor: &helpers::SequentialEditor) -> Vec<(String, Face)> {
+    editor.state().view[0].lenses[0].lines[0]
+        .iter()
+        .map(|tf| (tf.text.to_owned(), tf.face.clone()))
+        .collect()
+}
+
+fn exec(editor: &mut helpers::SequentialEditor, script: &str) {
+    editor.command_exec(CLIENT_ID, &script.to_owned()).unwrap();
+    editor.step();
+}
+
+#[test]
+fn highlight() {
+    let mut editor = helpers::SequentialEditor::new();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+
+    exec(
+        &mut editor,
+        r#"
+        editor:scratch(env.client, "code", "let n = 1; // one\n")
+        editor:set_grammar("code", "rust")
+        "#,
+    );
+    let text = |s: &str| s.to_owned();
+    assert_eq!(
+        faces(&editor),
+        vec![
+            (text("l"), Face::Selection),
+            (text("et"), Face::Keyword),
+            (text(" n = "), Face::Default),
+            (text("1"), Face::Number),
+            (text("; "), Face::Default),
+            (text("// one"), Face::Comment),
+            (text(" "), Face::Default),
+        ]
+    );
+
+    // the edited line is highlighted again
+    exec(
+        &mut editor,
+        r#"editor:insert_before(env.client, "fn f() { \"s\" }\n")"#,
+    );
+    let lines = &editor.state().view[0].lenses[0].lines;
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].iter().any(|tf| tf.face == Face::Comment));
+    assert!(lines[0]
+        .iter()
+        .any(|tf| tf.text == "\"s\"" && tf.face == Face::String));
+
+    exec(&mut editor, r#"editor:set_grammar("code", nil)"#);
+    assert!(faces(&editor)
+        .iter()
+        .all(|(_, face)| *face == Face::Default || *face == Face::Selection));
+}
@@ -0,0 +1,76 @@
+mod helpers;
+
+use ced::remote::protocol::{requests, KeyEvent};
+
+const CLIENT_ID: usize = 1;
+
+fn keys(keys: &[&str]) -> Vec<KeyEvent> {
+    keys.iter().map(|&k| KeyEvent::from(k)).collect()
+}
+
+fn replay(register: Option<&str>, count: usize) -> requests::MacroReplayParams {
+    requests::MacroReplayParams {
+        client: None,
+        register: register.map(str::to_owned),
+        count,
+    }
+}
+
+fn assert_content(editor: &mut helpers::SequentialEditor, content: &str) {
+    let script = format!(
+        r#"
+        editor:move_to_begin(env.client)
+        editor:move_to_end(env.client, true)
+        local text = editor:get_context(env.client).selections["macro"][1].text
+        assert(text == {:?}, text)
+        "#,
+        content
+    );
+    editor.command_exec(CLIENT_ID, &script).unwrap();
+}
+
+#[test]
+fn record_and_replay() {
+    let mut editor = helpers::SequentialEditor::new();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+    editor
+        .command_edit(
+            CLIENT_ID,
+            &requests::EditParams {
+                name: "macro".to_owned(),
+                scratch: true,
+            },
+        )
+        .unwrap();
+    editor.step();
+
+    editor
+        .command_keys(CLIENT_ID, &keys(&["s-q", "i", "x", "esc", "s-q"]))
+        .unwrap();
+    assert_content(&mut editor, "x\n");
+    editor.command_keys(CLIENT_ID, &keys(&["g", "h"])).unwrap();
+
+    editor
+        .command_macro_replay(CLIENT_ID, &replay(None, 2))
+        .unwrap();
+    assert_content(&mut editor, "xxx\n");
+
+    // a replay is undone at once
+    editor.command_undo(CLIENT_ID).unwrap();
+    assert_content(&mut editor, "x\n");
+
+    editor
+        .command_macro_replay(CLIENT_ID, &replay(None, 0))
+        .unwrap();
+    assert_content(&mut editor, "xx\n");
+
+    assert!(editor
+        .command_macro_replay(CLIENT_ID, &replay(Some("z"), 1))
+        .is_err());
+    let mut other_client = replay(None, 1);
+    other_client.client = Some(CLIENT_ID + 1);
+    assert!(editor
+        .command_macro_replay(CLIENT_ID, &other_client)
+        .is_err());
+}
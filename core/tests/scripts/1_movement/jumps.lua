@@ -0,0 +1,28 @@
+editor:scratch(env.client, "testing", "one\ntwo\nthree\n")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+-- small moves are left out of the jump list
+editor:move_down(env.client)
+editor:move_to_end(env.client)
+editor:search(env.client, "thr", true)
+assert_sel("thr")
+assert(editor:jump_back(env.client))
+assert_sel("\n")
+assert(editor:jump_back(env.client))
+assert_sel("t")
+assert(editor:jump_forward(env.client))
+assert(editor:jump_forward(env.client))
+assert_sel("thr")
+assert(not editor:jump_forward(env.client))
+
+-- a new jump drops the positions gone back over
+assert(editor:jump_back(env.client))
+assert(editor:jump_back(env.client))
+editor:move_to_begin(env.client)
+assert(not editor:jump_forward(env.client))
+assert(editor:jump_back(env.client))
+assert_sel("t")
@@ -0,0 +1,38 @@
+editor:scratch(env.client, "testing", "    let x = (a + b[1]);\n\nfoo-bar baz\n")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+editor:move_to_first_non_blank(env.client)
+assert_sel("l")
+editor:move_next_word_end(env.client, true)
+assert_sel("let")
+editor:move_next_word_start(env.client)
+assert_sel("x")
+editor:move_next_word_start(env.client)
+assert_sel("=")
+editor:move_next_word_start(env.client)
+assert_sel("(")
+editor:move_to_matching_bracket(env.client, true)
+assert_sel("(a + b[1])")
+editor:move_previous_word_start(env.client)
+assert_sel("]")
+
+editor:move_next_paragraph(env.client)
+assert_sel("\n")
+editor:move_next_word_start(env.client)
+assert_sel("f")
+editor:move_next_word_end(env.client)
+assert_sel("o")
+editor:set_word_chars("_-")
+editor:move_previous_word_start(env.client)
+editor:move_next_word_end(env.client)
+assert_sel("r")
+editor:move_previous_big_word_start(env.client)
+assert_sel("f")
+editor:move_next_big_word_end(env.client, true)
+assert_sel("foo-bar")
+editor:move_previous_paragraph(env.client)
+assert_sel("\n")
@@ -0,0 +1,43 @@
+editor:scratch(env.client, "testing", "one two\nthree four\nfive\n")
+
+local function sels()
+    local texts = {}
+    for _, sel in ipairs(editor:get_context(env.client).selections.testing) do
+        table.insert(texts, sel.text)
+    end
+    return table.concat(texts, ",")
+end
+
+local function assert_sels(c)
+    local got = sels()
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+editor:move_to_end(env.client, true)
+assert(editor:split_lines(env.client) == 3)
+assert_sels("one two\n,three four\n,five\n")
+editor:keep_main_selection(env.client)
+assert_sels("one two\n")
+
+assert(editor:split_matching(env.client, "\\s") == 2)
+assert_sels("one,two")
+editor:rotate_selections(env.client, false)
+assert_sels("two,one")
+editor:rotate_selections(env.client, true)
+assert_sels("one,two")
+editor:drop_main_selection(env.client)
+assert_sels("two")
+editor:drop_main_selection(env.client)
+assert_sels("two")
+
+assert(editor:duplicate_selections(env.client, false))
+assert_sels("two,e f")
+-- the copy of the first one merges with the second one, the last line being too short
+assert(not editor:duplicate_selections(env.client, false))
+assert_sels("two,e f")
+assert(not editor:duplicate_selections(env.client, true))
+assert_sels("two,e f")
+
+-- selections meeting after a movement merge
+editor:move_to_begin(env.client)
+assert_sels("o")
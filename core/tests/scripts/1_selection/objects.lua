@@ -0,0 +1,32 @@
+editor:scratch(env.client, "testing", "fn f(a, [b, c]) {\n    \"x y\"\n}\n")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+editor:move_next_word_start(env.client)
+editor:move_next_word_start(env.client)
+editor:move_next_word_start(env.client)
+assert_sel("a")
+assert(editor:select_object(env.client, "parentheses", true))
+assert_sel("a, [b, c]")
+assert(editor:select_object(env.client, "parentheses", false))
+assert_sel("(a, [b, c])")
+assert(not editor:select_object(env.client, "brackets", false))
+assert_sel("(a, [b, c])")
+
+editor:move_down(env.client)
+editor:move_to_first_non_blank(env.client)
+assert(editor:select_object(env.client, "double_quotes", true))
+assert_sel("x y")
+assert(editor:select_object(env.client, "word", false))
+assert_sel(" y")
+assert(editor:select_object(env.client, "indent", true))
+assert_sel("    \"x y\"\n")
+assert(editor:select_object(env.client, "paragraph", true))
+assert_sel("fn f(a, [b, c]) {\n    \"x y\"\n}\n")
+assert(not editor:select_object(env.client, "paragraph", true))
+
+local ok = pcall(editor.select_object, editor, env.client, "sentence", true)
+assert(not ok)
@@ -0,0 +1,17 @@
+editor:scratch(env.client, "testing", "12\n34")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+editor:delete_before(env.client)
+assert_sel("1")
+editor:move_down(env.client)
+editor:delete_before(env.client)
+assert_sel("3")
+editor:delete_before(env.client)
+assert_sel("3")
+editor:move_to_begin(env.client)
+editor:move_to_end(env.client, true)
+assert_sel("134")
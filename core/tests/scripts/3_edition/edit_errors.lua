@@ -0,0 +1,12 @@
+local ok, err = pcall(editor.edit, editor, env.client, "no/such/dir/file.txt")
+assert(not ok)
+assert(string.find(tostring(err), "file not found"), tostring(err))
+
+ok, err = pcall(editor.edit, editor, env.client, "tests")
+assert(not ok)
+assert(string.find(tostring(err), "is a directory"), tostring(err))
+
+-- a path that does not exist yet opens an empty buffer
+editor:edit(env.client, "tests/not-created-yet.txt")
+local sel = editor:get_context(env.client).selections["tests/not-created-yet.txt"][1]
+assert(sel.text == "\n", string.format("%q", sel.text))
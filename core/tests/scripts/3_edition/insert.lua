@@ -0,0 +1,39 @@
+editor:scratch(env.client, "testing", "123\n456")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+local function assert_content(c)
+    editor:move_to_begin(env.client)
+    editor:move_to_end(env.client, true)
+    assert_sel(c)
+    editor:move_to_begin(env.client)
+end
+
+editor:insert_text(env.client, "ab")
+assert_sel("1")
+assert_content("ab123\n456")
+
+editor:move_right(env.client)
+editor:move_right(env.client, true)
+assert_sel("b1")
+editor:insert_before(env.client, "<")
+assert_sel("b1")
+editor:insert_after(env.client, ">")
+assert_sel("b1")
+assert_content("a<b1>23\n456")
+
+editor:move_down(env.client)
+editor:move_right(env.client, true)
+assert_sel("45")
+editor:replace_selection(env.client, "four five")
+assert_sel("four five")
+assert_content("a<b1>23\nfour five6")
+
+editor:move_down(env.client)
+editor:move_to_line_end(env.client)
+editor:insert_text(env.client, "\n7")
+assert_sel("6")
+assert_content("a<b1>23\nfour five\n76")
@@ -0,0 +1,25 @@
+editor:scratch(env.client, "testing", "end")
+
+local handler = editor.clients[tonumber(env.client)].key_handler
+
+local function key(display, text)
+    handler:handle({ display = display, value = display, text = text })
+end
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+key("i", "i")
+key("s-h", "H")
+key("i", "i")
+key("ret")
+key("x", "x")
+key("bkspc")
+key("esc")
+assert_sel("e")
+
+-- back in normal mode, keys move the cursor again
+key("s-k", "K")
+assert_sel("Hi\ne")
@@ -0,0 +1,25 @@
+editor:scratch(env.client, "testing", "one two\n")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+assert(not editor:goto_mark(env.client, "a"))
+editor:search(env.client, "two")
+assert(editor:set_mark(env.client, "a"))
+assert(editor:marks("testing").a == 6)
+
+-- the mark stays on the same text
+editor:move_to_begin(env.client)
+editor:insert_before(env.client, "zero ")
+assert(editor:marks("testing").a == 11)
+assert(editor:goto_mark(env.client, "a"))
+assert_sel("o")
+editor:move_to_begin(env.client)
+editor:move_to_end(env.client, true)
+editor:delete_selection(env.client)
+assert(editor:marks("testing").a == 0)
+
+local ok = pcall(editor.set_mark, editor, env.client, "ab")
+assert(not ok)
@@ -0,0 +1,66 @@
+editor:scratch(env.client, "testing", "one two\n")
+
+local function sels()
+    local texts = {}
+    for _, sel in ipairs(editor:get_context(env.client).selections.testing) do
+        table.insert(texts, sel.text)
+    end
+    return table.concat(texts, ",")
+end
+
+local function assert_sels(c)
+    local got = sels()
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+local function assert_content(c)
+    editor:move_to_begin(env.client)
+    editor:move_to_end(env.client, true)
+    assert_sels(c)
+end
+
+-- each selection gets its own value, in order
+editor:move_to_end(env.client, true)
+editor:split_matching(env.client, "\\s")
+assert(editor:yank(env.client) == 2)
+assert(editor:register()[2] == "two")
+editor:rotate_selections(env.client, false)
+assert(editor:paste_after(env.client))
+assert_sels("one,two")
+assert_content("onetwo twoone\n")
+
+-- deleting fills the register unless dropped
+editor:move_to_begin(env.client)
+editor:move_next_word_end(env.client, true)
+assert(editor:delete_selection(env.client, "a")[1] == "onetwo")
+assert(editor:register("a")[1] == "onetwo")
+editor:delete_selection(env.client, "_")
+assert(editor:register("_")[1] == nil)
+assert(editor:paste_before(env.client, "a"))
+assert_sels("onetwo")
+editor:set_register("b", { "1" })
+assert(editor:replace_with_register(env.client, "b"))
+assert_content("1twoone\n")
+assert(not editor:paste_after(env.client, "z"))
+assert(not pcall(editor.yank, editor, env.client, "ab"))
+
+editor:move_to_begin(env.client)
+editor:move_next_word_end(env.client, true)
+assert(editor:split_matching(env.client, "o") == 2)
+local deleted = editor:delete_selection(env.client)
+assert(#deleted == 2 and deleted[1] == "1tw" and deleted[2] == "ne")
+assert_content("oo\n")
+
+-- the clipboard register goes through the configured commands
+local file = os.tmpname()
+editor:set_clipboard("cat > " .. file, "cat " .. file)
+editor:move_to_begin(env.client)
+assert(editor:yank(env.client, "+") == 1)
+assert(editor:register("+")[1] == "o")
+local f = io.open(file)
+assert(f:read("a") == "o")
+f:close()
+os.remove(file)
+assert(not pcall(editor.register, editor, "+"))
+editor:set_clipboard(nil, nil)
+assert(editor:register("+")[1] == nil)
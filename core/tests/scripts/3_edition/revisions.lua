@@ -0,0 +1,25 @@
+editor:scratch(env.client, "testing", "text\n")
+
+local function assert_text(t)
+    editor:move_to_begin(env.client)
+    editor:move_to_end(env.client, true)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == t, string.format("%q != %q", got, t))
+end
+
+editor:insert_text(env.client, "a ")
+editor:undo(env.client)
+editor:move_to_begin(env.client)
+editor:insert_text(env.client, "the ")
+assert_text("the text\n")
+
+-- the undone branch is still reachable
+assert(editor:goto_revision(env.client, "testing", 1))
+assert_text("a text\n")
+assert(not editor:goto_revision(env.client, "testing", 1))
+assert(not pcall(editor.goto_revision, editor, env.client, "unknown", 1))
+
+assert(editor:earlier(env.client, 3600))
+assert_text("text\n")
+assert(editor:later(env.client, 3600))
+assert_text("the text\n")
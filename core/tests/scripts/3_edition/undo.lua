@@ -0,0 +1,34 @@
+editor:scratch(env.client, "testing", "hello\n")
+
+local function assert_text(t)
+    editor:move_to_begin(env.client)
+    editor:move_to_end(env.client, true)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == t, string.format("%q != %q", got, t))
+end
+
+assert(not editor:undo(env.client))
+
+editor:insert_text(env.client, "a")
+editor:insert_text(env.client, "b")
+assert_text("abhello\n")
+
+assert(editor:undo(env.client))
+assert_text("ahello\n")
+assert(editor:undo(env.client))
+assert_text("hello\n")
+assert(not editor:undo(env.client))
+
+assert(editor:redo(env.client))
+assert(editor:redo(env.client))
+assert_text("abhello\n")
+assert(not editor:redo(env.client))
+
+-- grouped edits are undone at once
+editor:move_to_begin(env.client)
+editor:begin_undo_group(env.client)
+editor:insert_text(env.client, "x")
+editor:insert_text(env.client, "y")
+editor:end_undo_group(env.client)
+assert(editor:undo(env.client))
+assert_text("abhello\n")
@@ -0,0 +1,7 @@
+editor:scratch(env.client, "testing", "123")
+
+-- scratch buffers have nowhere to be written to
+assert(not pcall(function()
+    editor:write(env.client, "testing")
+end))
+assert(editor:write(env.client) == 0)
@@ -0,0 +1,45 @@
+editor:scratch(env.client, "testing", "Foo bar\nfoo baz\nFOO\n")
+
+local function assert_sel(c)
+    local got = editor:get_context(env.client).selections.testing[1].text
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+local function assert_offsets(anchor, cursor)
+    local sel = editor:get_context(env.client).selections.testing[1]
+    assert(sel.anchor.offset == anchor, string.format("anchor %d != %d", sel.anchor.offset, anchor))
+    assert(sel.cursor.offset == cursor, string.format("cursor %d != %d", sel.cursor.offset, cursor))
+end
+
+assert(editor:search(env.client, "ba."))
+assert_sel("bar")
+assert(editor:search_next(env.client))
+assert_sel("baz")
+-- wraps around to the first match
+assert(editor:search_next(env.client))
+assert_sel("bar")
+assert(editor:search_next(env.client, true))
+assert_sel("baz")
+
+-- smart case: insensitive unless the pattern has an uppercase letter
+editor:move_to_begin(env.client)
+assert(editor:search(env.client, "foo"))
+assert_offsets(8, 10)
+assert(editor:search(env.client, "FOO"))
+assert_offsets(16, 18)
+assert(editor:search(env.client, "foo", false, false, "insensitive"))
+assert_offsets(0, 2)
+assert(editor:search(env.client, "foo", true, false, "sensitive"))
+assert_offsets(10, 8)
+
+-- extending keeps the anchor
+editor:move_to_begin(env.client)
+assert(editor:search(env.client, "z", false, true))
+assert_sel("Foo bar\nfoo baz")
+
+assert(not editor:search(env.client, "nowhere"))
+assert_sel("Foo bar\nfoo baz")
+
+local ok, err = pcall(editor.search, editor, env.client, "(")
+assert(not ok)
+assert(string.find(tostring(err), "invalid pattern"), tostring(err))
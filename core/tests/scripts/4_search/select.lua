@@ -0,0 +1,32 @@
+editor:scratch(env.client, "testing", "one two\nthree four\nfive\n")
+
+local function sels()
+    local texts = {}
+    for _, sel in ipairs(editor:get_context(env.client).selections.testing) do
+        table.insert(texts, sel.text)
+    end
+    return table.concat(texts, ",")
+end
+
+local function assert_sels(c)
+    local got = sels()
+    assert(got == c, string.format("%q != %q", got, c))
+end
+
+editor:move_to_end(env.client, true)
+assert(editor:select_matches(env.client, "\\w+") == 5)
+assert_sels("one,two,three,four,five")
+
+assert(editor:keep_matching(env.client, "o") == 2)
+assert_sels("one,two,four")
+assert(editor:remove_matching(env.client, "^t") == 1)
+assert_sels("one,four")
+
+-- nothing is left without a selection
+assert(editor:keep_matching(env.client, "nothing") == 0)
+assert_sels("one,four")
+assert(editor:select_matches(env.client, "x") == 0)
+assert_sels("one,four")
+
+assert(editor:select_matches(env.client, "[aeiou]") == 4)
+assert_sels("o,e,o,u")
@@ -0,0 +1,111 @@
+mod helpers;
+
+use ced::remote::protocol::requests;
+
+const CLIENT_ID: usize = 1;
+
+/// First line number and number of lines of the lens shown to the client.
+fn shown(editor: &helpers::SequentialEditor) -> (usize, usize) {
+    let lens = &editor.state().view[0].lenses[0];
+    (lens.first_line_num, lens.lines.len())
+}
+
+fn exec(editor: &mut helpers::SequentialEditor, script: &str) {
+    editor.command_exec(CLIENT_ID, &script.to_owned()).unwrap();
+    editor.step();
+}
+
+#[test]
+fn scroll() {
+    let mut editor = helpers::SequentialEditor::new();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+
+    let content = (1..=20).map(|i| format!("{}\n", i)).collect::<String>();
+    exec(
+        &mut editor,
+        &format!(r#"editor:scratch(env.client, "long", {:?})"#, content),
+    );
+    assert_eq!(shown(&editor), (1, 20));
+
+    editor
+        .command_resize(
+            CLIENT_ID,
+            &requests::ResizeParams {
+                height: 5,
+                width: 80,
+            },
+        )
+        .unwrap();
+    editor.step();
+    // a row is left to the header
+    assert_eq!(shown(&editor), (1, 4));
+
+    exec(
+        &mut editor,
+        "for _ = 1, 7 do editor:move_down(env.client, false) end",
+    );
+    assert_eq!(shown(&editor), (5, 4));
+
+    // the cursor is left out of sight
+    exec(&mut editor, "editor:scroll_pages(env.client, 1)");
+    assert_eq!(shown(&editor), (10, 4));
+
+    exec(&mut editor, "editor:center_cursor(env.client)");
+    assert_eq!(shown(&editor), (6, 4));
+
+    exec(&mut editor, "editor:scroll(env.client, 100)");
+    assert_eq!(shown(&editor), (20, 1));
+    exec(&mut editor, "editor:scroll(env.client, -100)");
+    assert_eq!(shown(&editor), (1, 4));
+
+    exec(&mut editor, "editor:move_to_end(env.client, false)");
+    assert_eq!(shown(&editor), (17, 4));
+}
+
+fn rows(editor: &helpers::SequentialEditor) -> Vec<String> {
+    let lens = &editor.state().view[0].lenses[0];
+    lens.lines.iter().map(|row| row.plain()).collect()
+}
+
+#[test]
+fn long_lines() {
+    let mut editor = helpers::SequentialEditor::new();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+
+    exec(
+        &mut editor,
+        r#"editor:scratch(env.client, "long", "one two\tthree four\n")"#,
+    );
+    editor
+        .command_resize(
+            CLIENT_ID,
+            &requests::ResizeParams {
+                height: 5,
+                width: 8,
+            },
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(rows(&editor), vec!["one two "]);
+
+    // the cursor is followed horizontally
+    exec(&mut editor, "editor:move_to_line_end(env.client, false)");
+    assert_eq!(rows(&editor), vec!["ree four"]);
+    exec(&mut editor, "editor:scroll_columns(env.client, -100)");
+    assert_eq!(rows(&editor), vec!["one two "]);
+
+    exec(&mut editor, r#"editor:set_wrap(env.client, "word")"#);
+    assert_eq!(rows(&editor), vec!["one two ", "three ", "four "]);
+    assert_eq!(editor.state().view[0].lenses[0].continuations, vec![1, 2]);
+
+    exec(
+        &mut editor,
+        r#"
+        editor:set_wrap(env.client, "char")
+        editor:set_tab_width(3)
+        "#,
+    );
+    assert_eq!(rows(&editor), vec!["one two ", " three f", "our "]);
+}
@@ -0,0 +1,86 @@
+mod helpers;
+
+use ced::remote::protocol::{requests, Color, Face};
+
+const CLIENT_ID: usize = 1;
+
+fn keyword_color(editor: &helpers::SequentialEditor) -> Option<Color> {
+    editor.state().theme.style(&Face::Keyword).foreground
+}
+
+#[test]
+fn switch_theme() {
+    let mut editor = helpers::SequentialEditor::new();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+    assert_eq!(editor.state().theme.name, "default");
+    assert_eq!(keyword_color(&editor), Some(Color::Yellow));
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"editor:set_theme(env.client, "light")"#.to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(editor.state().theme.name, "light");
+    assert_eq!(keyword_color(&editor), Some(Color::DarkYellow));
+    assert!(editor.state().theme.style(&Face::Keyword).bold);
+
+    // from the menu
+    editor
+        .command_menu(
+            CLIENT_ID,
+            &requests::MenuParams {
+                command: "theme".to_owned(),
+                search: String::new(),
+            },
+        )
+        .unwrap();
+    editor
+        .command_menu_select(
+            CLIENT_ID,
+            &requests::MenuSelectParams {
+                command: "theme".to_owned(),
+                choice: "dark".to_owned(),
+            },
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(editor.state().theme.name, "dark");
+    assert_eq!(keyword_color(&editor), Some(Color::Rgb(0xff, 0xaf, 0x00)));
+
+    assert!(editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"editor:set_theme(env.client, "nope")"#.to_owned(),
+        )
+        .is_err());
+}
+
+#[test]
+fn theme_from_config_dir() {
+    let dir = std::env::temp_dir().join(format!("ced-test-{}-config", std::process::id()));
+    std::fs::create_dir_all(dir.join("themes")).unwrap();
+    std::fs::write(
+        dir.join("themes").join("mine.toml"),
+        "[faces.keyword]\nforeground = \"dark_green\"\n",
+    )
+    .unwrap();
+
+    let mut editor = helpers::SequentialEditor::new();
+    editor.load_config(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"editor:set_theme(env.client, "mine")"#.to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(editor.state().theme.name, "mine");
+    assert_eq!(keyword_color(&editor), Some(Color::DarkGreen));
+}
@@ -96,3 +96,135 @@ fn remove_and_delete() {
 
     helpers::assert_buffers(&editor.state().view, vec![String::from("oh hi!")]);
 }
+
+fn lens_lines(editor: &helpers::SequentialEditor, buffer: &str) -> (usize, usize) {
+    let item = editor
+        .state()
+        .view
+        .iter()
+        .find(|item| item.buffer == buffer)
+        .unwrap();
+    (item.start, item.end)
+}
+
+fn editor_with_range_lens() -> helpers::SequentialEditor {
+    let mut editor = helpers::SequentialEditor::new();
+    editor.add_client(CLIENT_ID);
+    editor.step();
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"
+            editor:scratch(env.client, "lines", "a\nb\nc\nd\n")
+            editor:scratch(env.client, "other", "x\n")
+            editor:add_lines_to_view(env.client, "lines", 3, 3)
+            "#
+            .to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(lens_lines(&editor, "lines"), (3, 3));
+    editor
+}
+
+#[test]
+fn edit_above_range() {
+    let mut editor = editor_with_range_lens();
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"
+            local view = editor:get_context(env.client).view
+            editor:insert_before(env.client, "new\n")
+            local moved = editor:get_context(env.client).view
+            assert(moved.id == view.id, moved.id)
+            assert(moved.key ~= view.key, moved.key)
+            "#
+            .to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(lens_lines(&editor, "lines"), (4, 4));
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"
+            editor:move_to_begin(env.client)
+            editor:move_to_end(env.client, true)
+            editor:delete_selection(env.client)
+            "#
+            .to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(lens_lines(&editor, "lines"), (1, 1));
+}
+
+#[test]
+fn edit_inside_range() {
+    let mut editor = editor_with_range_lens();
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"
+            editor:search(env.client, "c")
+            editor:insert_after(env.client, "\nmore")
+            "#
+            .to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(lens_lines(&editor, "lines"), (3, 4));
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"
+            editor:move_to_end(env.client)
+            editor:insert_before(env.client, "e\n")
+            "#
+            .to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    assert_eq!(lens_lines(&editor, "lines"), (3, 4));
+}
+
+#[test]
+fn patch_acknowledged_view() {
+    let mut editor = editor_with_range_lens();
+    // views are sent whole until patches are asked for
+    assert_eq!(editor.state().view_revision, 0);
+    editor.command_view_patches(CLIENT_ID).unwrap();
+    editor
+        .command_exec(CLIENT_ID, &"editor:move_to_end(env.client)".to_owned())
+        .unwrap();
+    editor.step();
+    let revision = editor.state().view_revision;
+    assert_eq!(revision, 1);
+    editor.command_view_ack(CLIENT_ID, &revision).unwrap();
+    assert!(editor.command_view_ack(CLIENT_ID, &0).is_err());
+
+    editor
+        .command_exec(
+            CLIENT_ID,
+            &r#"
+            editor:search(env.client, "c")
+            editor:insert_after(env.client, "\nmore")
+            "#
+            .to_owned(),
+        )
+        .unwrap();
+    editor.step();
+    let lens = &editor.state().view[0].lenses[0];
+    let text = lens
+        .lines
+        .iter()
+        .map(|line| line.iter().map(|tf| tf.text.as_str()).collect::<String>())
+        .collect::<Vec<_>>();
+    assert_eq!(text, vec!["c ", "more "]);
+}
@@ -167,7 +167,7 @@ impl SourceBuilder {
         Ok(builder)
     }
     pub fn source(&self) -> String {
-        vec![
+        [
             self.config.header.clone(),
             String::new(),
             self.uses.join("\n"),
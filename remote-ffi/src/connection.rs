@@ -30,10 +30,10 @@ pub unsafe extern "C" fn ced_connection_create(session: *const c_char) -> *mut C
     std::thread::spawn(|| future::block_on(request_loop));
     let (tx, rx) = mpsc::channel();
     std::thread::spawn(move || {
-        let sync_events = stream::block_on(events);
-        for ev in sync_events {
-            if let Err(err) = tx.send(ev) {
-                dbg!(&err.0);
+        for ev in stream::block_on(events) {
+            // the connection handle is gone along with the receiver
+            if tx.send(ev).is_err() {
+                break;
             }
         }
     });
@@ -43,7 +43,7 @@ pub unsafe extern "C" fn ced_connection_create(session: *const c_char) -> *mut C
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn ced_connection_next_event<'a>(p: *mut CedConnection) -> *mut CedEvent {
+pub unsafe extern "C" fn ced_connection_next_event(p: *mut CedConnection) -> *mut CedEvent {
     let handle: &CedConnection = &*p;
     // TODO switch to this when it is possible to exit cleanly
     // match handle.events.try_recv() {
@@ -60,11 +60,13 @@ pub unsafe extern "C" fn ced_connection_next_event<'a>(p: *mut CedConnection) ->
     //         ptr::null_mut()
     //     }
     // }
-    if let Ok(ev) = handle.events.recv_timeout(Duration::from_secs(5)) {
+    while let Ok(ev) = handle.events.recv_timeout(Duration::from_secs(5)) {
         println!("<-- {:?}", ev);
-        Box::into_raw(Box::new(CedEvent::from(ev)))
-    } else {
-        println!("<-- None");
-        ptr::null_mut()
+        // responses to the requests, the view acknowledgements among them, are skipped
+        if let Some(event) = CedEvent::from_connection(ev) {
+            return Box::into_raw(Box::new(event));
+        }
     }
+    println!("<-- None");
+    ptr::null_mut()
 }
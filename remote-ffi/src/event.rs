@@ -4,11 +4,15 @@ use std::ptr;
 use crate::text::CedTextIterator;
 use crate::{cstring, raw, IndexedIterator};
 use remote::protocol::{
-    notifications::{MenuParamsEntry, StatusParamsItem, ViewParamsItem, ViewParamsLens},
-    Text,
+    notifications::{
+        MenuParamsEntry, StatusParamsItem, ThemeParamsFace, ViewParamsItem, ViewParamsLens,
+    },
+    Color, Text,
 };
 use remote::ConnectionEvent;
 
+// fields are only read from the C side
+#[allow(dead_code)]
 #[repr(C)]
 #[derive(Debug)]
 pub enum CedEvent {
@@ -32,11 +36,21 @@ pub enum CedEvent {
     View {
         items: *mut ViewIterator,
     },
+    Theme {
+        name: *const c_char,
+        faces: *mut ThemeFaceIterator,
+    },
+    /// the connection to the server failed, `message` telling why
+    Error {
+        message: *const c_char,
+    },
 }
 
-impl From<ConnectionEvent> for CedEvent {
-    fn from(event: ConnectionEvent) -> CedEvent {
-        match event {
+impl CedEvent {
+    /// Event shown for a connection event, none for the responses to requests which show
+    /// nothing.
+    pub fn from_connection(event: ConnectionEvent) -> Option<CedEvent> {
+        Some(match event {
             ConnectionEvent::Echo(text) => CedEvent::Echo {
                 message: raw!(CedTextIterator::from(&text)),
             },
@@ -58,9 +72,15 @@ impl From<ConnectionEvent> for CedEvent {
             ConnectionEvent::View(view) => CedEvent::View {
                 items: raw!(ViewIterator::from(&view)),
             },
-            ConnectionEvent::ConnErr(_msg) => todo!(),
-            ConnectionEvent::Noop => todo!(),
-        }
+            ConnectionEvent::Theme(theme) => CedEvent::Theme {
+                name: cstring!(theme.name),
+                faces: raw!(ThemeFaceIterator::from(&theme.faces)),
+            },
+            ConnectionEvent::ConnErr(message) => CedEvent::Error {
+                message: cstring!(message),
+            },
+            ConnectionEvent::Noop => return None,
+        })
     }
 }
 
@@ -103,6 +123,41 @@ impl From<StatusParamsItem> for StatusItem {
 
 pub type StatusIterator = IndexedIterator<StatusParamsItem, StatusItem>;
 
+#[repr(C)]
+pub struct ThemeFace {
+    face: *const c_char,
+    /// color name or `#rrggbb`, null to keep the frontend default
+    foreground: *const c_char,
+    background: *const c_char,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    reverse: bool,
+}
+
+fn color_cstring(color: Option<Color>) -> *const c_char {
+    match color {
+        Some(color) => cstring!(color.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+impl From<ThemeParamsFace> for ThemeFace {
+    fn from(face: ThemeParamsFace) -> ThemeFace {
+        ThemeFace {
+            face: cstring!(face.face.to_string()),
+            foreground: color_cstring(face.style.foreground),
+            background: color_cstring(face.style.background),
+            bold: face.style.bold,
+            italic: face.style.italic,
+            underline: face.style.underline,
+            reverse: face.style.reverse,
+        }
+    }
+}
+
+pub type ThemeFaceIterator = IndexedIterator<ThemeParamsFace, ThemeFace>;
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct ViewItem {
@@ -130,17 +185,35 @@ pub type ViewIterator = IndexedIterator<ViewParamsItem, ViewItem>;
 pub struct ViewLens {
     lines: *mut ViewLensLineIterator,
     first_line_num: u32,
+    /// rows continuing the line of the row before them, released along with the lens
+    continuations: *const u32,
+    continuation_count: u32,
 }
 
 impl From<ViewParamsLens> for ViewLens {
     fn from(lens: ViewParamsLens) -> ViewLens {
+        let continuations = lens
+            .continuations
+            .iter()
+            .map(|&row| row as u32)
+            .collect::<Box<[u32]>>();
         ViewLens {
             lines: raw!(ViewLensLineIterator::from(&lens.lines)),
             first_line_num: lens.first_line_num as u32,
+            continuation_count: continuations.len() as u32,
+            continuations: Box::into_raw(continuations) as *const u32,
         }
     }
 }
 
+impl Drop for ViewLens {
+    fn drop(&mut self) {
+        let count = self.continuation_count as usize;
+        let continuations = ptr::slice_from_raw_parts_mut(self.continuations as *mut u32, count);
+        drop(unsafe { Box::from_raw(continuations) });
+    }
+}
+
 pub type ViewLensIterator = IndexedIterator<ViewParamsLens, ViewLens>;
 
 pub type ViewLensLineIterator = IndexedIterator<Text, CedTextIterator>;
@@ -60,6 +60,10 @@ pub struct CedVersion {
     pre: *const c_char,
 }
 
+/// # Safety
+///
+/// The returned pointer is owned by the caller and must be released with the matching destroy
+/// function.
 #[no_mangle]
 pub unsafe extern "C" fn ced_version() -> *mut CedVersion {
     raw!(CedVersion {
@@ -87,6 +91,10 @@ fn take_last_error() -> Option<Box<dyn Error>> {
 
 /// Length of the last error message. Useful for last_error_message.
 /// If no error is pending the length will be -1.
+///
+/// # Safety
+///
+/// Must be called from the thread that triggered the error.
 #[no_mangle]
 pub unsafe extern "C" fn ced_last_error_length() -> c_int {
     LAST_ERROR.with(|last| match last.borrow().as_ref() {
@@ -98,6 +106,10 @@ pub unsafe extern "C" fn ced_last_error_length() -> c_int {
 /// Copy the last error message to the buffer, see last_error_length to get the length of the
 /// message. It returns the length of the message if the copy succeed, -1 if the buffer was a
 /// nullptr and -2 if the buffer was too small.
+///
+/// # Safety
+///
+/// `buffer` must be valid for writes of `length` bytes.
 #[no_mangle]
 pub unsafe extern "C" fn ced_last_error_message(buffer: *mut c_char, length: c_int) -> c_int {
     if buffer.is_null() {
@@ -7,6 +7,7 @@ from ctypes import (
     POINTER,
     Structure,
     Union,
+    c_bool,
     c_char_p,
     c_int,
     c_int64,
@@ -103,6 +104,8 @@ class EventType(IntEnum):
     Menu = 2
     Status = 3
     View = 4
+    Theme = 5
+    Error = 6
 
 
 class EventEcho(Structure):
@@ -141,10 +144,65 @@ class EventStatus(Structure):
         return it[0]
 
 
+class ThemeFaceIterator(Structure):
+    pass
+
+
+class ThemeFace(Structure):
+    _fields_ = [
+        ("face", c_char_p),
+        ("foreground", c_char_p),
+        ("background", c_char_p),
+        ("bold", c_bool),
+        ("italic", c_bool),
+        ("underline", c_bool),
+        ("reverse", c_bool),
+    ]
+
+    def __str__(self):
+        colors = [c.decode() if c else "-" for c in (self.foreground, self.background)]
+        return f"{self.face.decode()}({'/'.join(colors)})"
+
+
+class EventTheme(Structure):
+    _fields_ = [("name", c_char_p), ("faces", POINTER(ThemeFaceIterator))]
+
+    def __iter__(self):
+        return self
+
+    def __next__(self) -> ThemeFace:
+        it = lib.ced_theme_face_next_item(self.faces)
+        if not it:
+            raise StopIteration()
+        return it[0]
+
+
+class EventError(Structure):
+    _fields_ = [("message", c_char_p)]
+
+
 class ViewLensesIterator(Structure):
     pass
 
 
+class ViewLensLinesIterator(Structure):
+    pass
+
+
+class ViewLens(Structure):
+    _fields_ = [
+        ("lines", POINTER(ViewLensLinesIterator)),
+        ("first_line_num", c_uint32),
+        ("continuations", POINTER(c_uint32)),
+        ("continuation_count", c_uint32),
+    ]
+
+    def __str__(self):
+        count = self.continuation_count
+        continuations = [self.continuations[i] for i in range(count)]
+        return f"{self.first_line_num}{continuations}"
+
+
 class EventViewItem(Structure):
     _fields_ = [
         ("buffer", c_char_p),
@@ -153,8 +211,18 @@ class EventViewItem(Structure):
         ("lenses", POINTER(ViewLensesIterator)),
     ]
 
+    def __iter__(self):
+        return self
+
+    def __next__(self) -> ViewLens:
+        it = lib.ced_view_lens_next_item(self.lenses)
+        if not it:
+            raise StopIteration()
+        return it[0]
+
     def __str__(self):
-        return f"{self.buffer.decode()}[{self.start}:{self.end}]"
+        lenses = [str(x) for x in self]
+        return f"{self.buffer.decode()}[{self.start}:{self.end}]{lenses}"
 
 
 class ViewIterator(Structure):
@@ -181,6 +249,8 @@ class _EventBody(Union):
         ("MENU", EventMenu),
         ("STATUS", EventStatus),
         ("VIEW", EventView),
+        ("THEME", EventTheme),
+        ("ERROR", EventError),
     ]
 
 
@@ -210,6 +280,11 @@ class Event(Structure):
             s += f"items={items}"
             # for it in items:
             #     del it
+        elif self.type == EventType.Theme:
+            faces = [str(x) for x in self.THEME]
+            s += f"name={self.THEME.name.decode()!r}, faces={faces}"
+        elif self.type == EventType.Error:
+            s += f"message={self.ERROR.message.decode()!r}"
         elif self.type == EventType.View:
             items = [str(x) for x in self.VIEW]
             s += f"items={items}"
@@ -233,7 +308,9 @@ lib.ced_event_destroy.argtypes = [POINTER(Event)]
 lib.ced_status_next_item.restype = POINTER(StatusItem)
 lib.ced_text_item_destroy.argtypes = [POINTER(TextItem)]
 lib.ced_text_next_item.restype = POINTER(TextItem)
+lib.ced_theme_face_next_item.restype = POINTER(ThemeFace)
 lib.ced_view_next_item.restype = POINTER(EventViewItem)
+lib.ced_view_lens_next_item.restype = POINTER(ViewLens)
 
 
 def _last_error():
@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::{Arc, RwLock};
 
 use crate::client::{Client, ClientEventResult, ClientEventStream};
@@ -47,6 +48,7 @@ pub enum ConnectionEvent {
     Info(String, String),
     Menu(Menu),
     Status(notifications::StatusParams),
+    Theme(notifications::ThemeParams),
     View(notifications::ViewParams),
 }
 
@@ -58,11 +60,39 @@ pub struct ConnectionState {
     pub session: String,
     pub echo: Option<Text>,
     pub status: notifications::StatusParams,
+    pub theme: notifications::ThemeParams,
     pub view: notifications::ViewParams,
+    /// revision of `view`, none if it was not sent as a patch
+    pub view_revision: Option<usize>,
+    // views received since the oldest one patches can still be made against
+    view_bases: Vec<(usize, notifications::ViewParams)>,
     pub menu: Option<Menu>,
 }
 
 impl ConnectionState {
+    /// Applies the patch to the view it was made against, none if that view is unknown.
+    fn apply_view_patch(
+        &mut self,
+        patch: &notifications::ViewPatchParams,
+    ) -> Option<notifications::ViewParams> {
+        let view = match patch.base {
+            Some(base) => {
+                let index = self.view_bases.iter().position(|(rev, _)| *rev == base)?;
+                // the server does not go back to older views
+                self.view_bases.drain(..index);
+                patch.apply(&self.view_bases[0].1)
+            }
+            None => {
+                self.view_bases.clear();
+                patch.apply(&[])
+            }
+        };
+        self.view_bases.push((patch.revision, view.clone()));
+        self.view = view;
+        self.view_revision = Some(patch.revision);
+        Some(self.view.clone())
+    }
+
     fn event_update(&mut self, event: &ClientEvent) -> Option<ConnectionEvent> {
         // TODO check if ConnectionEvent is really useful
         if let ClientEvent::Notification(notif) = event {
@@ -107,14 +137,30 @@ impl ConnectionState {
                         self.status = params;
                         ConnectionEvent::Status(self.status.clone())
                     }),
+                "theme" => notif
+                    .params::<notifications::ThemeParams>()
+                    .ok()
+                    .unwrap_or(None)
+                    .map(|params| {
+                        self.theme = params;
+                        ConnectionEvent::Theme(self.theme.clone())
+                    }),
                 "view" => notif
                     .params::<notifications::ViewParams>()
                     .ok()
                     .unwrap_or(None)
                     .map(|view| {
                         self.view = view;
+                        self.view_revision = None;
+                        self.view_bases.clear();
                         ConnectionEvent::View(self.view.clone())
                     }),
+                "view-patch" => notif
+                    .params::<notifications::ViewPatchParams>()
+                    .ok()
+                    .unwrap_or(None)
+                    .and_then(|patch| self.apply_view_patch(&patch))
+                    .map(ConnectionEvent::View),
                 _ => None,
             }
         } else {
@@ -127,18 +173,22 @@ pub struct Connection {
     client: Client,
     state_lock: Arc<RwLock<ConnectionState>>,
     requests: Sender<Request>,
-    next_request_id: i32,
+    // shared with the events acknowledging the views
+    next_request_id: Arc<AtomicI32>,
 }
 
 impl Connection {
     pub fn new(session: Session) -> Connection {
         let (client, requests) = Client::new(session);
-        Connection {
+        let mut connection = Connection {
             client,
             state_lock: Default::default(),
             requests,
-            next_request_id: 0,
-        }
+            next_request_id: Default::default(),
+        };
+        let id = connection.request_id();
+        connection.request(requests::ViewPatches::new_noarg(id));
+        connection
     }
 
     pub fn state(&self) -> ConnectionState {
@@ -152,12 +202,23 @@ impl Connection {
         impl Future<Output = ()>,
     ) {
         let ctx_lock = self.state_lock.clone();
+        let acks = self.requests.clone();
+        let next_id = self.next_request_id.clone();
         let (events, request_loop) = self.client.run().await.unwrap();
         (
             events.map(move |ev| match ev {
                 Ok(e) => {
                     let mut ctx = ctx_lock.write().unwrap();
-                    ctx.event_update(&e).unwrap_or(ConnectionEvent::Noop)
+                    let event = ctx.event_update(&e).unwrap_or(ConnectionEvent::Noop);
+                    if let (ConnectionEvent::View(_), Some(revision)) = (&event, ctx.view_revision)
+                    {
+                        // patches are then made against this view
+                        let id = next_request_id(&next_id);
+                        if let Err(e) = acks.try_send(requests::ViewAck::new(id, revision)) {
+                            return ConnectionEvent::ConnErr(e.to_string());
+                        }
+                    }
+                    event
                 }
                 Err(e) => ConnectionEvent::ConnErr(e.to_string()),
             }),
@@ -166,15 +227,33 @@ impl Connection {
     }
 
     fn request_id(&mut self) -> Id {
-        let id = self.next_request_id;
-        self.next_request_id += 1;
-        Id::Number(id)
+        next_request_id(&self.next_request_id)
     }
 
     fn request(&mut self, message: Request) {
         future::block_on(self.requests.send(message)).expect("send request");
     }
 
+    pub fn undo(&mut self) {
+        let id = self.request_id();
+        self.request(requests::Undo::new_noarg(id));
+    }
+
+    pub fn redo(&mut self) {
+        let id = self.request_id();
+        self.request(requests::Redo::new_noarg(id));
+    }
+
+    pub fn jump_back(&mut self) {
+        let id = self.request_id();
+        self.request(requests::JumpBack::new_noarg(id));
+    }
+
+    pub fn jump_forward(&mut self) {
+        let id = self.request_id();
+        self.request(requests::JumpForward::new_noarg(id));
+    }
+
     pub fn quit(&mut self) {
         let id = self.request_id();
         self.request(requests::Quit::new_noarg(id));
@@ -239,8 +318,60 @@ impl Connection {
         self.request(requests::Keys::new(id, keys));
     }
 
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let id = self.request_id();
+        self.request(requests::Resize::new(
+            id,
+            requests::ResizeParams { height, width },
+        ));
+    }
+
+    pub fn write(&mut self, force: bool) {
+        let id = self.request_id();
+        let params = requests::WriteParams {
+            buffer: None,
+            force,
+        };
+        self.request(requests::Write::new(id, params));
+    }
+
+    pub fn write_all(&mut self, force: bool) {
+        let id = self.request_id();
+        let params = requests::WriteParams {
+            buffer: None,
+            force,
+        };
+        self.request(requests::WriteAll::new(id, params));
+    }
+
+    pub fn session_save(&mut self, name: Option<String>) {
+        let id = self.request_id();
+        let params = requests::SessionParams { name };
+        self.request(requests::SessionSave::new(id, params));
+    }
+
+    pub fn session_restore(&mut self, name: Option<String>) {
+        let id = self.request_id();
+        let params = requests::SessionParams { name };
+        self.request(requests::SessionRestore::new(id, params));
+    }
+
+    pub fn macro_replay(&mut self, client: Option<usize>, register: Option<String>, count: usize) {
+        let id = self.request_id();
+        let params = requests::MacroReplayParams {
+            client,
+            register,
+            count,
+        };
+        self.request(requests::MacroReplay::new(id, params));
+    }
+
     pub fn exec(&mut self, source: &str) {
         let id = self.request_id();
         self.request(requests::Exec::new(id, source.to_owned()));
     }
 }
+
+fn next_request_id(next: &AtomicI32) -> Id {
+    Id::Number(next.fetch_add(1, Ordering::Relaxed))
+}
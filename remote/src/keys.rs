@@ -13,6 +13,8 @@ pub enum Key {
     Down,
     Left,
     Right,
+    PageUp,
+    PageDown,
 }
 
 impl Default for Key {
@@ -35,6 +37,8 @@ impl fmt::Display for Key {
             Down => f.write_str("down"),
             Left => f.write_str("left"),
             Right => f.write_str("right"),
+            PageUp => f.write_str("pgup"),
+            PageDown => f.write_str("pgdn"),
         }
     }
 }
@@ -44,6 +48,12 @@ pub struct ParseKeyError {
     raw_source: String,
 }
 
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid key: {}", self.raw_source)
+    }
+}
+
 impl FromStr for Key {
     type Err = ParseKeyError;
 
@@ -53,7 +63,16 @@ impl FromStr for Key {
         }
 
         match s {
+            "bkspc" => Ok(Key::Backspace),
+            "ret" => Ok(Key::Enter),
             "esc" => Ok(Key::Escape),
+
+            "up" => Ok(Key::Up),
+            "down" => Ok(Key::Down),
+            "left" => Ok(Key::Left),
+            "right" => Ok(Key::Right),
+            "pgup" => Ok(Key::PageUp),
+            "pgdn" => Ok(Key::PageDown),
             _ => Err(ParseKeyError {
                 raw_source: s.to_owned(),
             }),
@@ -78,8 +97,10 @@ impl From<Key> for KeyEvent {
     }
 }
 
-impl From<&str> for KeyEvent {
-    fn from(s: &str) -> KeyEvent {
+impl FromStr for KeyEvent {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut event = KeyEvent::default();
         let mut cursor = 0;
         if s[cursor..].starts_with("c-") {
@@ -94,13 +115,14 @@ impl From<&str> for KeyEvent {
             event.shift = true;
             cursor += 2;
         }
-        event.key = s
-            .chars()
-            .skip(cursor)
-            .collect::<String>()
-            .parse()
-            .expect("extract key value");
-        event
+        event.key = s[cursor..].parse()?;
+        Ok(event)
+    }
+}
+
+impl From<&str> for KeyEvent {
+    fn from(s: &str) -> KeyEvent {
+        s.parse().expect("extract key value")
     }
 }
 
@@ -191,5 +213,15 @@ mod tests {
             };
             assert_eq!(k, KeyEvent::from(k.to_string().as_str()));
         }
+        {
+            let k = KeyEvent {
+                shift: true,
+                key: Key::Enter,
+                ..Default::default()
+            };
+            assert_eq!(k, KeyEvent::from(k.to_string().as_str()));
+        }
+        assert!("c-".parse::<KeyEvent>().is_err());
+        assert!("a-enter".parse::<KeyEvent>().is_err());
     }
 }
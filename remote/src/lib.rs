@@ -84,7 +84,7 @@ pub fn start_daemon(session: &Session) -> io::Result<u32> {
 pub fn ensure_session(session: &Session) -> io::Result<()> {
     if let ConnectionMode::Socket(path) = &session.mode {
         if !path.exists() {
-            start_daemon(&session)?;
+            start_daemon(session)?;
         }
     }
     Ok(())
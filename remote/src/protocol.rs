@@ -1,14 +1,24 @@
+use std::convert::TryFrom;
 use std::fmt;
+use std::str::FromStr;
 
 pub use crate::keys::{Key, KeyEvent};
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Face {
+    #[default]
     Default,
     Error,
     Match,
     Prompt,
     Selection,
+    Comment,
+    Constant,
+    Function,
+    Keyword,
+    Number,
+    String,
+    Type,
 }
 
 // used in ffi to convert enum value to string
@@ -18,23 +28,138 @@ impl fmt::Display for Face {
     }
 }
 
-impl Default for Face {
-    fn default() -> Face {
-        Face::Default
+impl Face {
+    pub const ALL: [Face; 12] = [
+        Face::Default,
+        Face::Error,
+        Face::Match,
+        Face::Prompt,
+        Face::Selection,
+        Face::Comment,
+        Face::Constant,
+        Face::Function,
+        Face::Keyword,
+        Face::Number,
+        Face::String,
+        Face::Type,
+    ];
+}
+
+/// A color of the terminal palette or a RGB color, written as its name or as `#rrggbb`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Color {
+    Black,
+    DarkGrey,
+    Red,
+    DarkRed,
+    Green,
+    DarkGreen,
+    Yellow,
+    DarkYellow,
+    Blue,
+    DarkBlue,
+    Magenta,
+    DarkMagenta,
+    Cyan,
+    DarkCyan,
+    White,
+    Grey,
+    Rgb(u8, u8, u8),
+}
+
+const COLOR_NAMES: [(&str, Color); 16] = [
+    ("black", Color::Black),
+    ("dark_grey", Color::DarkGrey),
+    ("red", Color::Red),
+    ("dark_red", Color::DarkRed),
+    ("green", Color::Green),
+    ("dark_green", Color::DarkGreen),
+    ("yellow", Color::Yellow),
+    ("dark_yellow", Color::DarkYellow),
+    ("blue", Color::Blue),
+    ("dark_blue", Color::DarkBlue),
+    ("magenta", Color::Magenta),
+    ("dark_magenta", Color::DarkMagenta),
+    ("cyan", Color::Cyan),
+    ("dark_cyan", Color::DarkCyan),
+    ("white", Color::White),
+    ("grey", Color::Grey),
+];
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            color => {
+                let (name, _) = COLOR_NAMES.iter().find(|(_, c)| c == color).unwrap();
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid color: {}", s);
+        match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.is_ascii() => {
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+                match (channel(0), channel(2), channel(4)) {
+                    (Ok(r), Ok(g), Ok(b)) => Ok(Color::Rgb(r, g, b)),
+                    _ => Err(invalid()),
+                }
+            }
+            Some(_) => Err(invalid()),
+            None => COLOR_NAMES
+                .iter()
+                .find(|(name, _)| *name == s)
+                .map(|(_, color)| *color)
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        color.to_string()
     }
 }
 
+/// How the text of a face is shown, the frontend defaults being kept for what is not set.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
 pub type Text = ornament::Text<Face>;
 pub type TextFragment = ornament::TextFragment<Face>;
 
 pub mod notifications {
     use crate::jsonrpc::Notification as JNotification;
-    use crate::protocol::Text;
+    use crate::protocol::{Face, Style, Text};
 
     pub trait Notification {
         const METHOD: &'static str;
         type Params: serde::Serialize;
 
+        #[allow(clippy::new_ret_no_self)]
         fn new(p: impl Into<Self::Params>) -> JNotification {
             let params: Self::Params = p.into();
             JNotification::new(Self::METHOD, params)
@@ -62,7 +187,9 @@ pub mod notifications {
     notification!(Info, "info", InfoParams);
     notification!(Menu, "menu", MenuParams);
     notification!(Status, "status", StatusParams);
+    notification!(Theme, "theme", ThemeParams);
     notification!(View, "view", ViewParams);
+    notification!(ViewPatch, "view-patch", ViewPatchParams);
 
     #[derive(Debug, Serialize, Deserialize)]
     pub struct HintParams {
@@ -99,10 +226,38 @@ pub mod notifications {
 
     pub type StatusParams = Vec<StatusParamsItem>;
 
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct ThemeParamsFace {
+        pub face: Face,
+        pub style: Style,
+    }
+
+    /// Styles of the faces in the theme of a client.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct ThemeParams {
+        pub name: String,
+        pub faces: Vec<ThemeParamsFace>,
+    }
+
+    impl ThemeParams {
+        /// Style of `face`, the default one if the theme has none for it.
+        pub fn style(&self, face: &Face) -> Style {
+            self.faces
+                .iter()
+                .find(|f| f.face == *face)
+                .map(|f| f.style.clone())
+                .unwrap_or_default()
+        }
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct ViewParamsLens {
+        /// rows showing the lines, several of them for a wrapped line
         pub lines: Vec<Text>,
         pub first_line_num: usize,
+        /// rows continuing the line of the row before them
+        #[serde(default)]
+        pub continuations: Vec<usize>,
     }
 
     #[derive(Clone, Default, Debug, Serialize, Deserialize)]
@@ -114,6 +269,82 @@ pub mod notifications {
     }
 
     pub type ViewParams = Vec<ViewParamsItem>;
+
+    /// Lines replacing the `removed` lines of a lens from `start`, counted once the previous
+    /// changes of the lens are made.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct ViewPatchLines {
+        pub start: usize,
+        pub removed: usize,
+        pub lines: Vec<Text>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct ViewPatchLens {
+        pub first_line_num: usize,
+        /// lens of the same buffer in the base view whose lines are changed, none to start
+        /// from no line
+        pub base: Option<usize>,
+        pub changes: Vec<ViewPatchLines>,
+        #[serde(default)]
+        pub continuations: Vec<usize>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct ViewPatchItem {
+        pub buffer: String,
+        pub start: usize,
+        pub end: usize,
+        pub lenses: Vec<ViewPatchLens>,
+    }
+
+    /// Changes turning the view of revision `base` into the view of revision `revision`.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct ViewPatchParams {
+        /// revision of the view the patch applies to, none for an empty view
+        pub base: Option<usize>,
+        pub revision: usize,
+        pub items: Vec<ViewPatchItem>,
+    }
+
+    impl ViewPatchParams {
+        /// Applies the patch to `base`, the view of revision `self.base`.
+        pub fn apply(&self, base: &[ViewParamsItem]) -> ViewParams {
+            self.items
+                .iter()
+                .map(|item| {
+                    let old = base.iter().find(|old| old.buffer == item.buffer);
+                    let lenses = item
+                        .lenses
+                        .iter()
+                        .map(|lens| {
+                            let mut lines = lens
+                                .base
+                                .and_then(|i| old?.lenses.get(i))
+                                .map(|old| old.lines.clone())
+                                .unwrap_or_default();
+                            for change in &lens.changes {
+                                let end = (change.start + change.removed).min(lines.len());
+                                let start = change.start.min(end);
+                                lines.splice(start..end, change.lines.iter().cloned());
+                            }
+                            ViewParamsLens {
+                                lines,
+                                first_line_num: lens.first_line_num,
+                                continuations: lens.continuations.clone(),
+                            }
+                        })
+                        .collect();
+                    ViewParamsItem {
+                        buffer: item.buffer.to_owned(),
+                        start: item.start,
+                        end: item.end,
+                        lenses,
+                    }
+                })
+                .collect()
+        }
+    }
 }
 
 pub mod requests {
@@ -125,6 +356,7 @@ pub mod requests {
         type Params: serde::Serialize;
         type Result;
 
+        #[allow(clippy::new_ret_no_self)]
         fn new(id: Id, p: impl Into<Self::Params>) -> JRequest {
             let params: Self::Params = p.into();
             JRequest::new(id, Self::METHOD, params)
@@ -149,15 +381,28 @@ pub mod requests {
     }
 
     request!(Quit, "quit", (), ());
+    request!(Undo, "undo", (), ());
+    request!(Redo, "redo", (), ());
+    request!(JumpBack, "jump-back", (), ());
+    request!(JumpForward, "jump-forward", (), ());
     request!(Edit, "edit", EditParams, ());
     request!(View, "view", String, ());
     request!(ViewDelete, "view-delete", (), ());
     request!(ViewAdd, "view-add", String, ());
     request!(ViewRemove, "view-remove", String, ());
+    request!(ViewAck, "view-ack", usize, ());
+    // views are sent whole until a client asks for patches
+    request!(ViewPatches, "view-patches", (), ());
     request!(Menu, "menu", MenuParams, ());
     request!(MenuSelect, "menu-select", MenuSelectParams, ());
     request!(Keys, "keys", Vec<KeyEvent>, ());
+    request!(Resize, "resize", ResizeParams, ());
+    request!(MacroReplay, "macro-replay", MacroReplayParams, ());
     request!(Exec, "exec", String, ());
+    request!(Write, "write", WriteParams, ());
+    request!(WriteAll, "write-all", WriteParams, ());
+    request!(SessionSave, "session-save", SessionParams, ());
+    request!(SessionRestore, "session-restore", SessionParams, ());
 
     #[derive(Serialize, Deserialize)]
     pub struct EditParams {
@@ -171,9 +416,53 @@ pub mod requests {
         pub search: String,
     }
 
+    #[derive(Serialize, Deserialize)]
+    pub struct WriteParams {
+        pub buffer: Option<String>,
+        pub force: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ResizeParams {
+        /// number of rows the view is shown in, the header of each lens taking one
+        pub height: usize,
+        /// number of columns the lines are shown in
+        pub width: usize,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MacroReplayParams {
+        /// client replaying the macro, the requesting one if none
+        pub client: Option<usize>,
+        /// register holding the macro, "@" if none
+        pub register: Option<String>,
+        /// times to replay it, once if 0
+        pub count: usize,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct SessionParams {
+        pub name: Option<String>,
+    }
+
     #[derive(Serialize, Deserialize)]
     pub struct MenuSelectParams {
         pub command: String,
         pub choice: String,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert_eq!("dark_red".parse(), Ok(Color::DarkRed));
+        assert_eq!("#ff8000".parse(), Ok(Color::Rgb(255, 128, 0)));
+        assert!("#ff80".parse::<Color>().is_err());
+        assert!("orange".parse::<Color>().is_err());
+        assert_eq!(Color::Rgb(255, 128, 0).to_string(), "#ff8000");
+        assert_eq!(Color::Grey.to_string(), "grey");
+    }
+}
@@ -89,11 +89,19 @@ impl Session {
         Self::from_name(&std::process::id().to_string())
     }
 
+    /// Directory shared by all the sessions to keep the journals of unsaved changes.
+    pub fn swap_dir() -> PathBuf {
+        Self::build_root().join("swap")
+    }
+
     pub fn list() -> Vec<String> {
         match fs::read_dir(Self::build_root()) {
             Ok(entries) => entries
                 .filter_map(|entry| {
                     entry.ok().and_then(|e| {
+                        if e.file_type().map_or(true, |ft| ft.is_dir()) {
+                            return None;
+                        }
                         e.path()
                             .file_name()
                             .and_then(|n| n.to_str().map(String::from))